@@ -1,14 +1,36 @@
 import type {LoaderFunction, LoaderFunctionArgs} from "react-router";
 import {redirect} from "react-router"
 import {createContext, useContext} from "react";
+import {solveChallenge} from "./challenge.ts";
 
 export interface AuthUser {
     username: string;
+    csrfToken: string;
+}
+
+let csrfToken: string | null = null;
+
+/**
+ * Headers every mutating request has to carry while a session is active.
+ */
+export const csrfHeaders = (): Record<string, string> => {
+    return csrfToken ? {"X-CSRF-Token": csrfToken} : {};
 }
 
 interface AuthResult {
     success: boolean;
     error?: string;
+    /**
+     * Set when the password was right but the account has two-factor authentication enabled.
+     * The login is finished by passing it to {@link AuthProvider.completeTwoFactor} with a code.
+     */
+    pendingToken?: string;
+}
+
+interface PendingLogin {
+    twoFactorRequired: boolean,
+    pendingToken: string,
+    expiresIn: number,
 }
 
 interface ApiError {
@@ -20,6 +42,8 @@ interface AuthProvider {
 
     signIn(username: string, password: string): Promise<AuthResult>,
 
+    completeTwoFactor(pendingToken: string, code: string): Promise<AuthResult>,
+
     checkAuth(): Promise<AuthUser | null>,
 
     signOut(): Promise<AuthResult>,
@@ -34,8 +58,11 @@ export const authProvider: AuthProvider = {
         let response = await fetch("/api/auth/me", {credentials: "include"});
 
         if (response.ok) {
-            return await response.json() as AuthUser;
+            const user = await response.json() as AuthUser;
+            csrfToken = user.csrfToken;
+            return user;
         } else {
+            csrfToken = null;
             return null;
         }
     },
@@ -45,7 +72,8 @@ export const authProvider: AuthProvider = {
             credentials: "include",
             method: "POST",
             headers: {
-                "Content-Type": "application/json"
+                "Content-Type": "application/json",
+                ...csrfHeaders(),
             },
             body: JSON.stringify({
                 username: username,
@@ -56,14 +84,40 @@ export const authProvider: AuthProvider = {
             await this.checkAuth();
             return {success: true};
         }
+        if (response.status == 200) {
+            const pending = await response.json() as PendingLogin;
+            return {success: false, pendingToken: pending.pendingToken};
+        }
         const body = await response.json() as ApiError;
         return {success: false, error: body.message};
     },
 
+    async completeTwoFactor(pendingToken: string, code: string): Promise<AuthResult> {
+        let response = await fetch("/api/auth/login/2fa", {
+            credentials: "include",
+            method: "POST",
+            headers: {
+                "Content-Type": "application/json",
+                ...csrfHeaders(),
+            },
+            body: JSON.stringify({
+                pendingToken: pendingToken,
+                code: code,
+            })
+        });
+        if (response.status == 204) {
+            await this.checkAuth();
+            return {success: true};
+        }
+        const body = await response.json() as ApiError;
+        return {success: false, error: body.message, pendingToken: pendingToken};
+    },
+
     async signOut(): Promise<AuthResult> {
         let response = await fetch("/api/auth/logout", {
             credentials: "include",
-            method: "POST"
+            method: "POST",
+            headers: csrfHeaders(),
         });
 
         if (response.status == 204) {
@@ -80,12 +134,14 @@ export const authProvider: AuthProvider = {
     },
 
     async signUp(username: string, password: string): Promise<AuthResult> {
+        const solved = await solveChallenge("signup");
         const response = await fetch("/api/auth/signup", {
             method: "POST",
-            headers: {"Content-Type": "application/json"},
+            headers: {"Content-Type": "application/json", ...csrfHeaders()},
             body: JSON.stringify({
                 username,
-                password
+                password,
+                ...solved
             })
         });
         if (response.status == 204) {
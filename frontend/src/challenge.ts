@@ -0,0 +1,42 @@
+interface Challenge {
+    challenge: string;
+    difficulty: number;
+    expiresAt: string;
+    required: boolean;
+}
+
+export interface ChallengeSolution {
+    challenge: string;
+    solution: string;
+}
+
+const leadingZeroBits = (digest: Uint8Array): number => {
+    let bits = 0;
+    for (const byte of digest) {
+        if (byte === 0) {
+            bits += 8;
+            continue;
+        }
+        return bits + Math.clz32(byte) - 24;
+    }
+    return bits;
+}
+
+/**
+ * Fetches a proof-of-work challenge and searches for a solution. Resolves to null when the
+ * server doesn't ask for one.
+ */
+export const solveChallenge = async (purpose: "signup" | "post"): Promise<ChallengeSolution | null> => {
+    const response = await fetch(`/api/auth/challenge?purpose=${purpose}`, {credentials: "include"});
+    if (!response.ok) return null;
+
+    const {challenge, difficulty, required} = await response.json() as Challenge;
+    if (!required) return null;
+
+    const encoder = new TextEncoder();
+    for (let counter = 0; ; counter++) {
+        const solution = counter.toString(16);
+        const digest = await crypto.subtle.digest("SHA-256", encoder.encode(challenge + solution));
+        if (leadingZeroBits(new Uint8Array(digest)) >= difficulty) return {challenge, solution};
+    }
+}
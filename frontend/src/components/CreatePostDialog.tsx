@@ -61,7 +61,10 @@ export function CreatePostDialog() {
 
         setOpenModal(false);
         methods.reset();
-        if (actionData.success) {
+        if (actionData.success && actionData.heldReason) {
+            toast(`Your post is waiting for a moderator: ${actionData.heldReason}`,
+                {icon: "⏳", removeDelay: 5000, position: "top-right"});
+        } else if (actionData.success) {
             toast.success("Post published", {removeDelay: 5000, position: "top-right"});
         } else {
             toast.error("An error occurred", {removeDelay: 5000, position: "top-right"});
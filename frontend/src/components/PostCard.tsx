@@ -7,7 +7,7 @@ export interface Post {
     title: string,
     body: string,
     date: Date,
-    username: string,
+    username: string | null,
 }
 
 export default function PostCard(props: Post) {
@@ -27,8 +27,9 @@ export default function PostCard(props: Post) {
     return (
         <div className="relative w-full max-w-2xl mx-auto my-6 p-4 rounded-2xl shadow-lg bg-white">
             <h2 className="text-2xl font-semibold truncate">{props.title}</h2>
-            <p className="text-sm mb-2">by <Link to={`/users/${props.username}`}
-                                                 className="italic text-sm">{props.username}</Link> - {format(props.date, "MMM do yyyy p")}
+            <p className="text-sm mb-2">by {props.username
+                ? <Link to={`/users/${props.username}`} className="italic text-sm">{props.username}</Link>
+                : <span className="italic text-sm text-gray-500">[deleted]</span>} - {format(props.date, "MMM do yyyy p")}
             </p>
             <div ref={contentRef}
                  className={`transition-all duration-500 ${
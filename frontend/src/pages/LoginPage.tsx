@@ -14,20 +14,27 @@ import {yupResolver} from "@hookform/resolvers/yup";
 import {type HTMLInputTypeAttribute, useEffect} from "react";
 import {LoadingSpinner} from "../components/LoadingSpinner.tsx";
 
+type LoginActionData = {
+    error?: string,
+    pendingToken?: string,
+}
+
 export async function action({request}: ActionFunctionArgs) {
     const formData = await request.formData();
-    const username = formData.get("username") as string;
-    const password = formData.get("password") as string;
     const redirectTo = formData.get("redirectTo") as string;
+    const pendingToken = formData.get("pendingToken") as string | null;
 
-    const result = await authProvider.signIn(username, password);
+    const result = pendingToken
+        ? await authProvider.completeTwoFactor(pendingToken, formData.get("code") as string)
+        : await authProvider.signIn(formData.get("username") as string, formData.get("password") as string);
 
     if (result.success) {
         return redirect(redirectTo);
     }
     return {
-        error: result.error
-    }
+        error: result.error,
+        pendingToken: result.pendingToken,
+    } satisfies LoginActionData;
 }
 
 export async function loader() {
@@ -62,12 +69,73 @@ export function LoadingOverlay() {
     </div>;
 }
 
+type TwoFactorForm = {
+    code: string,
+}
+
+/**
+ * Second step of the login for accounts with two-factor authentication: asks for a code from
+ * the authenticator app or one of the recovery codes.
+ */
+function TwoFactorStep(props: { pendingToken: string, redirectTo: string, error?: string }) {
+    const submit = useSubmit();
+    const navigation = useNavigation();
+    const isVerifying = navigation.formData?.get("pendingToken") != null;
+
+    const validationSchema = Yup.object({
+        code: Yup.string().trim().required("Code is required"),
+    });
+
+    const {register, handleSubmit, setError, formState: {errors}} = useForm<TwoFactorForm>({
+        resolver: yupResolver(validationSchema),
+    });
+
+    useEffect(() => {
+        if (props.error) setError("code", {message: props.error});
+    }, [props.error]);
+
+    const onSubmit = async (data: TwoFactorForm) => {
+        await submit({...data, pendingToken: props.pendingToken, redirectTo: props.redirectTo}, {method: "post"});
+    }
+
+    return (
+        <div className="flex items-center justify-center m-auto min-w-xs max-w-sm relative">
+            <form className="w-full p-6 bg-white rounded-2xl shadow-md space-y-5"
+                  onSubmit={handleSubmit(onSubmit)}>
+                <h2 className="text-2xl font-semibold text-center">Two-factor authentication</h2>
+                <p className="text-sm text-gray-600">
+                    Enter the code from your authenticator app or one of your recovery codes.
+                </p>
+                <div>
+                    {errors.code?.message &&
+                        <label htmlFor="code"
+                               className="block text-sm font-medium text-red-400">{errors.code.message}</label>}
+                    {!errors.code?.message &&
+                        <label htmlFor="code" className="block text-sm font-medium text-gray-700">Code</label>}
+                    <input {...register("code")} id="code" autoComplete="one-time-code" autoFocus
+                           className="mt-1 w-full px-4 py-2 border border-gray-300 rounded-lg
+                               focus:outline-none focus:ring-2 focus:ring-blue-500" type="text"/>
+                </div>
+                <button type="submit" className="w-full py-2 bg-blue-600 text-white
+                    rounded-lg hover:bg-blue-700 transition-colors ease-in duration-200"
+                        disabled={isVerifying}>{isVerifying ? "Verifying..." : "Verify"}
+                </button>
+            </form>
+            {isVerifying && <LoadingOverlay/>}
+        </div>
+    );
+}
+
 export default function LoginPage() {
     const submit = useSubmit();
     const location = useLocation();
     const params = new URLSearchParams(location.search);
-    const redirectTo = params.get("redirectTo") || "/";
-    const actionData = useActionData() as { error: string } | undefined;
+    // A login through an identity provider lands here with a pending two-factor login in the
+    // fragment when the account needs a second factor.
+    const fragment = new URLSearchParams(location.hash.slice(1));
+    const redirectTo = params.get("redirectTo") || fragment.get("redirectTo") || "/";
+    const actionData = useActionData() as LoginActionData | undefined;
+    const pendingToken = actionData?.pendingToken ?? fragment.get("pendingToken");
 
     const validationSchema = Yup.object({
         username: Yup.string().required("Username is required"),
@@ -80,7 +148,7 @@ export default function LoginPage() {
     });
 
     useEffect(() => {
-        methods.setError("username", {message: actionData?.error});
+        if (!actionData?.pendingToken) methods.setError("username", {message: actionData?.error});
     }, [actionData]);
 
 
@@ -91,6 +159,11 @@ export default function LoginPage() {
         await submit({...data, redirectTo}, {method: "post"});
     }
 
+    if (pendingToken) {
+        return <TwoFactorStep pendingToken={pendingToken} redirectTo={redirectTo}
+                              error={actionData?.error}/>;
+    }
+
     return (
         <FormProvider {...methods}>
             <div className="flex items-center justify-center m-auto min-w-xs max-w-sm relative">
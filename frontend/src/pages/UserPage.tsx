@@ -1,8 +1,9 @@
-import {type ActionFunctionArgs, type LoaderFunctionArgs, useLoaderData} from "react-router";
+import {type ActionFunctionArgs, type LoaderFunctionArgs, redirect, useLoaderData} from "react-router";
 import type {Post} from "../components/PostCard.tsx";
 import {useCallback, useEffect, useRef, useState} from "react";
 import {PostsPaginatorBar} from "../components/Paginator.tsx";
-import {useAuthContext} from "../auth.ts";
+import {csrfHeaders, useAuthContext} from "../auth.ts";
+import {solveChallenge} from "../challenge.ts";
 import {EditableProfilePicture, ViewerProfilePicture} from "../components/ProfilePicture.tsx";
 import {format} from "date-fns";
 import PostLoadingSkeleton from "../components/PostLoadingSkeleton.tsx";
@@ -12,6 +13,11 @@ import DeletePostDialog from "../components/DeletePostDialog.tsx";
 interface UserDTO {
     username: string,
     joined: Date,
+    displayName: string | null,
+    bio: string | null,
+    links: string[],
+    location: string | null,
+    pronouns: string | null,
     posts: Post[],
     totalPosts: number,
 }
@@ -25,6 +31,8 @@ export async function loader({params, request}: LoaderFunctionArgs) {
     if (!response.ok) throw new Response("", {status: 404, statusText: "User not found"});
 
     const user = await response.json() as UserDTO;
+    // The API follows renamed accounts, keep the address bar on the current name.
+    if (response.redirected) throw redirect(`/users/${user.username}?page=${page}`);
 
     return {user, page};
 }
@@ -32,6 +40,8 @@ export async function loader({params, request}: LoaderFunctionArgs) {
 export type UserActionResult = {
     success: boolean,
     error?: string,
+    /** Set when a new post was accepted but waits for a moderator. */
+    heldReason?: string,
     type: "post" | "avatar" | "delete-post"
 }
 
@@ -45,21 +55,34 @@ export async function action({params, request}: ActionFunctionArgs): Promise<Use
         const result = await fetch(`/api/posts/${id}`, {
             method: "DELETE",
             credentials: "include",
+            headers: csrfHeaders(),
         });
         if (result.ok) return {success: true, type: "delete-post"};
         return {success: false, type: "delete-post"};
     }
 
     if (type === "post") {
+        const solved = await solveChallenge("post");
+        if (solved) {
+            formData.append("challenge", solved.challenge);
+            formData.append("solution", solved.solution);
+        }
+
         const result = await fetch("/api/posts/create", {
             method: "POST",
             body: formData,
             credentials: "include",
+            headers: csrfHeaders(),
         });
 
         if (!result.ok) {
             return {success: false, error: "TODO: Comprehensive error here.", type: "post"}
         }
+        // 202 means the post was saved but is held for review.
+        if (result.status === 202) {
+            const post = await result.json() as { heldReason: string };
+            return {success: true, heldReason: post.heldReason, type: "post"};
+        }
 
         return {success: true, type: "post"};
     }
@@ -69,6 +92,7 @@ export async function action({params, request}: ActionFunctionArgs): Promise<Use
             method: "POST",
             body: formData,
             credentials: "include",
+            headers: csrfHeaders(),
         });
         if (result.ok) return {success: true, type: "avatar"};
         return {success: false, error: "TODO", type: "avatar"};
@@ -101,10 +125,22 @@ export default function UserPage() {
                 {!isCurrentUser &&
                     <ViewerProfilePicture username={data.user.username}/>}
                 <div className="flex flex-col items-center md:items-start">
-                    <p className="block font-bold text-5xl">{data.user.username}</p>
-                    <p className="italic text-gray-400 text-lg">est. {format(data.user.joined, "MMM do yyyy")}</p>
+                    <p className="block font-bold text-5xl">{data.user.displayName ?? data.user.username}</p>
+                    {data.user.displayName && <p className="text-gray-500 text-lg">@{data.user.username}</p>}
+                    <p className="italic text-gray-400 text-lg">
+                        est. {format(data.user.joined, "MMM do yyyy")}
+                        {data.user.pronouns && ` · ${data.user.pronouns}`}
+                        {data.user.location && ` · ${data.user.location}`}
+                    </p>
                 </div>
             </div>
+            {data.user.bio && <p className="mt-4 whitespace-pre-line break-words">{data.user.bio}</p>}
+            {data.user.links.length > 0 && <ul className="mt-2 flex flex-wrap gap-x-4">
+                {data.user.links.map(link => <li key={link}>
+                    <a href={link} rel="nofollow noopener noreferrer" target="_blank"
+                       className="text-blue-600 hover:underline break-all">{link}</a>
+                </li>)}
+            </ul>}
             <hr className="my-4"/>
             <div className="flex flex-col space-y-6 items-center w-full">
                 <PostsPaginatorBar totalPosts={data.user.totalPosts}
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE password_reset_tokens
+(
+    token_hash VARCHAR   NOT NULL PRIMARY KEY,
+    username   VARCHAR   NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at    TIMESTAMP,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+ALTER TABLE users
+    ADD COLUMN email          VARCHAR UNIQUE,
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE email_verification_tokens
+(
+    token_hash VARCHAR   NOT NULL PRIMARY KEY,
+    username   VARCHAR   NOT NULL,
+    email      VARCHAR   NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE pending_logins;
+DROP TABLE recovery_codes;
+
+ALTER TABLE users
+    DROP COLUMN totp_last_step,
+    DROP COLUMN totp_enabled,
+    DROP COLUMN totp_secret;
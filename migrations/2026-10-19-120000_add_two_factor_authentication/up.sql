@@ -0,0 +1,23 @@
+-- Your SQL goes here
+ALTER TABLE users
+    ADD COLUMN totp_secret    VARCHAR,
+    ADD COLUMN totp_enabled   BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN totp_last_step BIGINT;
+
+CREATE TABLE recovery_codes
+(
+    id        SERIAL PRIMARY KEY,
+    username  VARCHAR NOT NULL,
+    code_hash VARCHAR NOT NULL,
+    used_at   TIMESTAMP,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
+
+CREATE TABLE pending_logins
+(
+    token_hash VARCHAR   NOT NULL PRIMARY KEY,
+    username   VARCHAR   NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    attempts   INT4      NOT NULL DEFAULT 0,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE webauthn_credentials
+(
+    credential_id VARCHAR   NOT NULL PRIMARY KEY,
+    username      VARCHAR   NOT NULL,
+    name          VARCHAR   NOT NULL,
+    passkey       TEXT      NOT NULL,
+    sign_count    BIGINT    NOT NULL DEFAULT 0,
+    created_at    TIMESTAMP NOT NULL,
+    last_used_at  TIMESTAMP,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
+
+CREATE TABLE webauthn_challenges
+(
+    challenge_id VARCHAR   NOT NULL PRIMARY KEY,
+    username     VARCHAR   NOT NULL,
+    ceremony     VARCHAR   NOT NULL,
+    state        TEXT      NOT NULL,
+    expires_at   TIMESTAMP NOT NULL,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
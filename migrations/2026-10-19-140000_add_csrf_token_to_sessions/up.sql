@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Existing sessions get a random token so that logged-in users aren't thrown out.
+ALTER TABLE sessions ADD COLUMN csrf_token VARCHAR NOT NULL DEFAULT md5(random()::text || clock_timestamp()::text);
+ALTER TABLE sessions ALTER COLUMN csrf_token DROP DEFAULT;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE api_tokens
+(
+    id           SERIAL    NOT NULL PRIMARY KEY,
+    username     VARCHAR   NOT NULL,
+    name         VARCHAR   NOT NULL,
+    token_hash   VARCHAR   NOT NULL UNIQUE,
+    scopes       TEXT[]    NOT NULL,
+    created_at   TIMESTAMP NOT NULL,
+    expires_at   TIMESTAMP,
+    last_used_at TIMESTAMP,
+    FOREIGN KEY (username) REFERENCES users (username)
+);
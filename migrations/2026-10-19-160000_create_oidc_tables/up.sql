@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE oidc_identities
+(
+    provider      VARCHAR   NOT NULL,
+    subject       VARCHAR   NOT NULL,
+    username      VARCHAR   NOT NULL,
+    email         VARCHAR,
+    created_at    TIMESTAMP NOT NULL,
+    last_login_at TIMESTAMP,
+    PRIMARY KEY (provider, subject),
+    FOREIGN KEY (username) REFERENCES users (username)
+);
+
+CREATE TABLE oidc_states
+(
+    state_hash    VARCHAR   NOT NULL PRIMARY KEY,
+    provider      VARCHAR   NOT NULL,
+    nonce         VARCHAR   NOT NULL,
+    pkce_verifier VARCHAR   NOT NULL,
+    link_username VARCHAR,
+    redirect_to   VARCHAR   NOT NULL,
+    expires_at    TIMESTAMP NOT NULL,
+    FOREIGN KEY (link_username) REFERENCES users (username)
+);
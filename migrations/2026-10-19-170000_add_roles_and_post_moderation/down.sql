@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE moderation_actions;
+ALTER TABLE posts DROP COLUMN hidden_by;
+ALTER TABLE posts DROP COLUMN hidden_at;
+ALTER TABLE users DROP COLUMN role;
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN role VARCHAR NOT NULL DEFAULT 'user'
+    CHECK (role IN ('user', 'moderator', 'admin'));
+
+ALTER TABLE posts ADD COLUMN hidden_at TIMESTAMP;
+ALTER TABLE posts ADD COLUMN hidden_by VARCHAR REFERENCES users (username);
+
+-- Posts deleted by moderators are gone, so the action keeps what is needed to tell which one it was.
+CREATE TABLE moderation_actions
+(
+    id          SERIAL    NOT NULL PRIMARY KEY,
+    moderator   VARCHAR   NOT NULL,
+    action      VARCHAR   NOT NULL,
+    post_id     INTEGER   NOT NULL,
+    post_author VARCHAR   NOT NULL,
+    post_title  TEXT      NOT NULL,
+    reason      TEXT,
+    created_at  TIMESTAMP NOT NULL,
+    FOREIGN KEY (moderator) REFERENCES users (username)
+);
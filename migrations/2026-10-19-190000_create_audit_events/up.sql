@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Usernames are copied rather than referenced so that events outlive the accounts they mention.
+CREATE TABLE audit_events
+(
+    id         BIGSERIAL NOT NULL PRIMARY KEY,
+    event      VARCHAR   NOT NULL,
+    actor      VARCHAR,
+    target     VARCHAR,
+    ip         VARCHAR,
+    details    TEXT,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX audit_events_actor_idx ON audit_events (actor, created_at);
+CREATE INDEX audit_events_target_idx ON audit_events (target, created_at);
+CREATE INDEX audit_events_created_at_idx ON audit_events (created_at);
+
+CREATE FUNCTION reject_audit_event_change() RETURNS TRIGGER AS
+$$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE
+    ON audit_events
+    FOR EACH ROW
+EXECUTE FUNCTION reject_audit_event_change();
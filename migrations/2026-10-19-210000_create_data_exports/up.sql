@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE data_exports
+(
+    id           SERIAL    NOT NULL PRIMARY KEY,
+    username     VARCHAR   NOT NULL,
+    token_hash   VARCHAR   NOT NULL UNIQUE,
+    status       VARCHAR   NOT NULL,
+    archive      BYTEA,
+    created_at   TIMESTAMP NOT NULL,
+    completed_at TIMESTAMP,
+    expires_at   TIMESTAMP NOT NULL,
+    FOREIGN KEY (username) REFERENCES users (username) ON DELETE CASCADE
+);
@@ -0,0 +1,58 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE username_history;
+
+ALTER TABLE posts
+    DROP CONSTRAINT posts_hidden_by_fkey,
+    ADD CONSTRAINT posts_hidden_by_fkey FOREIGN KEY (hidden_by) REFERENCES users (username)
+        ON DELETE SET NULL,
+    DROP CONSTRAINT posts_username_fkey,
+    ADD CONSTRAINT posts_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE sessions
+    DROP CONSTRAINT sessions_username_fkey,
+    ADD CONSTRAINT sessions_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE password_reset_tokens
+    DROP CONSTRAINT password_reset_tokens_username_fkey,
+    ADD CONSTRAINT password_reset_tokens_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE email_verification_tokens
+    DROP CONSTRAINT email_verification_tokens_username_fkey,
+    ADD CONSTRAINT email_verification_tokens_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE recovery_codes
+    DROP CONSTRAINT recovery_codes_username_fkey,
+    ADD CONSTRAINT recovery_codes_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE pending_logins
+    DROP CONSTRAINT pending_logins_username_fkey,
+    ADD CONSTRAINT pending_logins_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE webauthn_credentials
+    DROP CONSTRAINT webauthn_credentials_username_fkey,
+    ADD CONSTRAINT webauthn_credentials_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE webauthn_challenges
+    DROP CONSTRAINT webauthn_challenges_username_fkey,
+    ADD CONSTRAINT webauthn_challenges_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE api_tokens
+    DROP CONSTRAINT api_tokens_username_fkey,
+    ADD CONSTRAINT api_tokens_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE oidc_identities
+    DROP CONSTRAINT oidc_identities_username_fkey,
+    ADD CONSTRAINT oidc_identities_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE oidc_states
+    DROP CONSTRAINT oidc_states_link_username_fkey,
+    ADD CONSTRAINT oidc_states_link_username_fkey FOREIGN KEY (link_username) REFERENCES users (username)
+        ON DELETE CASCADE;
+ALTER TABLE moderation_actions
+    DROP CONSTRAINT moderation_actions_moderator_fkey,
+    ADD CONSTRAINT moderation_actions_moderator_fkey FOREIGN KEY (moderator) REFERENCES users (username)
+        ON DELETE SET NULL;
+ALTER TABLE data_exports
+    DROP CONSTRAINT data_exports_username_fkey,
+    ADD CONSTRAINT data_exports_username_fkey FOREIGN KEY (username) REFERENCES users (username)
+        ON DELETE CASCADE;
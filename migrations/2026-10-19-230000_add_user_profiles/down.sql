@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users DROP COLUMN pronouns;
+ALTER TABLE users DROP COLUMN location;
+ALTER TABLE users DROP COLUMN links;
+ALTER TABLE users DROP COLUMN bio;
+ALTER TABLE users DROP COLUMN display_name;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN display_name VARCHAR;
+ALTER TABLE users ADD COLUMN bio TEXT;
+ALTER TABLE users ADD COLUMN links TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE users ADD COLUMN location VARCHAR;
+ALTER TABLE users ADD COLUMN pronouns VARCHAR;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_display_name_trgm_idx;
+DROP INDEX users_username_trgm_idx;
+DROP EXTENSION IF EXISTS pg_trgm;
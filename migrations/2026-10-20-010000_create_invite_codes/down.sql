@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users DROP COLUMN invited_by;
+ALTER TABLE users DROP COLUMN approval_pending;
+DROP TABLE invite_codes;
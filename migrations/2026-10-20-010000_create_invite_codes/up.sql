@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE invite_codes
+(
+    id         SERIAL    NOT NULL PRIMARY KEY,
+    created_by VARCHAR   NOT NULL,
+    code_hash  VARCHAR   NOT NULL UNIQUE,
+    max_uses   INTEGER   NOT NULL,
+    uses       INTEGER   NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users (username) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+ALTER TABLE users ADD COLUMN approval_pending BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN invited_by INTEGER REFERENCES invite_codes (id) ON DELETE SET NULL;
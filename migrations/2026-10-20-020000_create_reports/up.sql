@@ -0,0 +1,25 @@
+-- Your SQL goes here
+CREATE TABLE reports
+(
+    id            SERIAL    NOT NULL PRIMARY KEY,
+    reporter      VARCHAR   NOT NULL,
+    post_id       INTEGER,
+    reported_user VARCHAR,
+    reason        VARCHAR   NOT NULL,
+    details       TEXT,
+    created_at    TIMESTAMP NOT NULL,
+    resolved_at   TIMESTAMP,
+    resolved_by   VARCHAR,
+    resolution    VARCHAR,
+    FOREIGN KEY (reporter) REFERENCES users (username) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (post_id) REFERENCES posts (id) ON DELETE CASCADE,
+    FOREIGN KEY (reported_user) REFERENCES users (username) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (resolved_by) REFERENCES users (username) ON DELETE SET NULL ON UPDATE CASCADE,
+    CHECK ((post_id IS NULL) <> (reported_user IS NULL))
+);
+
+-- Each reporter has at most one open report per post or user.
+CREATE UNIQUE INDEX reports_open_post_idx ON reports (reporter, post_id)
+    WHERE resolved_at IS NULL AND post_id IS NOT NULL;
+CREATE UNIQUE INDEX reports_open_user_idx ON reports (reporter, reported_user)
+    WHERE resolved_at IS NULL AND reported_user IS NOT NULL;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX posts_content_hash_idx;
+ALTER TABLE posts DROP COLUMN content_hash;
+ALTER TABLE posts DROP COLUMN held_reason;
+ALTER TABLE posts DROP COLUMN held_at;
+DROP TABLE word_filters;
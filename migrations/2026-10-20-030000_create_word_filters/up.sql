@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE word_filters
+(
+    id         SERIAL    NOT NULL PRIMARY KEY,
+    pattern    VARCHAR   NOT NULL,
+    is_regex   BOOLEAN   NOT NULL DEFAULT FALSE,
+    action     VARCHAR   NOT NULL CHECK (action IN ('block', 'flag')),
+    created_by VARCHAR,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (created_by) REFERENCES users (username) ON DELETE SET NULL ON UPDATE CASCADE
+);
+
+ALTER TABLE posts ADD COLUMN held_at TIMESTAMP;
+ALTER TABLE posts ADD COLUMN held_reason TEXT;
+ALTER TABLE posts ADD COLUMN content_hash VARCHAR;
+
+CREATE INDEX posts_content_hash_idx ON posts (content_hash);
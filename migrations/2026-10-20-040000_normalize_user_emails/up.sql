@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Addresses differing only in case belong to the same mailbox. Accounts sharing one can't be
+-- told apart here without one of them losing its recovery address, so the migration stops
+-- and lists them; change or clear the addresses by hand and run it again.
+DO
+$$
+    DECLARE
+        conflicts TEXT;
+    BEGIN
+        SELECT string_agg(lower(trim(email)) || ': ' || usernames, '; ')
+        INTO conflicts
+        FROM (SELECT email, string_agg(username, ', ' ORDER BY username) AS usernames
+              FROM (SELECT lower(trim(email)) AS email, username
+                    FROM users
+                    WHERE email IS NOT NULL) AS normalized
+              GROUP BY email
+              HAVING count(*) > 1) AS shared;
+
+        IF conflicts IS NOT NULL THEN
+            RAISE EXCEPTION 'Accounts share an email address that differs only in case: %', conflicts;
+        END IF;
+    END
+$$;
+
+UPDATE users SET email = lower(trim(email)) WHERE email IS NOT NULL;
+UPDATE email_verification_tokens SET email = lower(trim(email));
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX users_email_key ON users (lower(email));
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Sessions from before this migration count as started now.
+ALTER TABLE sessions ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT now();
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE audit_events
+    DROP COLUMN actor_id,
+    DROP COLUMN target_id;
+
+ALTER TABLE users DROP COLUMN id;
@@ -0,0 +1,50 @@
+-- Your SQL goes here
+-- Usernames can change and be taken over by someone else once given up, so audit events also
+-- point at a number that stays with the account. There is no foreign key, events outlive the
+-- accounts they mention.
+ALTER TABLE users ADD COLUMN id BIGSERIAL NOT NULL UNIQUE;
+
+ALTER TABLE audit_events
+    ADD COLUMN actor_id  BIGINT,
+    ADD COLUMN target_id BIGINT;
+
+CREATE INDEX audit_events_actor_id_idx ON audit_events (actor_id, created_at);
+CREATE INDEX audit_events_target_id_idx ON audit_events (target_id, created_at);
+
+-- Existing events are attributed as well as the names allow: a current name from the moment
+-- the account took it, a former name up to the rename.
+ALTER TABLE audit_events DISABLE TRIGGER audit_events_append_only;
+
+CREATE TEMPORARY TABLE audit_names AS
+SELECT u.id,
+       u.username AS name,
+       COALESCE((SELECT max(h.changed_at) FROM username_history h WHERE h.username = u.username),
+                u.joined::timestamp) AS held_from,
+       'infinity'::timestamp AS held_until
+FROM users u
+UNION ALL
+SELECT u.id,
+       h.old_username,
+       COALESCE(lag(h.changed_at) OVER (PARTITION BY h.username ORDER BY h.changed_at),
+                u.joined::timestamp),
+       h.changed_at
+FROM username_history h
+         JOIN users u ON u.username = h.username;
+
+UPDATE audit_events e
+SET actor_id = n.id
+FROM audit_names n
+WHERE e.actor = n.name
+  AND e.created_at >= n.held_from
+  AND e.created_at < n.held_until;
+
+UPDATE audit_events e
+SET target_id = n.id
+FROM audit_names n
+WHERE e.target = n.name
+  AND e.created_at >= n.held_from
+  AND e.created_at < n.held_until;
+
+DROP TABLE audit_names;
+
+ALTER TABLE audit_events ENABLE TRIGGER audit_events_append_only;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX data_exports_pending_key;
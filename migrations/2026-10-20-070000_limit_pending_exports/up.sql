@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- At most one export per user is built at a time. Older builds still pending alongside a newer
+-- one can't finish anymore anyway.
+UPDATE data_exports
+SET status = 'failed'
+WHERE status = 'pending'
+  AND id NOT IN (SELECT max(id) FROM data_exports WHERE status = 'pending' GROUP BY username);
+
+CREATE UNIQUE INDEX data_exports_pending_key ON data_exports (username) WHERE status = 'pending';
@@ -0,0 +1,28 @@
+use std::str::FromStr;
+
+/// Reads `key` from the environment, falling back to `default` when it is unset or unparsable.
+pub fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Reads a comma separated list from the environment, falling back to `default` when unset.
+pub fn env_list_or(key: &str, default: &[&str]) -> Vec<String> {
+    match std::env::var(key) {
+        Ok(value) => value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        Err(_) => default.iter().map(|item| item.to_string()).collect(),
+    }
+}
+
+/// Base URL of the deployment, used for links that leave the application (e.g. in mails).
+pub fn public_url() -> String {
+    env_or("PUBLIC_URL", "http://localhost:3000".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
@@ -0,0 +1,192 @@
+use crate::error::{AppResult, JsonResult};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::audit::{AuditKind, NewAuditEvent};
+use crate::model::post::ModerationForm;
+use crate::model::role::Permission;
+use crate::model::user::{AdminUserDTO, AdminUserSearch, SuspendForm};
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::net::IpAddr;
+
+pub async fn get_users(
+    State(state): State<AppState>,
+    Query(params): Query<AdminUserSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<AdminUserDTO>> {
+    current.authorize(Permission::ManageUsers)?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.admin_service.search_users(params.q, page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    Json(form): Json<SuspendForm>,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageUsers)?;
+    let details = describe(
+        format!("{} hours", form.duration_hours),
+        form.reason.as_deref(),
+    );
+
+    state
+        .admin_service
+        .suspend_user(&current.user, username.clone(), form)
+        .await?;
+    record(
+        &state,
+        ip,
+        AuditKind::UserSuspended,
+        &current,
+        &username,
+        Some(details),
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn ban_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    form: Option<Json<ModerationForm>>,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageUsers)?;
+    let Json(form) = form.unwrap_or_default();
+    let details = form.reason.clone();
+
+    state
+        .admin_service
+        .ban_user(&current.user, username.clone(), form.reason)
+        .await?;
+    record(
+        &state,
+        ip,
+        AuditKind::UserBanned,
+        &current,
+        &username,
+        details,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn reinstate_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageUsers)?;
+
+    state.admin_service.reinstate_user(username.clone()).await?;
+    record(
+        &state,
+        ip,
+        AuditKind::UserReinstated,
+        &current,
+        &username,
+        None,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn force_logout(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageUsers)?;
+
+    state.admin_service.force_logout(username.clone()).await?;
+    record(
+        &state,
+        ip,
+        AuditKind::UserLoggedOut,
+        &current,
+        &username,
+        None,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn reset_avatar(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageUsers)?;
+
+    state.admin_service.reset_avatar(username.clone()).await?;
+    record(
+        &state,
+        ip,
+        AuditKind::AvatarReset,
+        &current,
+        &username,
+        None,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageUsers)?;
+
+    state
+        .admin_service
+        .delete_user(&current.user, username.clone())
+        .await?;
+    record(
+        &state,
+        ip,
+        AuditKind::UserDeleted,
+        &current,
+        &username,
+        None,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn record(
+    state: &AppState,
+    ip: IpAddr,
+    kind: AuditKind,
+    current: &CurrentUser,
+    username: &str,
+    details: Option<String>,
+) -> AppResult<()> {
+    let mut event = NewAuditEvent::new(kind, ip)
+        .actor(&current.user.username)
+        .target(username);
+    if let Some(details) = details {
+        event = event.details(details);
+    }
+
+    state.audit_service.record(event).await
+}
+
+fn describe(summary: String, reason: Option<&str>) -> String {
+    match reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+        Some(reason) => format!("{summary}: {reason}"),
+        None => summary,
+    }
+}
@@ -0,0 +1,46 @@
+use crate::controller::current_user;
+use crate::error::{AppResult, JsonResult};
+use crate::model::api_token::{ApiToken, CreateApiTokenForm, CreatedApiTokenDTO};
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn get_tokens(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> JsonResult<Vec<ApiToken>> {
+    let user = current_user(&state, &jar).await?;
+    let result = state.api_token_service.get_tokens(user.username).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn create_token(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<CreateApiTokenForm>,
+) -> AppResult<(StatusCode, Json<CreatedApiTokenDTO>)> {
+    let user = current_user(&state, &jar).await?;
+    let result = state
+        .api_token_service
+        .create_token(user.username, form)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+pub async fn delete_token(
+    State(state): State<AppState>,
+    Path(token_id): Path<i32>,
+    jar: CookieJar,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+    state
+        .api_token_service
+        .delete_token(user.username, token_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
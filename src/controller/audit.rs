@@ -0,0 +1,39 @@
+use crate::controller::current_user;
+use crate::error::JsonResult;
+use crate::extract::CurrentUser;
+use crate::model::audit::{AuditEvent, AuditSearch};
+use crate::model::post::PaginatedPostSearch;
+use crate::model::role::Permission;
+use crate::AppState;
+use axum::extract::{Query, State};
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn get_events(
+    State(state): State<AppState>,
+    Query(search): Query<AuditSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<AuditEvent>> {
+    current.authorize(Permission::ViewAuditLog)?;
+    let page = search.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.audit_service.search(search, page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn get_account_activity(
+    State(state): State<AppState>,
+    Query(params): Query<PaginatedPostSearch>,
+    jar: CookieJar,
+) -> JsonResult<Vec<AuditEvent>> {
+    let user = current_user(&state, &jar).await?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state
+        .audit_service
+        .get_account_activity(user.username, page)
+        .await?;
+
+    Ok(Json(result))
+}
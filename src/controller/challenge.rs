@@ -0,0 +1,48 @@
+use crate::error::{AppResult, JsonResult};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::audit::{AuditKind, NewAuditEvent};
+use crate::model::challenge::{ChallengeDTO, ChallengeQuery, DifficultyDTO, UpdateDifficultyForm};
+use crate::model::role::Permission;
+use crate::AppState;
+use axum::extract::{Query, State};
+use axum::Json;
+
+pub async fn get_challenge(
+    State(state): State<AppState>,
+    Query(params): Query<ChallengeQuery>,
+    current: Option<CurrentUser>,
+) -> Json<ChallengeDTO> {
+    let user = current.map(|current| current.user);
+
+    Json(state.challenge_service.issue(params.purpose, user.as_ref()))
+}
+
+pub async fn get_difficulty(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> JsonResult<DifficultyDTO> {
+    current.authorize(Permission::ManageRegistrations)?;
+
+    Ok(Json(state.challenge_service.difficulty()))
+}
+
+pub async fn update_difficulty(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    Json(form): Json<UpdateDifficultyForm>,
+) -> AppResult<Json<DifficultyDTO>> {
+    current.authorize(Permission::ManageRegistrations)?;
+
+    let result = state.challenge_service.set_difficulty(form)?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::DifficultyChanged, ip)
+                .actor(&current.user.username)
+                .details(format!("signup {}, post {}", result.signup, result.post)),
+        )
+        .await?;
+
+    Ok(Json(result))
+}
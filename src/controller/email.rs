@@ -0,0 +1,60 @@
+use crate::error::AppError::LoginError;
+use crate::error::AppResult;
+use crate::model::email_verification::{ChangeEmailForm, VerifyEmailForm};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn change_email(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<ChangeEmailForm>,
+) -> AppResult<StatusCode> {
+    let session_id = jar
+        .get("session_id")
+        .ok_or(LoginError)?
+        .value()
+        .to_string();
+
+    let user = state
+        .user_service
+        .get_user_by_session(session_id)
+        .await?
+        .ok_or(LoginError)?;
+
+    state
+        .email_service
+        .change_email(user.username, form.email)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> AppResult<StatusCode> {
+    let session_id = jar
+        .get("session_id")
+        .ok_or(LoginError)?
+        .value()
+        .to_string();
+
+    let user = state
+        .user_service
+        .get_user_by_session(session_id)
+        .await?
+        .ok_or(LoginError)?;
+
+    state.email_service.send_verification(user.username).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(form): Json<VerifyEmailForm>,
+) -> AppResult<StatusCode> {
+    state.email_service.verify(form.token).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
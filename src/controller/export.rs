@@ -0,0 +1,73 @@
+use crate::controller::current_user;
+use crate::error::AppError::ForbiddenError;
+use crate::error::{AppResult, JsonResult};
+use crate::extract::ClientIp;
+use crate::model::audit::{AuditKind, NewAuditEvent};
+use crate::model::export::{CreatedExportDTO, DataExport};
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn request_export(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+) -> AppResult<(StatusCode, Json<CreatedExportDTO>)> {
+    let user = current_user(&state, &jar).await?;
+    if user.username != username {
+        return Err(ForbiddenError(
+            "You can only export your own account".to_string(),
+        ));
+    }
+
+    let result = state.export_service.request_export(username.clone()).await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::DataExportRequested, ip)
+                .actor(&username)
+                .target(&username),
+        )
+        .await?;
+
+    Ok((StatusCode::ACCEPTED, Json(result)))
+}
+
+pub async fn get_exports(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    jar: CookieJar,
+) -> JsonResult<Vec<DataExport>> {
+    let user = current_user(&state, &jar).await?;
+    if user.username != username {
+        return Err(ForbiddenError(
+            "You can only export your own account".to_string(),
+        ));
+    }
+
+    let result = state.export_service.get_exports(username).await?;
+
+    Ok(Json(result))
+}
+
+/// The link itself is the credential, so it works without a session.
+pub async fn download_export(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let (username, archive) = state.export_service.download(&token).await?;
+    let disposition = format!("attachment; filename=\"{username}-export.zip\"");
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/zip".to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    ))
+}
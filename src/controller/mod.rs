@@ -1,2 +1,64 @@
+use crate::error::AppError::{ForbiddenError, LoginError};
+use crate::error::AppResult;
+use crate::model::role::Permission;
+use crate::model::user::User;
+use crate::AppState;
+use axum_extra::extract::CookieJar;
+
 pub mod user;
-pub mod post;
\ No newline at end of file
+pub mod post;
+pub mod password;
+pub mod email;
+pub mod two_factor;
+pub mod webauthn;
+pub mod api_token;
+pub mod oidc;
+pub mod moderation;
+pub mod admin;
+pub mod audit;
+pub mod export;
+pub mod registration;
+
+/// Resolves the `session_id` cookie to the logged-in user, failing with `401` otherwise.
+/// Account management goes through here so that API tokens can't reach it, other routes
+/// use [`crate::extract::CurrentUser`]. Accounts scheduled for deletion get `403`: they can
+/// log in, but only to restore the account.
+pub async fn current_user(state: &AppState, jar: &CookieJar) -> AppResult<User> {
+    let user = session_user(state, jar).await?;
+    if user.deletion_scheduled_at.is_some() {
+        return Err(ForbiddenError(
+            "This account is scheduled for deletion, restore it to use it again".to_string(),
+        ));
+    }
+
+    Ok(user)
+}
+
+/// [`current_user`] without the deletion check, for restoring the account.
+pub async fn session_user(state: &AppState, jar: &CookieJar) -> AppResult<User> {
+    let session_id = jar
+        .get("session_id")
+        .ok_or(LoginError)?
+        .value()
+        .to_string();
+
+    state
+        .user_service
+        .get_user_by_session(session_id)
+        .await?
+        .ok_or(LoginError)
+}
+
+/// Fails with `403` unless the role of `user` grants `permission`.
+pub fn authorize(user: &User, permission: Permission) -> AppResult<()> {
+    if user.role.grants(permission) {
+        Ok(())
+    } else {
+        Err(ForbiddenError(
+            "You are not allowed to do this".to_string(),
+        ))
+    }
+}
+pub mod challenge;
+pub mod report;
+pub mod word_filter;
@@ -0,0 +1,142 @@
+use crate::error::{AppResult, JsonResult};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::audit::{post_details, AuditKind, NewAuditEvent};
+use crate::model::moderation::{ChangeRoleForm, ModerationAction};
+use crate::model::post::{ModerationForm, PaginatedPostSearch, Post};
+use crate::model::role::Permission;
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+
+pub async fn hide_post(
+    State(state): State<AppState>,
+    Path(post_id): Path<i32>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    form: Option<Json<ModerationForm>>,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ModeratePosts)?;
+    let Json(form) = form.unwrap_or_default();
+    let details = post_details(post_id, form.reason.as_deref());
+
+    let post = state
+        .post_service
+        .hide_post(&current.user, post_id, form.reason)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::PostHidden, ip)
+                .actor(&current.user.username)
+                .post_author(post.username.as_deref())
+                .details(details),
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unhide_post(
+    State(state): State<AppState>,
+    Path(post_id): Path<i32>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    form: Option<Json<ModerationForm>>,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ModeratePosts)?;
+    let Json(form) = form.unwrap_or_default();
+    let details = post_details(post_id, form.reason.as_deref());
+
+    let post = state
+        .post_service
+        .unhide_post(&current.user, post_id, form.reason)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::PostUnhidden, ip)
+                .actor(&current.user.username)
+                .post_author(post.username.as_deref())
+                .details(details),
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn release_post(
+    State(state): State<AppState>,
+    Path(post_id): Path<i32>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    form: Option<Json<ModerationForm>>,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ModeratePosts)?;
+    let Json(form) = form.unwrap_or_default();
+    let details = post_details(post_id, form.reason.as_deref());
+
+    let post = state
+        .post_service
+        .release_post(&current.user, post_id, form.reason)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::PostReleased, ip)
+                .actor(&current.user.username)
+                .post_author(post.username.as_deref())
+                .details(details),
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_held_posts(
+    State(state): State<AppState>,
+    Query(params): Query<PaginatedPostSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<Post>> {
+    current.authorize(Permission::ModeratePosts)?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.post_service.get_held_posts(page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn get_actions(
+    State(state): State<AppState>,
+    Query(params): Query<PaginatedPostSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<ModerationAction>> {
+    current.authorize(Permission::ModeratePosts)?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.post_service.get_moderation_actions(page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn change_role(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    Json(form): Json<ChangeRoleForm>,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageRoles)?;
+
+    state
+        .user_service
+        .change_role(&current.user, username.clone(), form.role)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::RoleChanged, ip)
+                .actor(&current.user.username)
+                .target(&username)
+                .details(form.role.as_str()),
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
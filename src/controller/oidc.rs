@@ -0,0 +1,142 @@
+use crate::controller::current_user;
+use crate::controller::user::{login_succeeded, session_cookie};
+use crate::error::{AppResult, JsonResult};
+use crate::extract::ClientIp;
+use crate::model::oidc::{
+    AuthorizationUrlDTO, OidcCallbackQuery, OidcIdentity, OidcLoginQuery, OidcProviderDTO,
+};
+use crate::service::oidc::OidcAuthorization;
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Json;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
+use openidconnect::url::form_urlencoded;
+
+const STATE_COOKIE: &str = "oidc_state";
+
+/// Binds an authorization request to the browser that started it. `Lax` still sends it along
+/// with the provider's top-level redirect back to the callback.
+fn state_cookie(value: String, max_age: time::Duration) -> Cookie<'static> {
+    Cookie::build((STATE_COOKIE, value))
+        .path("/api/auth/oidc")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(max_age)
+        .build()
+}
+
+fn remember_state(jar: CookieJar, authorization: &OidcAuthorization) -> CookieJar {
+    jar.add(state_cookie(
+        authorization.state_hash.clone(),
+        time::Duration::seconds(authorization.expires_in.num_seconds()),
+    ))
+}
+
+pub async fn get_providers(State(state): State<AppState>) -> Json<Vec<OidcProviderDTO>> {
+    Json(state.oidc_service.get_providers())
+}
+
+/// Sends the browser to the provider's login page.
+pub async fn start_login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    jar: CookieJar,
+    Query(query): Query<OidcLoginQuery>,
+) -> AppResult<(CookieJar, Redirect)> {
+    let authorization = state
+        .oidc_service
+        .authorization_url(&provider, None, query.redirect_to)
+        .await?;
+
+    Ok((
+        remember_state(jar, &authorization),
+        Redirect::to(&authorization.url),
+    ))
+}
+
+/// Linking is started by the logged-in frontend, which then navigates to the returned URL.
+pub async fn start_link(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    jar: CookieJar,
+    Query(query): Query<OidcLoginQuery>,
+) -> AppResult<(CookieJar, Json<AuthorizationUrlDTO>)> {
+    let user = current_user(&state, &jar).await?;
+    let authorization = state
+        .oidc_service
+        .authorization_url(&provider, Some(user.username), query.redirect_to)
+        .await?;
+
+    Ok((
+        remember_state(jar, &authorization),
+        Json(AuthorizationUrlDTO {
+            authorization_url: authorization.url.clone(),
+        }),
+    ))
+}
+
+pub async fn callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    Query(query): Query<OidcCallbackQuery>,
+) -> AppResult<Response> {
+    let state_cookie_value = jar.get(STATE_COOKIE).map(|cookie| cookie.value().to_string());
+    // The state can only be redeemed once, so the cookie is spent whatever the outcome.
+    let jar = jar.remove(state_cookie(String::new(), time::Duration::ZERO));
+
+    let login = match state
+        .oidc_service
+        .complete(&provider, query, state_cookie_value.as_deref())
+        .await
+    {
+        Ok(login) => login,
+        Err(err) => return Ok((jar, err).into_response()),
+    };
+    let redirect = Redirect::to(&login.redirect_to);
+
+    if !login.start_session {
+        return Ok((jar, redirect).into_response());
+    }
+
+    // The login page picks the pending login up from the fragment, which unlike the query
+    // isn't sent to the server or leaked through `Referer`.
+    if login.two_factor_required {
+        let pending = state.two_factor_service.begin_login(login.username).await?;
+        let fragment = form_urlencoded::Serializer::new(String::new())
+            .append_pair("pendingToken", &pending.pending_token)
+            .append_pair("redirectTo", &login.redirect_to)
+            .finish();
+        return Ok((jar, Redirect::to(&format!("/login#{fragment}"))).into_response());
+    }
+
+    let uuid = state.user_service.start_session(login.username.clone()).await?;
+    login_succeeded(&state, ip, &login.username, &format!("oidc:{provider}")).await?;
+    Ok((jar.add(session_cookie(uuid)), redirect).into_response())
+}
+
+pub async fn get_identities(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> JsonResult<Vec<OidcIdentity>> {
+    let user = current_user(&state, &jar).await?;
+    let result = state.oidc_service.get_identities(user.username).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn unlink_identity(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    jar: CookieJar,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+    state.oidc_service.unlink(user.username, provider).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
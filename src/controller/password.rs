@@ -0,0 +1,59 @@
+use crate::error::AppError::LoginError;
+use crate::error::AppResult;
+use crate::model::password_reset::{
+    ChangePasswordForm, PasswordResetConfirmForm, PasswordResetRequestForm,
+};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn change_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<ChangePasswordForm>,
+) -> AppResult<StatusCode> {
+    let session_id = jar
+        .get("session_id")
+        .ok_or(LoginError)?
+        .value()
+        .to_string();
+
+    let user = state
+        .user_service
+        .get_user_by_session(session_id.clone())
+        .await?
+        .ok_or(LoginError)?;
+
+    state
+        .password_service
+        .change_password(
+            user.username,
+            session_id,
+            form.current_password,
+            form.new_password,
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(form): Json<PasswordResetRequestForm>,
+) -> AppResult<StatusCode> {
+    state.password_service.request_reset(form.username).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(form): Json<PasswordResetConfirmForm>,
+) -> AppResult<StatusCode> {
+    state
+        .password_service
+        .confirm_reset(form.token, form.new_password)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,12 +1,19 @@
 use crate::error::AppError::{InternalError, NotFoundError};
 use crate::error::{AppResult, JsonResult};
-use crate::model::post::{PaginatedPostSearch, Post};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::api_token::Scope;
+use crate::model::audit::{post_details, AuditKind, NewAuditEvent};
+use crate::model::challenge::ChallengePurpose;
+use crate::model::post::{ModerationForm, PaginatedPostSearch, Post};
+use crate::model::role::Permission;
+use crate::validation::image_content_type;
 use crate::AppState;
 use axum::extract::{Path, Query, State};
-use axum::http::{HeaderMap, StatusCode};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::IntoResponse;
 use axum::Json;
-use axum_extra::extract::{CookieJar, Multipart};
+use axum_extra::extract::Multipart;
 
 pub async fn get_posts_on_page(State(state): State<AppState>, Query(params): Query<PaginatedPostSearch>) -> JsonResult<Vec<Post>> {
     let page = params.page.unwrap_or(1) as u32;
@@ -19,8 +26,10 @@ pub async fn get_posts_on_page(State(state): State<AppState>, Query(params): Que
 pub async fn get_post(
     Path(post_id): Path<i32>,
     State(state): State<AppState>,
+    current: Option<CurrentUser>,
 ) -> JsonResult<Option<Post>> {
-    let result = state.post_service.get_post(post_id).await?;
+    let viewer = current.map(|current| current.user);
+    let result = state.post_service.get_post(post_id, viewer.as_ref()).await?;
 
     Ok(Json(result))
 }
@@ -28,42 +37,34 @@ pub async fn get_post(
 pub async fn get_post_image(
     Path(post_id): Path<i32>,
     State(state): State<AppState>,
+    current: Option<CurrentUser>,
 ) -> AppResult<impl IntoResponse> {
-    let result = state.post_service.get_post_image(post_id).await?;
-
-    let content_type = "image/png";
-
-    let mut headers = HeaderMap::new();
-    headers.insert("Content-Type", content_type.parse().unwrap());
+    let viewer = current.map(|current| current.user);
+    let result = state.post_service.get_post_image(post_id, viewer.as_ref()).await?;
 
     match result {
         None => Err(NotFoundError("Could not find image".to_string())),
-        Some(data) => Ok((headers, data)),
+        Some(data) => {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(image_content_type(&data)));
+            Ok((headers, data))
+        }
     }
 }
 
 pub async fn create_post(
     State(state): State<AppState>,
-    jar: CookieJar,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
     mut form_data: Multipart,
 ) -> AppResult<impl IntoResponse> {
-    let cookie_opt = jar.get("session_id");
-    if cookie_opt.is_none() {
-        return Err(InternalError("Could not create post".to_string()));
-    }
-    let cookie = cookie_opt.unwrap();
-
-    let session_id = cookie.value().to_string();
-    let result = state.user_service.get_user_by_session(session_id).await?;
-
-    if result.is_none() {
-        return Err(InternalError("Could not create post".to_string()));
-    }
-
-    let user = result.unwrap();
+    current.require(Scope::PostsWrite)?;
+    let user = current.user;
     let mut title: String = "".to_string();
     let mut body: String = "".to_string();
     let mut image: Option<Vec<u8>> = None;
+    let mut challenge: Option<String> = None;
+    let mut solution: Option<String> = None;
 
     while let Some(field) = form_data.next_field().await? {
         let name = field
@@ -91,33 +92,85 @@ pub async fn create_post(
                     _ => image = None,
                 }
             }
+            "challenge" => {
+                challenge = Some(field.text().await?);
+            }
+            "solution" => {
+                solution = Some(field.text().await?);
+            }
             _ => {}
         }
     }
 
-    state
+    let claim = state.challenge_service.verify(
+        ChallengePurpose::Post,
+        Some(&user),
+        challenge.as_deref(),
+        solution.as_deref(),
+    )?;
+
+    let created = state
         .post_service
-        .create_post(title, body, image, user.username)
+        .create_post(title, body, image, &user)
+        .await?;
+    claim.redeem();
+    let details = match &created.held_reason {
+        Some(reason) => format!("{}, held: {reason}", post_details(created.id, None)),
+        None => post_details(created.id, None),
+    };
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::PostCreated, ip)
+                .actor(&user.username)
+                .target(&user.username)
+                .details(details),
+        )
         .await?;
-    Ok(())
+
+    // Held posts exist but aren't public until a moderator releases them.
+    let status = if created.held_reason.is_some() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(created)))
 }
 pub async fn delete_user_post(
     State(state): State<AppState>,
     Path(post_id): Path<i32>,
-    jar: CookieJar,
+    Query(form): Query<ModerationForm>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
 ) -> AppResult<StatusCode> {
-    let cookie = jar
-        .get("session_id")
-        .ok_or(InternalError("Could not delete post".to_string()))?;
-
-    let session_id = cookie.value().to_string();
+    current.require(Scope::PostsWrite)?;
 
-    let user = state
-        .user_service
-        .get_user_by_session(session_id)
+    let own_post = state
+        .post_service
+        .is_post_of_user(&current.user.username, post_id)
         .await?
-        .ok_or(InternalError("Could not delete post".to_string()))?;
-    
-    state.post_service.delete_post_of_user(user.username, post_id).await?;
+        .ok_or(NotFoundError("Could not find post".to_string()))?;
+
+    let event = if own_post {
+        let user = current.user;
+        state.post_service.delete_post_of_user(user.username.clone(), post_id).await?;
+        NewAuditEvent::new(AuditKind::PostDeleted, ip)
+            .actor(&user.username)
+            .target(&user.username)
+            .details(post_details(post_id, None))
+    } else {
+        current.authorize(Permission::ModeratePosts)?;
+        let details = post_details(post_id, form.reason.as_deref());
+        let post = state
+            .post_service
+            .moderate_delete(&current.user, post_id, form.reason)
+            .await?;
+        NewAuditEvent::new(AuditKind::PostDeleted, ip)
+            .actor(&current.user.username)
+            .post_author(post.username.as_deref())
+            .details(details)
+    };
+    state.audit_service.record(event).await?;
+
     Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,147 @@
+use crate::controller::{authorize, current_user};
+use crate::error::{AppResult, JsonResult};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::audit::{AuditKind, NewAuditEvent};
+use crate::model::invite::{CreateInviteForm, CreatedInviteDTO, Invite, RegistrationDTO};
+use crate::model::role::Permission;
+use crate::model::user::{AdminUserDTO, AdminUserSearch};
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn get_registration(State(state): State<AppState>) -> Json<RegistrationDTO> {
+    Json(RegistrationDTO {
+        mode: state.registration_service.mode(),
+    })
+}
+
+pub async fn get_invites(State(state): State<AppState>, jar: CookieJar) -> JsonResult<Vec<Invite>> {
+    let user = current_user(&state, &jar).await?;
+    let result = state
+        .registration_service
+        .get_invites(user.username)
+        .await?;
+
+    Ok(Json(result))
+}
+
+pub async fn create_invite(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    Json(form): Json<CreateInviteForm>,
+) -> AppResult<(StatusCode, Json<CreatedInviteDTO>)> {
+    let user = current_user(&state, &jar).await?;
+    let privileged = authorize(&user, Permission::ManageRegistrations).is_ok();
+
+    let result = state
+        .registration_service
+        .create_invite(&user, privileged, form)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::InviteCreated, ip)
+                .actor(&user.username)
+                .target(&user.username)
+                .details(format!(
+                    "#{}, {} uses",
+                    result.invite.id, result.invite.max_uses
+                )),
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+pub async fn delete_invite(
+    State(state): State<AppState>,
+    Path(invite_id): Path<i32>,
+    jar: CookieJar,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+    let privileged = authorize(&user, Permission::ManageRegistrations).is_ok();
+
+    state
+        .registration_service
+        .delete_invite(&user, privileged, invite_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_all_invites(
+    State(state): State<AppState>,
+    Query(params): Query<AdminUserSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<Invite>> {
+    current.authorize(Permission::ManageRegistrations)?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.registration_service.get_all_invites(page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn get_pending_registrations(
+    State(state): State<AppState>,
+    Query(params): Query<AdminUserSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<AdminUserDTO>> {
+    current.authorize(Permission::ManageRegistrations)?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.admin_service.get_pending_registrations(page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn approve_registration(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageRegistrations)?;
+
+    state
+        .admin_service
+        .approve_registration(username.clone())
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::RegistrationApproved, ip)
+                .actor(&current.user.username)
+                .target(&username),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn reject_registration(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageRegistrations)?;
+
+    state
+        .admin_service
+        .reject_registration(username.clone())
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::RegistrationRejected, ip)
+                .actor(&current.user.username)
+                .target(&username),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
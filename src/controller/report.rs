@@ -0,0 +1,208 @@
+use crate::controller::current_user;
+use crate::error::AppError::{NotFoundError, ValidationError};
+use crate::error::{AppResult, FieldError, JsonResult};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::audit::{post_details, AuditKind, NewAuditEvent};
+use crate::model::post::PaginatedPostSearch;
+use crate::model::report::{
+    ReportAction, ReportForm, ReportQueueEntry, ReportTarget, ResolveReportsForm,
+};
+use crate::model::role::Permission;
+use crate::model::user::SuspendForm;
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use std::net::IpAddr;
+
+pub async fn report_post(
+    State(state): State<AppState>,
+    Path(post_id): Path<i32>,
+    jar: CookieJar,
+    Json(form): Json<ReportForm>,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+    let post = state
+        .post_service
+        .get_post(post_id, Some(&user))
+        .await?
+        .ok_or(NotFoundError("Could not find post".to_string()))?;
+
+    state.report_service.report_post(&user, &post, form).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn report_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    jar: CookieJar,
+    Json(form): Json<ReportForm>,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+
+    state
+        .report_service
+        .report_user(&user, username, form)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_report_queue(
+    State(state): State<AppState>,
+    Query(params): Query<PaginatedPostSearch>,
+    current: CurrentUser,
+) -> JsonResult<Vec<ReportQueueEntry>> {
+    current.authorize(Permission::ModeratePosts)?;
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state.report_service.get_queue(page).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn resolve_post_reports(
+    State(state): State<AppState>,
+    Path(post_id): Path<i32>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    Json(form): Json<ResolveReportsForm>,
+) -> AppResult<StatusCode> {
+    resolve(&state, ip, &current, ReportTarget::Post(post_id), form).await
+}
+
+pub async fn resolve_user_reports(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    Json(form): Json<ResolveReportsForm>,
+) -> AppResult<StatusCode> {
+    resolve(&state, ip, &current, ReportTarget::User(username), form).await
+}
+
+/// Carries out `form.action` against the target or its author and closes the target's open
+/// reports.
+async fn resolve(
+    state: &AppState,
+    ip: IpAddr,
+    current: &CurrentUser,
+    target: ReportTarget,
+    form: ResolveReportsForm,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ModeratePosts)?;
+    state.report_service.ensure_open_reports(&target).await?;
+
+    let moderator = &current.user;
+    let reason = form.reason.as_deref();
+    let author = match &target {
+        ReportTarget::Post(post_id) => state
+            .post_service
+            .get_post(*post_id, Some(moderator))
+            .await?
+            .and_then(|post| post.username),
+        ReportTarget::User(username) => Some(username.clone()),
+    };
+    let require_author = || {
+        author.clone().ok_or(ValidationError(vec![FieldError::new(
+            "action",
+            "The post no longer has an author",
+        )]))
+    };
+
+    match form.action {
+        ReportAction::Dismiss => {}
+        ReportAction::Hide => {
+            let ReportTarget::Post(post_id) = target else {
+                return Err(ValidationError(vec![FieldError::new(
+                    "action",
+                    "Only posts can be hidden",
+                )]));
+            };
+            let post = state
+                .post_service
+                .hide_post(moderator, post_id, form.reason.clone())
+                .await?;
+            state
+                .audit_service
+                .record(
+                    NewAuditEvent::new(AuditKind::PostHidden, ip)
+                        .actor(&moderator.username)
+                        .post_author(post.username.as_deref())
+                        .details(post_details(post_id, reason)),
+                )
+                .await?;
+        }
+        ReportAction::Warn => {
+            let author = require_author()?;
+            state
+                .report_service
+                .warn_user(author.clone(), reason)
+                .await?;
+            let mut event = NewAuditEvent::new(AuditKind::UserWarned, ip)
+                .actor(&moderator.username)
+                .target(&author);
+            if let Some(reason) = reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+                event = event.details(reason);
+            }
+            state.audit_service.record(event).await?;
+        }
+        ReportAction::Suspend => {
+            current.authorize(Permission::ManageUsers)?;
+            let author = require_author()?;
+            let Some(duration_hours) = form.duration_hours else {
+                return Err(ValidationError(vec![FieldError::new(
+                    "durationHours",
+                    "Required to suspend",
+                )]));
+            };
+
+            state
+                .admin_service
+                .suspend_user(
+                    moderator,
+                    author.clone(),
+                    SuspendForm {
+                        duration_hours,
+                        reason: form.reason.clone(),
+                    },
+                )
+                .await?;
+            let details = match reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+                Some(reason) => format!("{duration_hours} hours: {reason}"),
+                None => format!("{duration_hours} hours"),
+            };
+            state
+                .audit_service
+                .record(
+                    NewAuditEvent::new(AuditKind::UserSuspended, ip)
+                        .actor(&moderator.username)
+                        .target(&author)
+                        .details(details),
+                )
+                .await?;
+        }
+    }
+
+    let details = target.describe();
+    let resolved = state
+        .report_service
+        .resolve(moderator, target, form.action)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::ReportsResolved, ip)
+                .actor(&moderator.username)
+                .post_author(author.as_deref())
+                .details(format!(
+                    "{details}: {}, {resolved} reports",
+                    form.action.as_str()
+                )),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,62 @@
+use crate::controller::current_user;
+use crate::error::{AppResult, JsonResult};
+use crate::model::two_factor::{
+    DisableTwoFactorForm, RecoveryCodesDTO, TwoFactorCodeForm, TwoFactorEnrollmentDTO,
+};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn begin_enrollment(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> JsonResult<TwoFactorEnrollmentDTO> {
+    let user = current_user(&state, &jar).await?;
+    let result = state.two_factor_service.begin_enrollment(&user).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn confirm_enrollment(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<TwoFactorCodeForm>,
+) -> JsonResult<RecoveryCodesDTO> {
+    let user = current_user(&state, &jar).await?;
+    let result = state
+        .two_factor_service
+        .confirm_enrollment(&user, form.code)
+        .await?;
+
+    Ok(Json(result))
+}
+
+pub async fn disable(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<DisableTwoFactorForm>,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+    state
+        .two_factor_service
+        .disable(&user, form.password, form.code)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<TwoFactorCodeForm>,
+) -> JsonResult<RecoveryCodesDTO> {
+    let user = current_user(&state, &jar).await?;
+    let result = state
+        .two_factor_service
+        .regenerate_recovery_codes(&user, form.code)
+        .await?;
+
+    Ok(Json(result))
+}
@@ -1,15 +1,34 @@
-use crate::error::AppError::{InternalError, LoginError, NotFoundError};
+use crate::error::AppError::{ForbiddenError, InternalError, LoginError, NotFoundError};
 use crate::error::{AppResult, JsonResult};
-use crate::model::user::{User, UserDTO};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::api_token::Scope;
+use crate::model::audit::{AuditKind, NewAuditEvent};
+use crate::model::challenge::ChallengePurpose;
+use crate::model::user::{
+    AccountDTO, AccountDeletionDTO, AutocompleteSearch, ChangeUsernameForm, DeleteAccountForm,
+    DirectoryUserDTO, UpdateProfileForm, UserDTO, UserDirectorySearch, UserSuggestionDTO,
+};
 use crate::AppState;
-use axum::extract::{Path, Query, State};
-use axum::http::{HeaderMap, StatusCode};
-use axum::response::IntoResponse;
+use axum::extract::{FromRequest, Path, Query, RawQuery, Request, State};
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
 use axum::Json;
+use std::net::IpAddr;
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::extract::{CookieJar, Multipart};
 use serde::Deserialize;
 use crate::model::post::PaginatedPostSearch;
+use crate::model::two_factor::TwoFactorLoginForm;
+use crate::model::webauthn::PasskeyLoginForm;
+use crate::service::avatar::AvatarFormat;
+use crate::service::user::LoginOutcome;
+use crate::validation::image_content_type;
+
+#[derive(Deserialize)]
+pub struct AvatarQuery {
+    format: Option<AvatarFormat>,
+}
 
 #[derive(Deserialize)]
 pub struct AuthForm {
@@ -17,37 +36,186 @@ pub struct AuthForm {
     password: String,
 }
 
+/// `/auth/login` accepts either a password or a signed WebAuthn assertion.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum LoginForm {
+    Passkey(PasskeyLoginForm),
+    Password(AuthForm),
+}
+
+#[derive(Deserialize)]
+pub struct SignUpForm {
+    username: String,
+    password: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    invite: Option<String>,
+    #[serde(default)]
+    challenge: Option<String>,
+    #[serde(default)]
+    solution: Option<String>,
+}
+
 pub async fn login_user(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     jar: CookieJar,
-    Json(form): Json<AuthForm>,
-) -> AppResult<(CookieJar, StatusCode)> {
-    let uuid = state
+    Json(form): Json<LoginForm>,
+) -> AppResult<Response> {
+    let form = match form {
+        LoginForm::Password(form) => form,
+        LoginForm::Passkey(form) => {
+            state.throttle_service.check_login(ip, None)?;
+            let username = match state
+                .webauthn_service
+                .finish_authentication(form.challenge_id, form.credential)
+                .await
+            {
+                Ok(username) => username,
+                Err(LoginError) => {
+                    login_failed(&state, ip, None, "passkey").await?;
+                    return Err(LoginError);
+                }
+                Err(err) => return Err(err),
+            };
+            let uuid = state.user_service.start_session(username.clone()).await?;
+            login_succeeded(&state, ip, &username, "passkey").await?;
+            return Ok(session_response(jar, uuid));
+        }
+    };
+
+    state
+        .throttle_service
+        .check_login(ip, Some(&form.username))?;
+
+    let outcome = match state
         .user_service
-        .login(form.username, form.password)
-        .await?;
+        .login(form.username.clone(), form.password)
+        .await
+    {
+        Ok(outcome) => {
+            state.throttle_service.login_succeeded(&form.username);
+            outcome
+        }
+        Err(LoginError) => {
+            login_failed(&state, ip, Some(&form.username), "password").await?;
+            return Err(LoginError);
+        }
+        Err(err) => return Err(err),
+    };
+
+    match outcome {
+        LoginOutcome::Session(uuid) => {
+            login_succeeded(&state, ip, &form.username, "password").await?;
+            Ok(session_response(jar, uuid))
+        }
+        LoginOutcome::TwoFactorRequired(username) => {
+            let pending = state.two_factor_service.begin_login(username).await?;
+            Ok(Json(pending).into_response())
+        }
+    }
+}
+
+pub async fn login_user_two_factor(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    Json(form): Json<TwoFactorLoginForm>,
+) -> AppResult<Response> {
+    state.throttle_service.check_login(ip, None)?;
+    let username = match state
+        .two_factor_service
+        .complete_login(form.pending_token, form.code)
+        .await
+    {
+        Ok(username) => username,
+        Err(LoginError) => {
+            login_failed(&state, ip, None, "two-factor").await?;
+            return Err(LoginError);
+        }
+        Err(err) => return Err(err),
+    };
+    let uuid = state.user_service.start_session(username.clone()).await?;
+    login_succeeded(&state, ip, &username, "two-factor").await?;
+
+    Ok(session_response(jar, uuid))
+}
+
+/// Records a successful login; `method` tells how the user authenticated.
+pub async fn login_succeeded(
+    state: &AppState,
+    ip: IpAddr,
+    username: &str,
+    method: &str,
+) -> AppResult<()> {
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::LoginSucceeded, ip)
+                .actor(username)
+                .target(username)
+                .details(method),
+        )
+        .await
+}
+
+/// Counts a failed attempt towards the lockout and records it. `username` is the name that
+/// was tried, if the method has one.
+async fn login_failed(
+    state: &AppState,
+    ip: IpAddr,
+    username: Option<&str>,
+    method: &str,
+) -> AppResult<()> {
+    state.throttle_service.login_failed(ip, username);
 
-    let cookie = Cookie::build(("session_id", uuid.to_string()))
+    let mut event = NewAuditEvent::new(AuditKind::LoginFailed, ip).details(method);
+    if let Some(username) = username {
+        event = event.target(username);
+    }
+    state.audit_service.record(event).await
+}
+
+pub fn session_cookie(session_id: uuid::Uuid) -> Cookie<'static> {
+    Cookie::build(("session_id", session_id.to_string()))
         .path("/")
         .http_only(true)
         .same_site(SameSite::Lax)
-        .build();
+        .build()
+}
 
-    Ok((jar.add(cookie), StatusCode::NO_CONTENT))
+fn session_response(jar: CookieJar, session_id: uuid::Uuid) -> Response {
+    (jar.add(session_cookie(session_id)), StatusCode::NO_CONTENT).into_response()
 }
 
 pub async fn logout_user(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     jar: CookieJar,
 ) -> AppResult<(CookieJar, StatusCode)> {
     let cookie_opt = jar.get("session_id");
     match cookie_opt {
         None => Err(InternalError("session_id missing".to_string())),
         Some(cookie) => {
-            state
+            let session_id = cookie.value().to_string();
+            let user = state
                 .user_service
-                .logout(cookie.value().to_string())
+                .get_user_by_session(session_id.clone())
                 .await?;
+            state.user_service.logout(session_id).await?;
+
+            if let Some(user) = user {
+                state
+                    .audit_service
+                    .record(
+                        NewAuditEvent::new(AuditKind::Logout, ip)
+                            .actor(&user.username)
+                            .target(&user.username),
+                    )
+                    .await?;
+            }
 
             let removed_cookie = Cookie::build(("session_id", ""))
                 .path("/")
@@ -62,43 +230,97 @@ pub async fn logout_user(
     }
 }
 
-pub async fn validate_session(State(state): State<AppState>, jar: CookieJar) -> JsonResult<User> {
+pub async fn validate_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> JsonResult<AccountDTO> {
     let cookie_opt = jar.get("session_id");
     match cookie_opt {
         None => Err(InternalError("session_id missing".to_string())),
         Some(cookie) => {
             let session_id = cookie.value().to_string();
 
-            let result = state
+            let session = state
+                .user_service
+                .get_session(session_id.clone())
+                .await?
+                .ok_or(LoginError)?;
+            let user = state
                 .user_service
                 .get_user_by_session(session_id)
                 .await?
-                .map(|user| Json(user));
+                .ok_or(LoginError)?;
 
-            Ok(result.ok_or(LoginError)?)
+            Ok(Json(AccountDTO::new(user, session.csrf_token)))
         }
     }
 }
 
 pub async fn signup_user(
     State(state): State<AppState>,
-    Json(form): Json<AuthForm>,
+    ClientIp(ip): ClientIp,
+    Json(form): Json<SignUpForm>,
 ) -> AppResult<StatusCode> {
+    state.throttle_service.check_signup(ip)?;
+    let claim = state.challenge_service.verify(
+        ChallengePurpose::Signup,
+        None,
+        form.challenge.as_deref(),
+        form.solution.as_deref(),
+    )?;
+    state.throttle_service.signup_attempted(ip);
+
+    let admission = state
+        .registration_service
+        .admit(form.invite.as_deref())?;
+    let approval_pending = admission.approval_pending;
+
     state
         .user_service
-        .create_user(form.username, form.password)
+        .create_user(form.username.clone(), form.password, form.email, admission)
         .await?;
-    Ok(StatusCode::NO_CONTENT)
+    claim.redeem();
+    let mut event = NewAuditEvent::new(AuditKind::SignUp, ip)
+        .actor(&form.username)
+        .target(&form.username);
+    if approval_pending {
+        event = event.details("awaiting approval");
+    }
+    state.audit_service.record(event).await?;
+    // The account exists at this point, so a mail failure must not fail the sign-up; the user
+    // can ask for a new link through /auth/email/resend.
+    if let Err(err) = state
+        .email_service
+        .send_verification(form.username.clone())
+        .await
+    {
+        eprintln!("Sending the verification mail to {} failed: {err}", form.username);
+    }
+
+    // The account exists but can't log in until an admin approves it.
+    if approval_pending {
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Ok(StatusCode::NO_CONTENT)
+    }
 }
 
 pub async fn get_user_with_posts(
     State(state): State<AppState>,
     Path(username): Path<String>,
     Query(params): Query<PaginatedPostSearch>,
-) -> JsonResult<Option<UserDTO>> {
+    RawQuery(query): RawQuery,
+) -> AppResult<Response> {
     let user = state.user_service.get_user_by_username(username.clone()).await?;
+    // Accounts awaiting deletion disappear right away, only their purge is delayed. Sign-ups
+    // waiting for approval don't show up before they are approved.
     match user {
-        None => Err(NotFoundError("Could not find user".to_string())),
+        None => {
+            redirect_renamed(&state, username, query, |name| format!("/api/users/{name}")).await
+        }
+        Some(u) if u.deletion_scheduled_at.is_some() || u.approval_pending => {
+            Err(NotFoundError("Could not find user".to_string()))
+        }
         Some(u) => {
             let count = state
                 .post_service
@@ -109,84 +331,302 @@ pub async fn get_user_with_posts(
                 .get_posts_of_user(&u, params.page.map_or(1, |t| t))
                 .await?;
 
-            Ok(Json(Some(UserDTO {
+            Ok(Json(UserDTO {
                 user: u,
                 posts,
                 total_posts: count,
-            })))
+            })
+            .into_response())
         }
     }
 }
 
-pub async fn update_user(
+pub async fn get_users(
+    State(state): State<AppState>,
+    Query(params): Query<UserDirectorySearch>,
+) -> JsonResult<Vec<DirectoryUserDTO>> {
+    let page = params.page.unwrap_or(1).max(1) as u32;
+
+    let result = state
+        .user_service
+        .search_directory(params.q, params.sort, page)
+        .await?;
+
+    Ok(Json(result))
+}
+
+pub async fn autocomplete_users(
+    State(state): State<AppState>,
+    Query(params): Query<AutocompleteSearch>,
+) -> JsonResult<Vec<UserSuggestionDTO>> {
+    let result = state.user_service.suggest_users(params.q).await?;
+
+    Ok(Json(result))
+}
+
+/// Serves the profile page of the frontend, sending visitors of a former username to the
+/// account's current one.
+pub async fn profile_page(
     State(state): State<AppState>,
     Path(username): Path<String>,
+    RawQuery(query): RawQuery,
+    request: Request,
+) -> AppResult<Response> {
+    if state
+        .user_service
+        .get_user_by_username(username.clone())
+        .await?
+        .is_none()
+    {
+        if let Some(current) = state.user_service.find_renamed_user(username).await? {
+            return Ok(renamed_redirect(format!("/users/{current}"), query));
+        }
+    }
+
+    let index = tower_http::services::ServeFile::new("frontend/dist/index.html")
+        .try_call(request)
+        .await
+        .map_err(|e| InternalError(e.to_string()))?;
+    Ok(index.into_response())
+}
+
+/// Answers a request for an unknown username with a redirect if the name belonged
+/// to an account that has since been renamed.
+async fn redirect_renamed(
+    state: &AppState,
+    username: String,
+    query: Option<String>,
+    location: impl FnOnce(&str) -> String,
+) -> AppResult<Response> {
+    match state.user_service.find_renamed_user(username).await? {
+        Some(current) => Ok(renamed_redirect(location(&current), query)),
+        None => Err(NotFoundError("Could not find user".to_string())),
+    }
+}
+
+/// Temporary, since the old name can be released and taken by another account, and browsers
+/// would keep following a cached permanent redirect.
+fn renamed_redirect(path: String, query: Option<String>) -> Response {
+    match query {
+        Some(query) => Redirect::temporary(&format!("{path}?{query}")).into_response(),
+        None => Redirect::temporary(&path).into_response(),
+    }
+}
+
+pub async fn change_username(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     jar: CookieJar,
-    mut multipart: Multipart,
+    Json(form): Json<ChangeUsernameForm>,
 ) -> AppResult<StatusCode> {
-    let cookie = jar.get("session_id").ok_or(InternalError(
-        "Could not update profile picture".to_string(),
-    ))?;
+    let user = crate::controller::current_user(&state, &jar).await?;
 
-    let session_id = cookie.value().to_string();
-    let user = state
+    let username = state
         .user_service
-        .get_user_by_session(session_id)
-        .await?
-        .ok_or(InternalError("Could not create post".to_string()))?;
+        .change_username(&user, form.username)
+        .await?;
+    if username != user.username {
+        state
+            .audit_service
+            .record(
+                NewAuditEvent::new(AuditKind::UsernameChanged, ip)
+                    .actor(&username)
+                    .target(&username)
+                    .details(format!("{} -> {}", user.username, username)),
+            )
+            .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Accepts the profile fields either as JSON or, together with an optional avatar, as
+/// multipart form data.
+pub async fn update_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    request: Request,
+) -> AppResult<Response> {
+    current.require(Scope::ProfileWrite)?;
+    let user = current.user;
 
     if user.username != username {
         return Err(InternalError("Usernames do not match".to_string()));
     }
 
+    let is_json = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    let mut profile = UpdateProfileForm::default();
     let mut avatar: Option<Vec<u8>> = None;
 
-    while let Some(field) = multipart.next_field().await? {
-        let name = field
-            .name()
-            .ok_or(InternalError("Field not found".to_string()))?;
-
-        match name {
-            "avatar" => {
-                let file_data = field.bytes().await;
-
-                match file_data {
-                    Ok(data) => {
-                        if data.is_empty() {
-                            avatar = None
-                        } else {
-                            avatar = Some(data.to_vec())
+    if is_json {
+        match Json::<UpdateProfileForm>::from_request(request, &state).await {
+            Ok(Json(form)) => profile = form,
+            Err(rejection) => return Ok(rejection.into_response()),
+        }
+    } else {
+        let mut multipart = match Multipart::from_request(request, &state).await {
+            Ok(multipart) => multipart,
+            Err(rejection) => return Ok(rejection.into_response()),
+        };
+
+        while let Some(field) = multipart.next_field().await? {
+            let name = field
+                .name()
+                .ok_or(InternalError("Field not found".to_string()))?
+                .to_string();
+
+            match name.as_str() {
+                "avatar" => {
+                    let file_data = field.bytes().await;
+
+                    match file_data {
+                        Ok(data) => {
+                            if data.is_empty() {
+                                avatar = None
+                            } else {
+                                avatar = Some(data.to_vec())
+                            }
                         }
+                        _ => avatar = None,
                     }
-                    _ => avatar = None,
                 }
+                "displayName" => profile.display_name = Some(field.text().await?),
+                "bio" => profile.bio = Some(field.text().await?),
+                // Repeated once per link; a single empty one clears them.
+                "links" => profile
+                    .links
+                    .get_or_insert_with(Vec::new)
+                    .push(field.text().await?),
+                "location" => profile.location = Some(field.text().await?),
+                "pronouns" => profile.pronouns = Some(field.text().await?),
+                _ => {}
             }
-            _ => {}
         }
     }
+
+    state
+        .user_service
+        .update_profile(username.clone(), profile)
+        .await?;
+
     if let Some(avatar) = avatar {
         state
             .user_service
-            .update_user_avatar(username, avatar)
+            .update_user_avatar(username.clone(), avatar)
+            .await?;
+        state
+            .audit_service
+            .record(
+                NewAuditEvent::new(AuditKind::AvatarChanged, ip)
+                    .actor(&username)
+                    .target(&username),
+            )
             .await?;
     }
 
-    Ok(StatusCode::OK)
+    Ok(StatusCode::OK.into_response())
 }
 
+/// Serves the uploaded avatar, or an identicon generated from the username if there is none.
 pub async fn get_user_avatar(
     State(state): State<AppState>,
     Path(username): Path<String>,
-) -> AppResult<impl IntoResponse> {
-    let result = state.user_service.get_user_avatar(username).await?;
-
-    let content_type = "image/png";
+    Query(params): Query<AvatarQuery>,
+    RawQuery(query): RawQuery,
+) -> AppResult<Response> {
+    let Some(user) = state
+        .user_service
+        .get_user_by_username(username.clone())
+        .await?
+    else {
+        return redirect_renamed(&state, username, query, |name| {
+            format!("/api/users/{name}/avatar")
+        })
+        .await;
+    };
 
     let mut headers = HeaderMap::new();
-    headers.insert("Content-Type", content_type.parse().unwrap());
 
-    match result {
-        None => Err(NotFoundError("Could not find image".to_string())),
-        Some(data) => Ok((headers, data)),
+    match user.avatar {
+        Some(data) => {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(image_content_type(&data)));
+            Ok((headers, data).into_response())
+        }
+        None => {
+            let format = params.format.unwrap_or_default();
+            let data = state
+                .avatar_service
+                .default_avatar(&user.username, format)?;
+
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+            // Generated avatars only change if an avatar gets uploaded, which the frontend
+            // already busts with a query parameter.
+            headers.insert(CACHE_CONTROL, HeaderValue::from_static("public, max-age=3600"));
+            Ok((headers, data).into_response())
+        }
+    }
+}
+
+pub async fn delete_account(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    Json(form): Json<DeleteAccountForm>,
+) -> AppResult<(StatusCode, Json<AccountDeletionDTO>)> {
+    let user = crate::controller::current_user(&state, &jar).await?;
+    if user.username != username {
+        return Err(ForbiddenError("You can only delete your own account".to_string()));
+    }
+
+    let deletion_scheduled_at = state.user_service.request_deletion(&user, form).await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::AccountDeletionRequested, ip)
+                .actor(&username)
+                .target(&username)
+                .details(deletion_scheduled_at.to_string()),
+        )
+        .await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(AccountDeletionDTO {
+            deletion_scheduled_at,
+        }),
+    ))
+}
+
+pub async fn restore_account(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+) -> AppResult<StatusCode> {
+    let user = crate::controller::session_user(&state, &jar).await?;
+    if user.username != username {
+        return Err(ForbiddenError("You can only restore your own account".to_string()));
+    }
+
+    if user.deletion_scheduled_at.is_some() {
+        state.user_service.restore_account(&user).await?;
+        state
+            .audit_service
+            .record(
+                NewAuditEvent::new(AuditKind::AccountRestored, ip)
+                    .actor(&username)
+                    .target(&username),
+            )
+            .await?;
     }
+
+    Ok(StatusCode::NO_CONTENT)
 }
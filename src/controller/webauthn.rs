@@ -0,0 +1,74 @@
+use crate::controller::current_user;
+use crate::error::{AppResult, JsonResult};
+use crate::model::webauthn::{
+    AuthenticationChallengeDTO, FinishRegistrationForm, RegistrationChallengeDTO,
+    StartAuthenticationForm, WebauthnCredential,
+};
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+
+pub async fn get_credentials(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> JsonResult<Vec<WebauthnCredential>> {
+    let user = current_user(&state, &jar).await?;
+    let result = state.webauthn_service.get_credentials(user.username).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn delete_credential(
+    State(state): State<AppState>,
+    Path(credential_id): Path<String>,
+    jar: CookieJar,
+) -> AppResult<StatusCode> {
+    let user = current_user(&state, &jar).await?;
+    state
+        .webauthn_service
+        .delete_credential(user.username, credential_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn start_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> JsonResult<RegistrationChallengeDTO> {
+    let user = current_user(&state, &jar).await?;
+    let result = state
+        .webauthn_service
+        .start_registration(user.username)
+        .await?;
+
+    Ok(Json(result))
+}
+
+pub async fn finish_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(form): Json<FinishRegistrationForm>,
+) -> AppResult<(StatusCode, Json<WebauthnCredential>)> {
+    let user = current_user(&state, &jar).await?;
+    let result = state
+        .webauthn_service
+        .finish_registration(user.username, form.challenge_id, form.name, form.credential)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+pub async fn start_authentication(
+    State(state): State<AppState>,
+    Json(form): Json<StartAuthenticationForm>,
+) -> JsonResult<AuthenticationChallengeDTO> {
+    let result = state
+        .webauthn_service
+        .start_authentication(form.username)
+        .await?;
+
+    Ok(Json(result))
+}
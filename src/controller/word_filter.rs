@@ -0,0 +1,68 @@
+use crate::error::{AppResult, JsonResult};
+use crate::extract::{ClientIp, CurrentUser};
+use crate::model::audit::{AuditKind, NewAuditEvent};
+use crate::model::role::Permission;
+use crate::model::word_filter::{CreateWordFilterForm, WordFilter};
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+
+pub async fn get_word_filters(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> JsonResult<Vec<WordFilter>> {
+    current.authorize(Permission::ManageWordFilters)?;
+
+    let result = state.screening_service.get_filters().await?;
+
+    Ok(Json(result))
+}
+
+pub async fn create_word_filter(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+    Json(form): Json<CreateWordFilterForm>,
+) -> AppResult<(StatusCode, Json<WordFilter>)> {
+    current.authorize(Permission::ManageWordFilters)?;
+
+    let result = state
+        .screening_service
+        .add_filter(&current.user, form)
+        .await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::WordFilterAdded, ip)
+                .actor(&current.user.username)
+                .details(format!(
+                    "#{} {}: {}",
+                    result.id, result.action, result.pattern
+                )),
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+pub async fn delete_word_filter(
+    State(state): State<AppState>,
+    Path(filter_id): Path<i32>,
+    ClientIp(ip): ClientIp,
+    current: CurrentUser,
+) -> AppResult<StatusCode> {
+    current.authorize(Permission::ManageWordFilters)?;
+
+    state.screening_service.delete_filter(filter_id).await?;
+    state
+        .audit_service
+        .record(
+            NewAuditEvent::new(AuditKind::WordFilterRemoved, ip)
+                .actor(&current.user.username)
+                .details(format!("#{filter_id}")),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
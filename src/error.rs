@@ -1,9 +1,11 @@
-use axum::http::StatusCode;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
 
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum AppError {
     #[error(transparent)]
     PoolError(#[from] deadpool_diesel::PoolError),
@@ -17,55 +19,241 @@ pub enum AppError {
     InternalError(String),
     #[error(transparent)]
     BcryptError(#[from] bcrypt::BcryptError),
+    #[error("Password hashing failed: {0}")]
+    PasswordHashError(String),
     #[error(transparent)]
     FormError(#[from] axum_extra::extract::multipart::MultipartError),
     #[error("{0}")]
-    SignUpError(String),
-    #[error("{0}")]
     DieselError(String),
+    #[error("{}", .0.iter().map(|e| e.message.as_str()).collect::<Vec<_>>().join(", "))]
+    ValidationError(Vec<FieldError>),
+    #[error("Could not send mail: {0}")]
+    MailError(String),
+    #[error("Identity provider error: {0}")]
+    OidcError(String),
+    #[error("{0}")]
+    ForbiddenError(String),
+    #[error("{0}")]
+    ConflictError(String),
+    #[error("Too many attempts, try again in {0} seconds")]
+    TooManyRequestsError(u64),
 }
 
 impl From<diesel::result::Error> for AppError {
     fn from(value: diesel::result::Error) -> Self {
         match value {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                ref info,
+            ) if info.constraint_name() == Some("users_email_key") => {
+                AppError::ValidationError(vec![FieldError::new(
+                    "email",
+                    "Email address is already in use",
+                )])
+            }
+            // Another request took the name between the availability check and the insert.
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                ref info,
+            ) if info.constraint_name() == Some("users_pkey") => username_taken(),
             diesel::result::Error::DatabaseError(
                 diesel::result::DatabaseErrorKind::UniqueViolation,
                 _,
-            ) => AppError::SignUpError("Username already exists".to_string()),
+            ) => AppError::ConflictError("This already exists".to_string()),
             _ => AppError::DieselError(value.to_string()),
         }
     }
 }
 
+pub fn username_taken() -> AppError {
+    AppError::ValidationError(vec![FieldError::new(
+        "username",
+        "Username is already taken",
+    )])
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<Vec<FieldError>>,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, message) = match &self {
+/// RFC 7807 problem details. Attached to every error response as an extension so that
+/// [`crate::middleware::problem::negotiate_problem_details`] can swap the body when the
+/// client asks for `application/problem+json`.
+#[derive(Serialize, Clone)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>,
+}
+
+impl AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
             AppError::PoolError(_)
             | AppError::DieselError(_)
             | AppError::InteractError(_)
             | AppError::BcryptError(_)
-            | AppError::FormError(_)
+            | AppError::PasswordHashError(_)
             | AppError::InternalError(_)
-            | AppError::SignUpError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            | AppError::MailError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+
+            // 413 when a field runs past the body limit, 400 for broken multipart framing.
+            AppError::FormError(err) => err.status(),
+
+            AppError::OidcError(_) => StatusCode::BAD_GATEWAY,
+
+            AppError::LoginError => StatusCode::UNAUTHORIZED,
+
+            AppError::ForbiddenError(_) => StatusCode::FORBIDDEN,
+
+            AppError::NotFoundError(_) => StatusCode::NOT_FOUND,
+
+            AppError::ConflictError(_) => StatusCode::CONFLICT,
+
+            AppError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+
+            AppError::TooManyRequestsError(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn problem_type(&self) -> (&'static str, &'static str) {
+        match self {
+            AppError::PoolError(_)
+            | AppError::DieselError(_)
+            | AppError::InteractError(_)
+            | AppError::BcryptError(_)
+            | AppError::PasswordHashError(_)
+            | AppError::InternalError(_) => ("internal-error", "Internal server error"),
+            AppError::FormError(_) => ("malformed-form", "Malformed form data"),
+            AppError::MailError(_) => ("mail-delivery-failed", "Mail delivery failed"),
+            AppError::OidcError(_) => ("identity-provider-error", "Identity provider error"),
+            AppError::LoginError => ("invalid-credentials", "Invalid credentials"),
+            AppError::ForbiddenError(_) => ("forbidden", "Forbidden"),
+            AppError::NotFoundError(_) => ("not-found", "Resource not found"),
+            AppError::ConflictError(_) => ("conflict", "Conflict"),
+            AppError::ValidationError(_) => ("validation-failed", "Validation failed"),
+            AppError::TooManyRequestsError(_) => ("too-many-requests", "Too many requests"),
+        }
+    }
+
+    fn field_errors(&self) -> Option<Vec<FieldError>> {
+        match self {
+            AppError::ValidationError(errors) => Some(errors.clone()),
+            _ => None,
+        }
+    }
 
-            AppError::LoginError => (StatusCode::UNAUTHORIZED, self.to_string()),
+    fn to_problem_details(&self) -> ProblemDetails {
+        let (slug, title) = self.problem_type();
 
-            AppError::NotFoundError(_) => (StatusCode::NOT_FOUND, self.to_string()),
-        };
+        ProblemDetails {
+            problem_type: format!("/problems/{slug}"),
+            title: title.to_string(),
+            status: self.status_code().as_u16(),
+            detail: self.to_string(),
+            instance: None,
+            errors: self.field_errors(),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
 
         let body = Json(ErrorResponse {
             error: status.to_string(),
-            message,
+            message: self.to_string(),
+            errors: self.field_errors(),
         });
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(self.to_problem_details());
+        if let AppError::TooManyRequestsError(seconds) = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 pub type AppResult<T> = Result<T, AppError>;
 pub type JsonResult<T> = AppResult<Json<T>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind};
+
+    struct Violation(&'static str);
+
+    impl DatabaseErrorInformation for Violation {
+        fn message(&self) -> &str {
+            "duplicate key value violates unique constraint"
+        }
+        fn details(&self) -> Option<&str> {
+            None
+        }
+        fn hint(&self) -> Option<&str> {
+            None
+        }
+        fn table_name(&self) -> Option<&str> {
+            None
+        }
+        fn column_name(&self) -> Option<&str> {
+            None
+        }
+        fn constraint_name(&self) -> Option<&str> {
+            Some(self.0)
+        }
+        fn statement_position(&self) -> Option<i32> {
+            None
+        }
+    }
+
+    fn unique_violation(constraint: &'static str) -> StatusCode {
+        let error = diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            Box::new(Violation(constraint)),
+        );
+        AppError::from(error).into_response().status()
+    }
+
+    #[test]
+    fn maps_unique_violations_to_client_errors() {
+        assert_eq!(
+            unique_violation("users_pkey"),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            unique_violation("users_email_key"),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(unique_violation("invite_codes_pkey"), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::config::env_or;
+use crate::error::AppError::{ForbiddenError, LoginError};
+use crate::error::{AppError, AppResult};
+use crate::model::api_token::Scope;
+use crate::model::role::Permission;
+use crate::model::user::User;
+use crate::AppState;
+use axum::extract::{ConnectInfo, FromRequestParts, OptionalFromRequestParts};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum_extra::extract::CookieJar;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::OnceLock;
+
+/// Address of the client that sent the request. `X-Forwarded-For` / `X-Real-IP` are only
+/// honoured when `TRUST_PROXY_HEADERS` is enabled, since anyone can send them otherwise.
+pub struct ClientIp(pub IpAddr);
+
+/// Number of reverse proxies in front of the server whose headers are trusted, `0` when
+/// `TRUST_PROXY_HEADERS` is off.
+fn trusted_proxies() -> usize {
+    static TRUSTED: OnceLock<usize> = OnceLock::new();
+    *TRUSTED.get_or_init(|| {
+        if env_or("TRUST_PROXY_HEADERS", false) {
+            env_or("TRUSTED_PROXY_COUNT", 1usize).max(1)
+        } else {
+            0
+        }
+    })
+}
+
+/// Picks the client from `X-Forwarded-For`. Every proxy appends the address it received the
+/// request from, so only the right-most `trusted_proxies` entries are reliable: the one
+/// added by the outermost trusted proxy is the client, anything left of it is whatever the
+/// client chose to send.
+fn forwarded_client(header: &str, trusted_proxies: usize) -> Option<IpAddr> {
+    let hops: Vec<&str> = header.split(',').map(str::trim).collect();
+    let index = hops.len().saturating_sub(trusted_proxies);
+    hops.get(index)?.parse().ok()
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let trusted_proxies = trusted_proxies();
+        if trusted_proxies > 0 {
+            let forwarded = match parts.headers.get("x-forwarded-for") {
+                Some(value) => value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| forwarded_client(value, trusted_proxies)),
+                None => parts
+                    .headers
+                    .get("x-real-ip")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse().ok()),
+            };
+
+            if let Some(ip) = forwarded {
+                return Ok(ClientIp(ip));
+            }
+        }
+
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        Ok(ClientIp(ip))
+    }
+}
+
+/// The caller, authenticated either by the `session_id` cookie or by an API token sent as
+/// `Authorization: Bearer`. Rejects with `401` when neither identifies a user.
+pub struct CurrentUser {
+    pub user: User,
+    /// `None` for sessions, which may do everything the user can.
+    scopes: Option<Vec<String>>,
+}
+
+impl CurrentUser {
+    pub fn require(&self, scope: Scope) -> AppResult<()> {
+        match &self.scopes {
+            Some(scopes) if !scopes.iter().any(|granted| granted == scope.as_str()) => Err(
+                ForbiddenError(format!("This token lacks the {scope} scope")),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Role-gated actions are only available to sessions; tokens can't carry them.
+    pub fn authorize(&self, permission: Permission) -> AppResult<()> {
+        if self.scopes.is_some() {
+            return Err(ForbiddenError(
+                "API tokens can't be used for this".to_string(),
+            ));
+        }
+
+        crate::controller::authorize(&self.user, permission)
+    }
+}
+
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> AppResult<Self> {
+        let bearer = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        if let Some(token) = bearer {
+            let (user, scopes) = state
+                .api_token_service
+                .authenticate(token.trim())
+                .await?
+                .ok_or(LoginError)?;
+
+            return Ok(CurrentUser {
+                user,
+                scopes: Some(scopes),
+            });
+        }
+
+        let jar = CookieJar::from_headers(&parts.headers);
+        let user = crate::controller::current_user(state, &jar).await?;
+
+        Ok(CurrentUser { user, scopes: None })
+    }
+}
+
+/// For routes open to everyone that show more to some callers; a missing or invalid
+/// credential just means an anonymous caller.
+impl OptionalFromRequestParts<AppState> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> AppResult<Option<Self>> {
+        match <CurrentUser as FromRequestParts<AppState>>::from_request_parts(parts, state).await {
+            Ok(current) => Ok(Some(current)),
+            Err(LoginError) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn takes_the_address_added_by_the_proxy() {
+        assert_eq!(forwarded_client("203.0.113.7", 1), ip("203.0.113.7"));
+        assert_eq!(
+            forwarded_client("1.2.3.4, 203.0.113.7", 1),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn skips_the_configured_number_of_proxies() {
+        let header = "1.2.3.4, 203.0.113.7, 198.51.100.2";
+        assert_eq!(forwarded_client(header, 2), ip("203.0.113.7"));
+        assert_eq!(forwarded_client(header, 3), ip("1.2.3.4"));
+        assert_eq!(forwarded_client("203.0.113.7", 2), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn ignores_garbage() {
+        assert_eq!(forwarded_client("1.2.3.4, not-an-ip", 1), None);
+        assert_eq!(forwarded_client("", 1), None);
+    }
+}
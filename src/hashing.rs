@@ -0,0 +1,152 @@
+use crate::config::env_or;
+use crate::error::AppError::PasswordHashError;
+use crate::error::AppResult;
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+use std::sync::OnceLock;
+
+/// Hashes passwords with Argon2id. Hashes produced by earlier versions (bcrypt, or Argon2 with
+/// weaker parameters) still verify, and [`PasswordHasher::needs_rehash`] tells the caller to
+/// replace them once the plaintext is at hand.
+pub struct PasswordHasher {
+    params: Params,
+    dummy_hash: OnceLock<String>,
+}
+
+impl PasswordHasher {
+    /// Defaults follow the OWASP recommendation for Argon2id (19 MiB, 2 passes, 1 lane).
+    pub fn from_env() -> Self {
+        let params = Params::new(
+            env_or("ARGON2_MEMORY_KIB", 19 * 1024),
+            env_or("ARGON2_ITERATIONS", 2),
+            env_or("ARGON2_PARALLELISM", 1),
+            None,
+        )
+        .unwrap_or_else(|err| panic!("Invalid Argon2 parameters: {err}"));
+
+        Self {
+            params,
+            dummy_hash: OnceLock::new(),
+        }
+    }
+
+    /// Hashes on the blocking thread pool: with the default parameters one hash takes tens of
+    /// milliseconds of CPU, which would otherwise stall every other request on the worker.
+    pub async fn hash(&self, password: &str) -> AppResult<String> {
+        let params = self.params.clone();
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || hash_with(params, &password))
+            .await
+            .map_err(|err| PasswordHashError(err.to_string()))?
+    }
+
+    /// Verifies on the blocking thread pool, see [`PasswordHasher::hash`].
+    pub async fn verify(&self, password: &str, hash: &str) -> AppResult<bool> {
+        let params = self.params.clone();
+        let password = password.to_string();
+        let hash = hash.to_string();
+        tokio::task::spawn_blocking(move || verify_with(params, &password, &hash))
+            .await
+            .map_err(|err| PasswordHashError(err.to_string()))?
+    }
+
+    /// Whether `hash` was made with another algorithm or other parameters than the
+    /// configured ones.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        if is_bcrypt(hash) {
+            return true;
+        }
+
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let current = Params::try_from(&parsed).ok();
+
+        parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+            || current.is_none_or(|params| {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            })
+    }
+
+    /// A hash of a random password made with the current parameters. Verified in place of
+    /// a stored hash when the account doesn't exist, so both cases take the same time.
+    pub async fn dummy_hash(&self) -> AppResult<String> {
+        if let Some(hash) = self.dummy_hash.get() {
+            return Ok(hash.clone());
+        }
+
+        let hash = self.hash(&crate::token::generate_token()).await?;
+        Ok(self.dummy_hash.get_or_init(|| hash).clone())
+    }
+}
+
+fn argon2(params: Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+fn hash_with(params: Params, password: &str) -> AppResult<String> {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(|err| PasswordHashError(err.to_string()))?;
+
+    argon2(params)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| PasswordHashError(err.to_string()))
+}
+
+fn verify_with(params: Params, password: &str, hash: &str) -> AppResult<bool> {
+    if is_bcrypt(hash) {
+        return Ok(bcrypt::verify(password, hash)?);
+    }
+
+    let parsed = PasswordHash::new(hash).map_err(|err| PasswordHashError(err.to_string()))?;
+    match argon2(params).verify_password(password.as_bytes(), &parsed) {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(err) => Err(PasswordHashError(err.to_string())),
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn verifies_its_own_hashes() {
+        let hasher = PasswordHasher::from_env();
+        let hash = hasher.hash("correct Horse 9 battery").await.unwrap();
+
+        assert!(hasher
+            .verify("correct Horse 9 battery", &hash)
+            .await
+            .unwrap());
+        assert!(!hasher
+            .verify("correct horse 9 battery", &hash)
+            .await
+            .unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[tokio::test]
+    async fn still_verifies_bcrypt_hashes() {
+        let hasher = PasswordHasher::from_env();
+        let hash = bcrypt::hash("correct Horse 9 battery", 4).unwrap();
+
+        assert!(hasher
+            .verify("correct Horse 9 battery", &hash)
+            .await
+            .unwrap());
+        assert!(hasher.needs_rehash(&hash));
+    }
+}
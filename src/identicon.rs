@@ -0,0 +1,173 @@
+use sha2::{Digest, Sha256};
+
+const GRID: usize = 5;
+/// Half a cell of margin on every side.
+const UNITS: usize = GRID + 1;
+const BACKGROUND: [u8; 3] = [240, 240, 240];
+
+/// A mirrored 5x5 pattern in a single color, derived from the SHA-256 of the username so the
+/// same name always gets the same picture.
+pub struct Identicon {
+    cells: [[bool; GRID]; GRID],
+    color: [u8; 3],
+}
+
+impl Identicon {
+    pub fn new(username: &str) -> Self {
+        let digest = Sha256::digest(username.as_bytes());
+
+        let mut cells = [[false; GRID]; GRID];
+        for (row, line) in cells.iter_mut().enumerate() {
+            for column in 0..GRID.div_ceil(2) {
+                let filled = digest[2 + row * 3 + column] & 1 == 1;
+                line[column] = filled;
+                line[GRID - 1 - column] = filled;
+            }
+        }
+
+        let hue = f32::from(u16::from_be_bytes([digest[0], digest[1]]) % 360);
+        Self {
+            cells,
+            color: hsl_to_rgb(hue, 0.55, 0.55),
+        }
+    }
+
+    pub fn to_svg(&self) -> String {
+        let [r, g, b] = self.color;
+        let [br, bg, bb] = BACKGROUND;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {UNITS} {UNITS}\" \
+             shape-rendering=\"crispEdges\">\
+             <rect width=\"{UNITS}\" height=\"{UNITS}\" fill=\"#{br:02x}{bg:02x}{bb:02x}\"/>\
+             <g fill=\"#{r:02x}{g:02x}{b:02x}\" transform=\"translate(0.5 0.5)\">"
+        );
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (column, _) in cells.iter().enumerate().filter(|(_, filled)| **filled) {
+                svg.push_str(&format!(
+                    "<rect x=\"{column}\" y=\"{row}\" width=\"1\" height=\"1\"/>"
+                ));
+            }
+        }
+        svg.push_str("</g></svg>");
+        svg
+    }
+
+    /// Renders a `size`x`size` RGB PNG; the size is rounded down so cells are an even number
+    /// of pixels wide and the half cell of margin splits evenly, keeping the picture mirrored.
+    pub fn to_png(&self, size: u32) -> Result<Vec<u8>, png::EncodingError> {
+        let unit = (size as usize / UNITS / 2).max(1) * 2;
+        let size = unit * UNITS;
+
+        let mut pixels = Vec::with_capacity(size * size * 3);
+        for y in 0..size {
+            for x in 0..size {
+                // Shift by half a unit so the grid sits in the middle of the margin.
+                let row = (y * 2 / unit).checked_sub(1).map(|row| row / 2);
+                let column = (x * 2 / unit).checked_sub(1).map(|column| column / 2);
+                let filled = matches!((row, column), (Some(row), Some(column))
+                    if row < GRID && column < GRID && self.cells[row][column]);
+
+                pixels.extend_from_slice(if filled { &self.color } else { &BACKGROUND });
+            }
+        }
+
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+
+        Ok(data)
+    }
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [u8; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let second = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let offset = lightness - chroma / 2.0;
+
+    [r, g, b].map(|channel| ((channel + offset) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_name_gives_same_picture() {
+        let first = Identicon::new("alice");
+        let second = Identicon::new("alice");
+        assert_eq!(first.cells, second.cells);
+        assert_eq!(first.color, second.color);
+        assert_eq!(first.to_svg(), second.to_svg());
+        assert_eq!(first.to_png(60).unwrap(), second.to_png(60).unwrap());
+
+        assert_ne!(
+            Identicon::new("alice").to_svg(),
+            Identicon::new("bob").to_svg()
+        );
+    }
+
+    #[test]
+    fn pattern_is_mirrored() {
+        for name in ["alice", "bob", "carol", "dave", ""] {
+            let identicon = Identicon::new(name);
+            for line in identicon.cells {
+                for column in 0..GRID {
+                    assert_eq!(line[column], line[GRID - 1 - column], "{name}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn svg_has_a_rect_per_filled_cell() {
+        let identicon = Identicon::new("alice");
+        let svg = identicon.to_svg();
+        let filled = identicon
+            .cells
+            .iter()
+            .flatten()
+            .filter(|filled| **filled)
+            .count();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</g></svg>"));
+        assert!(svg.contains(&format!("viewBox=\"0 0 {UNITS} {UNITS}\"")));
+        // One more for the background.
+        assert_eq!(svg.matches("<rect ").count(), filled + 1);
+    }
+
+    #[test]
+    fn png_is_a_mirrored_square_of_whole_units() {
+        let identicon = Identicon::new("alice");
+        let data = identicon.to_png(250).unwrap();
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(data))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (240, 240));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+
+        let size = info.width as usize;
+        let pixel = |x: usize, y: usize| &pixels[(y * size + x) * 3..][..3];
+        assert_eq!(pixel(0, 0), BACKGROUND);
+        for y in 0..size {
+            for x in 0..size {
+                assert_eq!(pixel(x, y), pixel(size - 1 - x, y));
+            }
+        }
+    }
+}
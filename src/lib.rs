@@ -6,8 +6,7 @@ pub mod database {
         dotenv().ok();
         let db_url = std::env::var("DATABASE_URL").unwrap();
         let manager = Manager::new(db_url, deadpool_diesel::Runtime::Tokio1);
-        let pool = Pool::builder(manager)
-            .build().unwrap();
-        pool
+        Pool::builder(manager)
+            .build().unwrap()
     }
 }
@@ -0,0 +1,41 @@
+use crate::error::AppError::MailError;
+use crate::error::AppResult;
+use crate::mailer::{Mail, Mailer};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+/// Development stand-in that appends every message to a single text file.
+pub struct FileMailer {
+    path: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> AppResult<()> {
+        let entry = format!(
+            "To: {}\nSubject: {}\nDate: {}\n\n{}\n\n",
+            mail.to,
+            mail.subject,
+            chrono::Utc::now().to_rfc2822(),
+            mail.body
+        );
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+        file.write_all(entry.as_bytes())
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+
+        Ok(())
+    }
+}
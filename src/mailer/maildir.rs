@@ -0,0 +1,61 @@
+use crate::error::AppError::MailError;
+use crate::error::AppResult;
+use crate::mailer::{build_message, Mail, Mailer};
+use lettre::message::Mailbox;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Writes every message into a local Maildir (`tmp/`, `new/`, `cur/`) so it can be opened with
+/// any mail client, which keeps mail flows testable without a network.
+pub struct MaildirMailer {
+    root: PathBuf,
+    from: Mailbox,
+    counter: AtomicU64,
+}
+
+impl MaildirMailer {
+    pub fn new(root: impl Into<PathBuf>, from: Mailbox) -> Self {
+        Self {
+            root: root.into(),
+            from,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    fn unique_name(&self) -> String {
+        let now = chrono::Utc::now();
+        format!(
+            "{}.M{}P{}Q{}.rusty_posts",
+            now.timestamp(),
+            now.timestamp_subsec_micros(),
+            std::process::id(),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for MaildirMailer {
+    async fn send(&self, mail: Mail) -> AppResult<()> {
+        let message = build_message(mail, &self.from)?.formatted();
+        let to_io_error = |e: std::io::Error| MailError(e.to_string());
+
+        for dir in ["tmp", "new", "cur"] {
+            tokio::fs::create_dir_all(self.root.join(dir))
+                .await
+                .map_err(to_io_error)?;
+        }
+
+        // Maildir delivery: write to tmp/ first, then atomically move into new/
+        let name = self.unique_name();
+        let tmp_path = self.root.join("tmp").join(&name);
+        tokio::fs::write(&tmp_path, message)
+            .await
+            .map_err(to_io_error)?;
+        tokio::fs::rename(&tmp_path, self.root.join("new").join(&name))
+            .await
+            .map_err(to_io_error)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::config::env_or;
+use crate::error::AppError::MailError;
+use crate::error::AppResult;
+use lettre::message::Mailbox;
+use lettre::Message;
+use std::sync::Arc;
+
+pub mod file;
+pub mod log;
+pub mod maildir;
+pub mod smtp;
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outbound mail delivery. Implementations are picked at startup by [`create_mailer`].
+#[async_trait::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> AppResult<()>;
+}
+
+/// Builds the mailer selected by the `MAILER` environment variable
+/// (`log`, `file`, `maildir` or `smtp`).
+pub fn create_mailer() -> Arc<dyn Mailer> {
+    match env_or("MAILER", "log".to_string()).as_str() {
+        "file" => Arc::new(file::FileMailer::new(env_or(
+            "MAILER_FILE",
+            "mail.log".to_string(),
+        ))),
+        "maildir" => Arc::new(maildir::MaildirMailer::new(
+            env_or("MAILDIR_PATH", "maildir".to_string()),
+            sender(),
+        )),
+        "smtp" => {
+            let url = std::env::var("SMTP_URL").expect("SMTP_URL must be set when MAILER=smtp");
+            Arc::new(
+                smtp::SmtpMailer::new(&url, sender())
+                    .unwrap_or_else(|err| panic!("SMTP_URL is not a valid relay URL: {err}")),
+            )
+        }
+        _ => Arc::new(log::LogMailer::new(env_or("MAILER_LOG_TOKENS", false))),
+    }
+}
+
+fn sender() -> Mailbox {
+    env_or(
+        "MAIL_FROM",
+        "RustyPosts <no-reply@localhost>".to_string(),
+    )
+    .parse()
+    .unwrap_or_else(|err| panic!("MAIL_FROM is not a valid mailbox: {err}"))
+}
+
+/// Renders `mail` as an RFC 5322 message for the transports that need a real one.
+fn build_message(mail: Mail, from: &Mailbox) -> AppResult<Message> {
+    let to: Mailbox = mail
+        .to
+        .parse()
+        .map_err(|e: lettre::address::AddressError| MailError(e.to_string()))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(mail.subject)
+        .body(mail.body)
+        .map_err(|e| MailError(e.to_string()))
+}
@@ -1,15 +1,25 @@
 use blog_posts::database;
 use deadpool_diesel::postgres::Pool;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
+use model::audit::{AuditKind, NewAuditEvent};
+mod config;
 mod controller;
 mod error;
+mod extract;
+mod hashing;
+mod identicon;
+mod mailer;
+mod middleware;
 mod model;
 mod repository;
 mod schema;
 
 mod service;
+mod token;
+mod validation;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/");
 
@@ -17,6 +27,22 @@ pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/");
 struct AppState {
     user_service: Arc<service::user::UserService>,
     post_service: Arc<service::post::PostService>,
+    password_service: Arc<service::password::PasswordService>,
+    email_service: Arc<service::email::EmailService>,
+    two_factor_service: Arc<service::two_factor::TwoFactorService>,
+    webauthn_service: Arc<service::webauthn::WebauthnService>,
+    throttle_service: Arc<service::throttle::ThrottleService>,
+    api_token_service: Arc<service::api_token::ApiTokenService>,
+    oidc_service: Arc<service::oidc::OidcService>,
+    admin_service: Arc<service::admin::AdminService>,
+    audit_service: Arc<service::audit::AuditService>,
+    export_service: Arc<service::export::ExportService>,
+    avatar_service: Arc<service::avatar::AvatarService>,
+    registration_service: Arc<service::registration::RegistrationService>,
+    challenge_service: Arc<service::challenge::ChallengeService>,
+    rate_limit_service: Arc<service::rate_limit::RateLimitService>,
+    report_service: Arc<service::report::ReportService>,
+    screening_service: Arc<service::screening::ScreeningService>,
 }
 
 impl AppState {
@@ -24,17 +50,150 @@ impl AppState {
         let user_repo = repository::user::UserRepository::new(pool.clone());
         let session_repo = repository::session::SessionRepository::new(pool.clone());
         let post_repo = repository::post::PostRepository::new(pool.clone());
+        let reset_repo = repository::password_reset::PasswordResetRepository::new(pool.clone());
+        let verification_repo =
+            repository::email_verification::EmailVerificationRepository::new(pool.clone());
+        let two_factor_repo = repository::two_factor::TwoFactorRepository::new(pool.clone());
+        let webauthn_repo = repository::webauthn::WebauthnRepository::new(pool.clone());
+        let api_token_repo = repository::api_token::ApiTokenRepository::new(pool.clone());
+        let oidc_repo = repository::oidc::OidcRepository::new(pool.clone());
+        let moderation_repo = repository::moderation::ModerationRepository::new(pool.clone());
+        let audit_repo = repository::audit::AuditRepository::new(pool.clone());
+        let export_repo = repository::export::ExportRepository::new(pool.clone());
+        let report_repo = repository::report::ReportRepository::new(pool.clone());
 
-        let user_service = Arc::new(service::user::UserService::new(user_repo, session_repo));
-        let post_service = Arc::new(service::post::PostService::new(post_repo));
+        let validation_rules = Arc::new(validation::ValidationRules::from_env());
+        let password_hasher = Arc::new(hashing::PasswordHasher::from_env());
+        let mailer = mailer::create_mailer();
+
+        let user_service = Arc::new(service::user::UserService::new(
+            user_repo,
+            session_repo,
+            validation_rules.clone(),
+            password_hasher.clone(),
+        ));
+        let screening_service = Arc::new(service::screening::ScreeningService::new(
+            repository::word_filter::WordFilterRepository::new(pool.clone()),
+            repository::post::PostRepository::new(pool.clone()),
+        ));
+        let post_service = Arc::new(service::post::PostService::new(
+            post_repo,
+            moderation_repo,
+            validation_rules.clone(),
+            screening_service.clone(),
+        ));
+        let password_service = Arc::new(service::password::PasswordService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            repository::session::SessionRepository::new(pool.clone()),
+            reset_repo,
+            mailer.clone(),
+            validation_rules.clone(),
+            password_hasher.clone(),
+        ));
+        let email_service = Arc::new(service::email::EmailService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            verification_repo,
+            mailer.clone(),
+            validation_rules.clone(),
+        ));
+        let throttle_service = Arc::new(service::throttle::ThrottleService::new());
+        let two_factor_service = Arc::new(service::two_factor::TwoFactorService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            two_factor_repo,
+            password_hasher.clone(),
+            throttle_service.clone(),
+        ));
+        let webauthn_service = Arc::new(service::webauthn::WebauthnService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            webauthn_repo,
+        ));
+        let api_token_service = Arc::new(service::api_token::ApiTokenService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            api_token_repo,
+        ));
+        let registration_service = Arc::new(service::registration::RegistrationService::new(
+            repository::invite::InviteRepository::new(pool.clone()),
+        ));
+        let oidc_service = Arc::new(service::oidc::OidcService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            oidc_repo,
+            validation_rules,
+            password_hasher,
+            registration_service.mode(),
+        ));
+        let admin_service = Arc::new(service::admin::AdminService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            repository::session::SessionRepository::new(pool.clone()),
+        ));
+        let audit_service = Arc::new(service::audit::AuditService::new(audit_repo));
+        let export_service = Arc::new(service::export::ExportService::new(
+            repository::user::UserRepository::new(pool.clone()),
+            repository::post::PostRepository::new(pool.clone()),
+            repository::audit::AuditRepository::new(pool.clone()),
+            repository::session::SessionRepository::new(pool.clone()),
+            export_repo,
+        ));
+        let avatar_service = Arc::new(service::avatar::AvatarService::new());
+        let challenge_service = Arc::new(service::challenge::ChallengeService::new());
+        let rate_limit_service = Arc::new(service::rate_limit::RateLimitService::new());
+        let report_service = Arc::new(service::report::ReportService::new(
+            report_repo,
+            repository::user::UserRepository::new(pool.clone()),
+            mailer,
+        ));
 
         Self {
             user_service,
             post_service,
+            password_service,
+            email_service,
+            two_factor_service,
+            webauthn_service,
+            throttle_service,
+            api_token_service,
+            oidc_service,
+            admin_service,
+            audit_service,
+            export_service,
+            avatar_service,
+            registration_service,
+            challenge_service,
+            rate_limit_service,
+            report_service,
+            screening_service,
         }
     }
 }
 
+/// Periodically deletes the accounts whose deletion grace period is over.
+fn spawn_account_purge(state: AppState) {
+    let period = std::time::Duration::from_secs(config::env_or(
+        "ACCOUNT_PURGE_INTERVAL_SECONDS",
+        3600,
+    ));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+
+            let purged = match state.user_service.purge_deleted_accounts().await {
+                Ok(purged) => purged,
+                Err(err) => {
+                    eprintln!("Account purge failed: {err}");
+                    continue;
+                }
+            };
+            for username in purged {
+                let event = NewAuditEvent::system(AuditKind::AccountPurged).target(&username);
+                if let Err(err) = state.audit_service.record(event).await {
+                    eprintln!("Recording the purge of {username} failed: {err}");
+                }
+            }
+        }
+    });
+}
+
 #[tokio::main]
 async fn main() {
     let pool = database::create_pool();
@@ -48,19 +207,32 @@ async fn main() {
     }
 
     let cors = CorsLayer::new()
+        // The same origins the CSRF check accepts, so only those can read credentialed
+        // responses such as the CSRF token.
         .allow_origin(
-            "http://localhost:5173"
-                .parse::<axum::http::HeaderValue>()
-                .unwrap(),
+            middleware::csrf::trusted_origins()
+                .iter()
+                .map(|origin| origin.parse::<axum::http::HeaderValue>().unwrap())
+                .collect::<Vec<_>>(),
         )
         .allow_credentials(true)
+        .allow_headers([
+            axum::http::header::CONTENT_TYPE,
+            axum::http::header::AUTHORIZATION,
+            axum::http::HeaderName::from_static(middleware::csrf::CSRF_HEADER),
+        ])
+        // The API has no PUT or PATCH routes; add them here if that changes.
         .allow_methods([
             axum::http::Method::GET,
             axum::http::Method::POST,
             axum::http::Method::DELETE,
         ]);
 
+    // Axum's default of 2 MB would cut uploads off below the configured image size limit.
+    let body_limit = validation::ValidationRules::from_env().request_body_limit();
+
     let state = AppState::new(pool);
+    spawn_account_purge(state.clone());
     let api_routes = axum::Router::new()
         .route(
             "/posts",
@@ -82,6 +254,122 @@ async fn main() {
             "/posts/{postId}",
             axum::routing::delete(controller::post::delete_user_post),
         )
+        .route(
+            "/posts/{postId}/hide",
+            axum::routing::post(controller::moderation::hide_post),
+        )
+        .route(
+            "/posts/{postId}/unhide",
+            axum::routing::post(controller::moderation::unhide_post),
+        )
+        .route(
+            "/posts/{postId}/release",
+            axum::routing::post(controller::moderation::release_post),
+        )
+        .route(
+            "/posts/{postId}/report",
+            axum::routing::post(controller::report::report_post),
+        )
+        .route(
+            "/moderation/actions",
+            axum::routing::get(controller::moderation::get_actions),
+        )
+        .route(
+            "/moderation/held",
+            axum::routing::get(controller::moderation::get_held_posts),
+        )
+        .route(
+            "/moderation/reports",
+            axum::routing::get(controller::report::get_report_queue),
+        )
+        .route(
+            "/moderation/reports/posts/{postId}",
+            axum::routing::post(controller::report::resolve_post_reports),
+        )
+        .route(
+            "/moderation/reports/users/{username}",
+            axum::routing::post(controller::report::resolve_user_reports),
+        )
+        .route(
+            "/admin/users",
+            axum::routing::get(controller::admin::get_users),
+        )
+        .route(
+            "/admin/users/{username}",
+            axum::routing::delete(controller::admin::delete_user),
+        )
+        .route(
+            "/admin/users/{username}/suspend",
+            axum::routing::post(controller::admin::suspend_user),
+        )
+        .route(
+            "/admin/users/{username}/ban",
+            axum::routing::post(controller::admin::ban_user),
+        )
+        .route(
+            "/admin/users/{username}/reinstate",
+            axum::routing::post(controller::admin::reinstate_user),
+        )
+        .route(
+            "/admin/users/{username}/logout",
+            axum::routing::post(controller::admin::force_logout),
+        )
+        .route(
+            "/admin/users/{username}/avatar",
+            axum::routing::delete(controller::admin::reset_avatar),
+        )
+        .route(
+            "/admin/audit",
+            axum::routing::get(controller::audit::get_events),
+        )
+        .route(
+            "/admin/invites",
+            axum::routing::get(controller::registration::get_all_invites),
+        )
+        .route(
+            "/admin/registrations",
+            axum::routing::get(controller::registration::get_pending_registrations),
+        )
+        .route(
+            "/admin/registrations/{username}/approve",
+            axum::routing::post(controller::registration::approve_registration),
+        )
+        .route(
+            "/admin/registrations/{username}",
+            axum::routing::delete(controller::registration::reject_registration),
+        )
+        .route(
+            "/admin/challenge",
+            axum::routing::get(controller::challenge::get_difficulty),
+        )
+        .route(
+            "/admin/challenge",
+            axum::routing::post(controller::challenge::update_difficulty),
+        )
+        .route(
+            "/admin/word-filters",
+            axum::routing::get(controller::word_filter::get_word_filters),
+        )
+        .route(
+            "/admin/word-filters",
+            axum::routing::post(controller::word_filter::create_word_filter),
+        )
+        .route(
+            "/admin/word-filters/{filterId}",
+            axum::routing::delete(controller::word_filter::delete_word_filter),
+        )
+        .route(
+            "/admin/users/{username}/role",
+            axum::routing::post(controller::moderation::change_role),
+        )
+        .route(
+            "/users",
+            axum::routing::get(controller::user::get_users),
+        )
+        .route(
+            "/users/autocomplete",
+            axum::routing::get(controller::user::autocomplete_users),
+        )
         .route(
             "/users/{username}",
             axum::routing::get(controller::user::get_user_with_posts),
@@ -90,6 +378,30 @@ async fn main() {
             "/users/{username}",
             axum::routing::post(controller::user::update_user),
         )
+        .route(
+            "/users/{username}",
+            axum::routing::delete(controller::user::delete_account),
+        )
+        .route(
+            "/users/{username}/report",
+            axum::routing::post(controller::report::report_user),
+        )
+        .route(
+            "/users/{username}/restore",
+            axum::routing::post(controller::user::restore_account),
+        )
+        .route(
+            "/users/{username}/export",
+            axum::routing::get(controller::export::get_exports),
+        )
+        .route(
+            "/users/{username}/export",
+            axum::routing::post(controller::export::request_export),
+        )
+        .route(
+            "/exports/{token}",
+            axum::routing::get(controller::export::download_export),
+        )
         .route(
             "/users/{username}/avatar",
             axum::routing::get(controller::user::get_user_avatar),
@@ -98,10 +410,18 @@ async fn main() {
             "/auth/login",
             axum::routing::post(controller::user::login_user),
         )
+        .route(
+            "/auth/login/2fa",
+            axum::routing::post(controller::user::login_user_two_factor),
+        )
         .route(
             "/auth/logout",
             axum::routing::post(controller::user::logout_user),
         )
+        .route(
+            "/auth/challenge",
+            axum::routing::get(controller::challenge::get_challenge),
+        )
         .route(
             "/auth/signup",
             axum::routing::post(controller::user::signup_user),
@@ -109,7 +429,136 @@ async fn main() {
         .route(
             "/auth/me",
             axum::routing::get(controller::user::validate_session),
-        );
+        )
+        .route(
+            "/auth/password",
+            axum::routing::post(controller::password::change_password),
+        )
+        .route(
+            "/auth/password/reset",
+            axum::routing::post(controller::password::request_password_reset),
+        )
+        .route(
+            "/auth/password/reset/confirm",
+            axum::routing::post(controller::password::confirm_password_reset),
+        )
+        .route(
+            "/auth/email",
+            axum::routing::post(controller::email::change_email),
+        )
+        .route(
+            "/auth/username",
+            axum::routing::post(controller::user::change_username),
+        )
+        .route(
+            "/auth/email/resend",
+            axum::routing::post(controller::email::resend_verification),
+        )
+        .route(
+            "/auth/email/verify",
+            axum::routing::post(controller::email::verify_email),
+        )
+        .route(
+            "/auth/2fa/enroll",
+            axum::routing::post(controller::two_factor::begin_enrollment),
+        )
+        .route(
+            "/auth/2fa/confirm",
+            axum::routing::post(controller::two_factor::confirm_enrollment),
+        )
+        .route(
+            "/auth/2fa/disable",
+            axum::routing::post(controller::two_factor::disable),
+        )
+        .route(
+            "/auth/2fa/recovery-codes",
+            axum::routing::post(controller::two_factor::regenerate_recovery_codes),
+        )
+        .route(
+            "/auth/webauthn/credentials",
+            axum::routing::get(controller::webauthn::get_credentials),
+        )
+        .route(
+            "/auth/webauthn/credentials/{credentialId}",
+            axum::routing::delete(controller::webauthn::delete_credential),
+        )
+        .route(
+            "/auth/webauthn/register/start",
+            axum::routing::post(controller::webauthn::start_registration),
+        )
+        .route(
+            "/auth/webauthn/register/finish",
+            axum::routing::post(controller::webauthn::finish_registration),
+        )
+        .route(
+            "/auth/webauthn/login/start",
+            axum::routing::post(controller::webauthn::start_authentication),
+        )
+        .route(
+            "/auth/activity",
+            axum::routing::get(controller::audit::get_account_activity),
+        )
+        .route(
+            "/auth/registration",
+            axum::routing::get(controller::registration::get_registration),
+        )
+        .route(
+            "/auth/invites",
+            axum::routing::get(controller::registration::get_invites),
+        )
+        .route(
+            "/auth/invites",
+            axum::routing::post(controller::registration::create_invite),
+        )
+        .route(
+            "/auth/invites/{inviteId}",
+            axum::routing::delete(controller::registration::delete_invite),
+        )
+        .route(
+            "/auth/tokens",
+            axum::routing::get(controller::api_token::get_tokens),
+        )
+        .route(
+            "/auth/tokens",
+            axum::routing::post(controller::api_token::create_token),
+        )
+        .route(
+            "/auth/tokens/{tokenId}",
+            axum::routing::delete(controller::api_token::delete_token),
+        )
+        .route(
+            "/auth/oidc/providers",
+            axum::routing::get(controller::oidc::get_providers),
+        )
+        .route(
+            "/auth/oidc/identities",
+            axum::routing::get(controller::oidc::get_identities),
+        )
+        .route(
+            "/auth/oidc/identities/{provider}",
+            axum::routing::delete(controller::oidc::unlink_identity),
+        )
+        .route(
+            "/auth/oidc/{provider}/login",
+            axum::routing::get(controller::oidc::start_login),
+        )
+        .route(
+            "/auth/oidc/{provider}/link",
+            axum::routing::post(controller::oidc::start_link),
+        )
+        .route(
+            "/auth/oidc/{provider}/callback",
+            axum::routing::get(controller::oidc::callback),
+        )
+        .layer(axum::extract::DefaultBodyLimit::max(body_limit))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::csrf::verify_csrf,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::rate_limit::rate_limit,
+        ));
 
     let assets = tower_http::services::ServeDir::new("frontend/dist/assets");
 
@@ -123,10 +572,22 @@ async fn main() {
         .nest("/api", api_routes)
         .nest_service("/assets", assets)
         .route("/favicon.ico", favicon)
+        .route(
+            "/users/{username}",
+            axum::routing::get(controller::user::profile_page),
+        )
         .fallback(axum::routing::get_service(spa_dir))
         .with_state(state)
+        .layer(axum::middleware::from_fn(
+            middleware::problem::negotiate_problem_details,
+        ))
         .layer(cors);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
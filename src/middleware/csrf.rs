@@ -0,0 +1,206 @@
+use crate::config::{env_list_or, public_url};
+use crate::error::AppError::ForbiddenError;
+use crate::error::AppResult;
+use crate::token::constant_time_eq;
+use crate::AppState;
+use axum::extract::{Request, State};
+use axum::http::header::{ORIGIN, REFERER};
+use axum::http::{HeaderMap, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum_extra::extract::CookieJar;
+use std::sync::OnceLock;
+
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Origins allowed to send mutating requests, and to read credentialed responses through CORS.
+pub fn trusted_origins() -> &'static [String] {
+    static ORIGINS: OnceLock<Vec<String>> = OnceLock::new();
+    ORIGINS.get_or_init(|| {
+        // Only the deployment itself by default. The Vite dev server has to be listed
+        // explicitly, a production setup shouldn't accept requests from whatever runs there.
+        let public_url = public_url();
+        env_list_or("CSRF_TRUSTED_ORIGINS", &[public_url.as_str()])
+            .into_iter()
+            .map(|origin| origin.trim_end_matches('/').to_string())
+            .collect()
+    })
+}
+
+/// `scheme://host[:port]` of a URL, which is all an `Origin` header carries.
+fn origin_of(url: &str) -> Option<&str> {
+    let (scheme, rest) = url.split_once("://")?;
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    Some(&url[..scheme.len() + 3 + end])
+}
+
+/// Browsers attach `Origin` (or at least `Referer`) to every unsafe request, so a foreign
+/// value means the request was forged by another site. Clients that send neither, like curl,
+/// aren't browsers and can't be tricked into riding a victim's cookie.
+fn check_origin(headers: &HeaderMap, trusted_origins: &[String]) -> AppResult<()> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let origin = match (header(ORIGIN), header(REFERER)) {
+        (Some(origin), _) => Some(origin),
+        (None, Some(referer)) => Some(origin_of(referer).unwrap_or(referer)),
+        (None, None) => None,
+    };
+
+    match origin {
+        Some(origin) if !trusted_origins.iter().any(|trusted| trusted == origin) => Err(
+            ForbiddenError("Cross-site request rejected".to_string()),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Guards every request with a method other than GET, HEAD or OPTIONS: it must come from a
+/// trusted origin and, when it is authenticated by the session cookie, echo the session's
+/// CSRF token in `X-CSRF-Token`.
+pub async fn verify_csrf(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request,
+    next: Next,
+) -> AppResult<Response> {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Ok(next.run(request).await);
+    }
+
+    check_origin(request.headers(), trusted_origins())?;
+
+    if let Some(cookie) = jar.get("session_id") {
+        let session = state
+            .user_service
+            .get_session(cookie.value().to_string())
+            .await?;
+
+        // A stale cookie authenticates nothing, the handler will answer it on its own.
+        if let Some(session) = session {
+            let token = request
+                .headers()
+                .get(CSRF_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+
+            if !constant_time_eq(token, &session.csrf_token) {
+                return Err(ForbiddenError("Missing or invalid CSRF token".to_string()));
+            }
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderValue, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn trusted() -> Vec<String> {
+        vec!["https://blog.example".to_string()]
+    }
+
+    fn headers(pairs: &[(axum::http::HeaderName, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn takes_the_origin_of_a_url() {
+        assert_eq!(
+            origin_of("https://blog.example/users/a?page=2"),
+            Some("https://blog.example")
+        );
+        assert_eq!(
+            origin_of("http://localhost:5173#top"),
+            Some("http://localhost:5173")
+        );
+        assert_eq!(origin_of("not a url"), None);
+    }
+
+    #[test]
+    fn rejects_foreign_origins() {
+        let foreign = headers(&[(ORIGIN, "https://evil.example")]);
+        assert!(check_origin(&foreign, &trusted()).is_err());
+
+        // The Origin header wins over a trusted Referer.
+        let foreign = headers(&[
+            (ORIGIN, "https://evil.example"),
+            (REFERER, "https://blog.example/"),
+        ]);
+        assert!(check_origin(&foreign, &trusted()).is_err());
+
+        let own = headers(&[(ORIGIN, "https://blog.example")]);
+        assert!(check_origin(&own, &trusted()).is_ok());
+    }
+
+    #[test]
+    fn falls_back_to_the_referer() {
+        let own = headers(&[(REFERER, "https://blog.example/users/a")]);
+        assert!(check_origin(&own, &trusted()).is_ok());
+
+        let foreign = headers(&[(REFERER, "https://blog.example.evil.example/")]);
+        assert!(check_origin(&foreign, &trusted()).is_err());
+    }
+
+    #[test]
+    fn lets_requests_without_origin_or_referer_through() {
+        assert!(check_origin(&HeaderMap::new(), &trusted()).is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn requires_the_session_csrf_token() {
+        let pool = crate::repository::test_pool();
+        let state = AppState::new(pool.clone());
+        let username = crate::repository::test_user(&pool, "csrf").await;
+        let session_id = state
+            .user_service
+            .start_session(username)
+            .await
+            .unwrap()
+            .to_string();
+        let csrf_token = state
+            .user_service
+            .get_session(session_id.clone())
+            .await
+            .unwrap()
+            .unwrap()
+            .csrf_token;
+
+        let app = Router::new()
+            .route("/", post(|| async { StatusCode::NO_CONTENT }))
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                verify_csrf,
+            ));
+        let send = |token: Option<&str>| {
+            let mut request = Request::post("/")
+                .header("cookie", format!("session_id={session_id}"))
+                .body(axum::body::Body::empty())
+                .unwrap();
+            if let Some(token) = token {
+                request
+                    .headers_mut()
+                    .insert(CSRF_HEADER, token.parse().unwrap());
+            }
+            app.clone().oneshot(request)
+        };
+
+        let status = |response: Result<Response, _>| response.unwrap().status();
+        assert_eq!(status(send(None).await), StatusCode::FORBIDDEN);
+        assert_eq!(status(send(Some("wrong")).await), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(send(Some(&csrf_token)).await),
+            StatusCode::NO_CONTENT
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod csrf;
+pub mod problem;
+pub mod rate_limit;
@@ -0,0 +1,66 @@
+use crate::error::ProblemDetails;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Returns `true` when the `Accept` header ranks `application/problem+json` at least as high
+/// as plain JSON. Clients that don't mention it keep receiving the regular `ErrorResponse`.
+fn prefers_problem_json(accept: &str) -> bool {
+    let mut problem_q = 0.0_f32;
+    let mut json_q = 0.0_f32;
+
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';');
+        let media_type = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        match media_type.as_str() {
+            PROBLEM_JSON => problem_q = problem_q.max(quality),
+            "application/json" | "application/*" | "*/*" => json_q = json_q.max(quality),
+            _ => {}
+        }
+    }
+
+    problem_q > 0.0 && problem_q >= json_q
+}
+
+pub async fn negotiate_problem_details(request: Request, next: Next) -> Response {
+    let wants_problem = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(prefers_problem_json);
+    let instance = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+
+    if !wants_problem {
+        return response;
+    }
+
+    let Some(mut problem) = response.extensions().get::<ProblemDetails>().cloned() else {
+        return response;
+    };
+    problem.instance = Some(instance);
+
+    let body = match serde_json::to_vec(&problem) {
+        Ok(body) => body,
+        Err(_) => return response,
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,94 @@
+use crate::error::AppError::TooManyRequestsError;
+use crate::extract::ClientIp;
+use crate::service::rate_limit::{RateLimitKey, RouteGroup};
+use crate::token::hash_token;
+use crate::AppState;
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+
+/// Endpoints that take credentials or mint something for anonymous callers.
+const AUTH_PREFIXES: [&str; 7] = [
+    "/auth/login",
+    "/auth/signup",
+    "/auth/password/reset",
+    "/auth/email/verify",
+    "/auth/webauthn/login",
+    "/auth/challenge",
+    "/auth/oidc/",
+];
+
+fn route_group(method: &Method, path: &str) -> RouteGroup {
+    let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+
+    if AUTH_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        RouteGroup::Auth
+    } else if !is_read {
+        RouteGroup::Writes
+    } else if path.ends_with("/avatar") || path.ends_with("/image") {
+        RouteGroup::Media
+    } else {
+        RouteGroup::Reads
+    }
+}
+
+/// Limits every API request with a token bucket per route group and per caller, see
+/// [`crate::service::rate_limit::RateLimitService`]. Responses carry the `RateLimit-*`
+/// headers; rejected requests get `429` with `Retry-After`.
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    let group = route_group(request.method(), request.uri().path());
+
+    // Credentials are only checked by the handler, so limited requests don't reach the
+    // database.
+    let mut keys = vec![RateLimitKey::Ip(ip)];
+    if let Some(credential) = credential(request.headers()) {
+        keys.push(RateLimitKey::Credential(hash_token(&credential)));
+    }
+
+    let Some(decision) = state.rate_limit_service.acquire(group, &keys) else {
+        return next.run(request).await;
+    };
+
+    let mut response = match decision.retry_after {
+        Some(retry_after) => TooManyRequestsError(retry_after).into_response(),
+        None => next.run(request).await,
+    };
+    set_header(response.headers_mut(), "ratelimit-limit", decision.limit);
+    set_header(
+        response.headers_mut(),
+        "ratelimit-remaining",
+        decision.remaining,
+    );
+    set_header(response.headers_mut(), "ratelimit-reset", decision.reset);
+
+    response
+}
+
+/// The API token or session id the request carries, the same way [`CurrentUser`] reads them.
+///
+/// [`CurrentUser`]: crate::extract::CurrentUser
+fn credential(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.trim().to_string());
+    }
+
+    CookieJar::from_headers(headers)
+        .get("session_id")
+        .map(|cookie| cookie.value().to_string())
+}
+
+fn set_header(headers: &mut HeaderMap, name: &'static str, value: u64) {
+    headers.insert(name, HeaderValue::from(value));
+}
@@ -0,0 +1,79 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What an API token may be used for. Sessions implicitly hold every scope.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    PostsWrite,
+    ProfileWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::PostsWrite, Scope::ProfileWrite];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::PostsWrite => "posts:write",
+            Scope::ProfileWrite => "profile:write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Queryable, Selectable, Associations, Serialize)]
+#[diesel(table_name = crate::schema::api_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(User, foreign_key = username))]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    pub id: i32,
+    #[serde(skip_serializing)]
+    pub username: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::api_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewApiToken {
+    pub username: String,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenForm {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Tokens without an expiry stay valid until revoked.
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Returned once on creation, the plaintext token can't be recovered afterwards.
+#[derive(Serialize)]
+pub struct CreatedApiTokenDTO {
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+}
@@ -0,0 +1,169 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Kinds of events kept in `audit_events`, stored by their dotted name.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    LoginSucceeded,
+    LoginFailed,
+    Logout,
+    SignUp,
+    AvatarChanged,
+    PostCreated,
+    PostDeleted,
+    PostHidden,
+    PostUnhidden,
+    RoleChanged,
+    UserSuspended,
+    UserBanned,
+    UserReinstated,
+    UserLoggedOut,
+    AvatarReset,
+    UserDeleted,
+    AccountDeletionRequested,
+    AccountRestored,
+    AccountPurged,
+    DataExportRequested,
+    UsernameChanged,
+    InviteCreated,
+    RegistrationApproved,
+    RegistrationRejected,
+    DifficultyChanged,
+    UserWarned,
+    ReportsResolved,
+    PostReleased,
+    WordFilterAdded,
+    WordFilterRemoved,
+}
+
+impl AuditKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditKind::LoginSucceeded => "login.success",
+            AuditKind::LoginFailed => "login.failure",
+            AuditKind::Logout => "logout",
+            AuditKind::SignUp => "signup",
+            AuditKind::AvatarChanged => "avatar.change",
+            AuditKind::PostCreated => "post.create",
+            AuditKind::PostDeleted => "post.delete",
+            AuditKind::PostHidden => "post.hide",
+            AuditKind::PostUnhidden => "post.unhide",
+            AuditKind::RoleChanged => "admin.role_change",
+            AuditKind::UserSuspended => "admin.suspend",
+            AuditKind::UserBanned => "admin.ban",
+            AuditKind::UserReinstated => "admin.reinstate",
+            AuditKind::UserLoggedOut => "admin.force_logout",
+            AuditKind::AvatarReset => "admin.avatar_reset",
+            AuditKind::UserDeleted => "admin.user_delete",
+            AuditKind::AccountDeletionRequested => "account.delete_request",
+            AuditKind::AccountRestored => "account.restore",
+            AuditKind::AccountPurged => "account.purge",
+            AuditKind::DataExportRequested => "account.export",
+            AuditKind::UsernameChanged => "account.rename",
+            AuditKind::InviteCreated => "invite.create",
+            AuditKind::RegistrationApproved => "admin.approve",
+            AuditKind::RegistrationRejected => "admin.reject",
+            AuditKind::DifficultyChanged => "admin.difficulty_change",
+            AuditKind::UserWarned => "moderation.warn",
+            AuditKind::ReportsResolved => "moderation.resolve",
+            AuditKind::PostReleased => "post.release",
+            AuditKind::WordFilterAdded => "admin.filter_add",
+            AuditKind::WordFilterRemoved => "admin.filter_remove",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::audit_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub id: i64,
+    pub event: String,
+    pub actor: Option<String>,
+    pub target: Option<String>,
+    pub ip: Option<String>,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// `actor` is who did it, `target` the account it concerns. Failed logins have no actor.
+/// The names are kept as they were at the time, the account ids are filled in on insert.
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::audit_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewAuditEvent {
+    pub event: String,
+    pub actor: Option<String>,
+    pub target: Option<String>,
+    pub ip: Option<String>,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub actor_id: Option<i64>,
+    pub target_id: Option<i64>,
+}
+
+impl NewAuditEvent {
+    pub fn new(kind: AuditKind, ip: IpAddr) -> Self {
+        Self {
+            ip: Some(ip.to_string()),
+            ..Self::system(kind)
+        }
+    }
+
+    /// For events the server causes on its own, such as purging deleted accounts.
+    pub fn system(kind: AuditKind) -> Self {
+        Self {
+            event: kind.as_str().to_string(),
+            actor: None,
+            target: None,
+            ip: None,
+            details: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            actor_id: None,
+            target_id: None,
+        }
+    }
+
+    pub fn actor(mut self, username: &str) -> Self {
+        self.actor = Some(username.to_string());
+        self
+    }
+
+    pub fn target(mut self, username: &str) -> Self {
+        self.target = Some(username.to_string());
+        self
+    }
+
+    /// Targets the author of a post, who is gone if the post was anonymized.
+    pub fn post_author(mut self, username: Option<&str>) -> Self {
+        self.target = username.map(str::to_string);
+        self
+    }
+
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+/// Filters of the admin audit log; all of them are optional and combined.
+#[derive(Deserialize)]
+pub struct AuditSearch {
+    pub page: Option<i32>,
+    pub event: Option<String>,
+    pub actor: Option<String>,
+    pub target: Option<String>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+/// Details of an event about a post, with the reason a moderator gave if any.
+pub fn post_details(post_id: i32, reason: Option<&str>) -> String {
+    match reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+        Some(reason) => format!("post {post_id}: {reason}"),
+        None => format!("post {post_id}"),
+    }
+}
@@ -0,0 +1,58 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// What a proof-of-work challenge may be spent on. A challenge issued for one purpose is
+/// rejected for the other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChallengePurpose {
+    Signup,
+    Post,
+}
+
+impl ChallengePurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChallengePurpose::Signup => "signup",
+            ChallengePurpose::Post => "post",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ChallengePurpose> {
+        [ChallengePurpose::Signup, ChallengePurpose::Post]
+            .into_iter()
+            .find(|purpose| purpose.as_str() == value)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChallengeQuery {
+    pub purpose: ChallengePurpose,
+}
+
+/// A solution is any string for which `SHA-256(challenge + solution)` starts with at least
+/// `difficulty` zero bits. `required` tells the client whether it has to bother at all.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeDTO {
+    pub challenge: String,
+    pub difficulty: u32,
+    pub expires_at: NaiveDateTime,
+    pub required: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyDTO {
+    pub signup: u32,
+    pub post: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateDifficultyForm {
+    #[serde(default)]
+    pub signup: Option<u32>,
+    #[serde(default)]
+    pub post: Option<u32>,
+}
@@ -0,0 +1,26 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::Deserialize;
+
+#[derive(Insertable, Queryable, Selectable, Associations)]
+#[diesel(table_name = crate::schema::email_verification_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(token_hash))]
+#[diesel(belongs_to(User, foreign_key = username))]
+pub struct EmailVerificationToken {
+    pub token_hash: String,
+    pub username: String,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailForm {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailForm {
+    pub token: String,
+}
@@ -0,0 +1,44 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::Serialize;
+
+pub const EXPORT_PENDING: &str = "pending";
+pub const EXPORT_READY: &str = "ready";
+pub const EXPORT_FAILED: &str = "failed";
+
+/// A takeout archive; the archive itself is only loaded for downloads.
+#[derive(Queryable, Selectable, Associations, Serialize)]
+#[diesel(table_name = crate::schema::data_exports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(User, foreign_key = username))]
+#[serde(rename_all = "camelCase")]
+pub struct DataExport {
+    pub id: i32,
+    #[serde(skip_serializing)]
+    pub username: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::data_exports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewDataExport {
+    pub username: String,
+    pub token_hash: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// The download link can only be shown once, since only its hash is stored.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedExportDTO {
+    #[serde(flatten)]
+    pub export: DataExport,
+    pub download_url: String,
+}
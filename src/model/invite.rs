@@ -0,0 +1,90 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Who may create an account, set through `REGISTRATION_MODE`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationMode {
+    #[default]
+    Open,
+    /// Only with a valid invite code.
+    Invite,
+    /// Nobody, not even with an invite.
+    Closed,
+    /// Anyone, but the account stays locked until an admin approves it. An invite skips the
+    /// queue.
+    Approval,
+}
+
+impl FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "open" => Ok(RegistrationMode::Open),
+            "invite" => Ok(RegistrationMode::Invite),
+            "closed" => Ok(RegistrationMode::Closed),
+            "approval" => Ok(RegistrationMode::Approval),
+            _ => Err(format!("Unknown registration mode {value}")),
+        }
+    }
+}
+
+/// How a sign-up was let in, decided before the account is created.
+#[derive(Default)]
+pub struct Admission {
+    /// Digest of the invite code to redeem along with creating the account.
+    pub invite_hash: Option<String>,
+    pub approval_pending: bool,
+}
+
+#[derive(Queryable, Selectable, Associations, Serialize)]
+#[diesel(table_name = crate::schema::invite_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(User, foreign_key = created_by))]
+#[serde(rename_all = "camelCase")]
+pub struct Invite {
+    pub id: i32,
+    pub created_by: String,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::invite_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewInvite {
+    pub created_by: String,
+    pub code_hash: String,
+    pub max_uses: i32,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInviteForm {
+    #[serde(default)]
+    pub max_uses: Option<i32>,
+    /// Only admins may mint invites that never expire.
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Returned once on creation, the plaintext code can't be recovered afterwards.
+#[derive(Serialize)]
+pub struct CreatedInviteDTO {
+    pub code: String,
+    #[serde(flatten)]
+    pub invite: Invite,
+}
+
+#[derive(Serialize)]
+pub struct RegistrationDTO {
+    pub mode: RegistrationMode,
+}
@@ -1,3 +1,17 @@
 pub mod user;
 pub mod post;
-pub mod session;
\ No newline at end of file
+pub mod session;
+pub mod password_reset;
+pub mod email_verification;
+pub mod two_factor;
+pub mod webauthn;
+pub mod api_token;
+pub mod oidc;
+pub mod role;
+pub mod moderation;
+pub mod audit;
+pub mod export;
+pub mod invite;
+pub mod challenge;
+pub mod report;
+pub mod word_filter;
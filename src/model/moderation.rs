@@ -0,0 +1,52 @@
+use crate::model::role::Role;
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+pub const ACTION_HIDE: &str = "hide";
+pub const ACTION_UNHIDE: &str = "unhide";
+pub const ACTION_DELETE: &str = "delete";
+pub const ACTION_RELEASE: &str = "release";
+
+/// What a moderation action does to its post.
+pub enum PostChange {
+    Hide { at: NaiveDateTime, by: String },
+    Unhide,
+    Release,
+    Delete,
+}
+
+#[derive(Queryable, Selectable, Associations, Serialize)]
+#[diesel(table_name = crate::schema::moderation_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(User, foreign_key = moderator))]
+#[serde(rename_all = "camelCase")]
+pub struct ModerationAction {
+    pub id: i32,
+    pub moderator: Option<String>,
+    pub action: String,
+    pub post_id: i32,
+    pub post_author: Option<String>,
+    pub post_title: String,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::moderation_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewModerationAction {
+    pub moderator: String,
+    pub action: String,
+    pub post_id: i32,
+    pub post_author: Option<String>,
+    pub post_title: String,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeRoleForm {
+    pub role: Role,
+}
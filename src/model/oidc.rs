@@ -0,0 +1,65 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// An account at an external identity provider, identified by the provider's stable `sub`.
+#[derive(Insertable, Queryable, Selectable, Associations, Serialize)]
+#[diesel(table_name = crate::schema::oidc_identities)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(provider, subject))]
+#[diesel(belongs_to(User, foreign_key = username))]
+#[serde(rename_all = "camelCase")]
+pub struct OidcIdentity {
+    pub provider: String,
+    #[serde(skip_serializing)]
+    pub subject: String,
+    #[serde(skip_serializing)]
+    pub username: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+/// Everything needed to finish an authorization request once the provider redirects back.
+#[derive(Insertable, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::oidc_states)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(state_hash))]
+pub struct OidcState {
+    pub state_hash: String,
+    pub provider: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+    /// Set when a logged-in user links the identity instead of logging in with it.
+    pub link_username: Option<String>,
+    pub redirect_to: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct OidcProviderDTO {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorizationUrlDTO {
+    pub authorization_url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcLoginQuery {
+    #[serde(default)]
+    pub redirect_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
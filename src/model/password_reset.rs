@@ -0,0 +1,35 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::Deserialize;
+
+#[derive(Insertable, Queryable, Selectable, Associations)]
+#[diesel(table_name = crate::schema::password_reset_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(token_hash))]
+#[diesel(belongs_to(User, foreign_key = username))]
+pub struct PasswordResetToken {
+    pub token_hash: String,
+    pub username: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordForm {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequestForm {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetConfirmForm {
+    pub token: String,
+    pub new_password: String,
+}
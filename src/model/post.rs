@@ -14,7 +14,17 @@ pub struct Post {
     pub date: NaiveDateTime,
     #[serde(skip_serializing)]
     pub image: Option<Vec<u8>>,
-    pub username: String,
+    /// `None` once the author deleted their account but kept the post.
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hidden_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hidden_by: Option<String>,
+    /// Set when screening held the post back until a moderator releases it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub held_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub held_reason: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -26,9 +36,28 @@ pub struct NewPost {
     pub date: NaiveDateTime,
     pub image: Option<Vec<u8>>,
     pub username: String,
+    pub held_at: Option<NaiveDateTime>,
+    pub held_reason: Option<String>,
+    /// Digest of the normalized body, used to spot the same text being posted over and over.
+    pub content_hash: String,
+}
+
+/// Returned on creation; `heldReason` is only set if the post awaits review.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedPostDTO {
+    pub id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub held_reason: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct PaginatedPostSearch {
     pub page: Option<i32>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct ModerationForm {
+    #[serde(default)]
+    pub reason: Option<String>,
 }
\ No newline at end of file
@@ -0,0 +1,176 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Why something was reported, stored in `reports.reason` by its lowercase name.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportReason {
+    Spam,
+    Harassment,
+    Hate,
+    Violence,
+    Sexual,
+    Impersonation,
+    Misinformation,
+    Other,
+}
+
+impl ReportReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportReason::Spam => "spam",
+            ReportReason::Harassment => "harassment",
+            ReportReason::Hate => "hate",
+            ReportReason::Violence => "violence",
+            ReportReason::Sexual => "sexual",
+            ReportReason::Impersonation => "impersonation",
+            ReportReason::Misinformation => "misinformation",
+            ReportReason::Other => "other",
+        }
+    }
+}
+
+/// What a moderator did about the open reports of a post or user, stored in
+/// `reports.resolution`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportAction {
+    /// Close the reports without doing anything.
+    Dismiss,
+    /// Hide the reported post.
+    Hide,
+    /// Notify the author that they broke the rules.
+    Warn,
+    /// Suspend the author, which needs [`crate::model::role::Permission::ManageUsers`].
+    Suspend,
+}
+
+impl ReportAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportAction::Dismiss => "dismiss",
+            ReportAction::Hide => "hide",
+            ReportAction::Warn => "warn",
+            ReportAction::Suspend => "suspend",
+        }
+    }
+}
+
+/// The post or account a report is about.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ReportTarget {
+    Post(i32),
+    User(String),
+}
+
+impl ReportTarget {
+    /// Rebuilds the target from the columns of `reports`, where exactly one of them is set.
+    pub fn from_columns(post_id: Option<i32>, reported_user: Option<String>) -> Self {
+        match (post_id, reported_user) {
+            (Some(post_id), _) => ReportTarget::Post(post_id),
+            (None, Some(username)) => ReportTarget::User(username),
+            (None, None) => unreachable!("ruled out by the check constraint on reports"),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ReportTarget::Post(_) => "post",
+            ReportTarget::User(_) => "user",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ReportTarget::Post(post_id) => format!("post {post_id}"),
+            ReportTarget::User(username) => format!("user {username}"),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::reports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub id: i32,
+    pub reporter: String,
+    pub post_id: Option<i32>,
+    pub reported_user: Option<String>,
+    pub reason: String,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+}
+
+impl Report {
+    pub fn target(&self) -> ReportTarget {
+        ReportTarget::from_columns(self.post_id, self.reported_user.clone())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::reports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewReport {
+    pub reporter: String,
+    pub post_id: Option<i32>,
+    pub reported_user: Option<String>,
+    pub reason: String,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct ReportForm {
+    pub reason: ReportReason,
+    #[serde(default)]
+    pub details: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveReportsForm {
+    pub action: ReportAction,
+    /// Passed on to the hide, warning or suspension.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Required to suspend.
+    #[serde(default)]
+    pub duration_hours: Option<i64>,
+}
+
+/// Open reports of one post or user, as listed in the moderation queue.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportQueueEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_title: Option<String>,
+    /// The reported user, or the author of the reported post.
+    pub username: Option<String>,
+    pub report_count: i64,
+    /// How many of the reports give each reason.
+    pub reasons: BTreeMap<String, i64>,
+    pub first_reported_at: NaiveDateTime,
+    pub last_reported_at: NaiveDateTime,
+    pub reports: Vec<Report>,
+}
+
+/// A post or user with open reports, aggregated over those reports.
+#[derive(Queryable)]
+pub struct OpenReportSummary {
+    pub post_id: Option<i32>,
+    pub reported_user: Option<String>,
+    pub report_count: i64,
+    pub first_reported_at: Option<NaiveDateTime>,
+    pub last_reported_at: Option<NaiveDateTime>,
+}
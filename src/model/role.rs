@@ -0,0 +1,81 @@
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Stored in `users.role`; the database only accepts these three values.
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Moderator,
+    Admin,
+}
+
+/// Something only some roles may do. Controllers check these through
+/// [`crate::controller::authorize`] rather than comparing roles directly.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Hide or delete posts of other users.
+    ModeratePosts,
+    /// Promote or demote other users.
+    ManageRoles,
+    /// Suspend, ban, log out and delete other users.
+    ManageUsers,
+    /// Read the audit log of every account.
+    ViewAuditLog,
+    /// Approve sign-ups and mint invites without the limits of regular users.
+    ManageRegistrations,
+    /// Edit the terms that block posts or hold them for review.
+    ManageWordFilters,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        [Role::User, Role::Moderator, Role::Admin]
+            .into_iter()
+            .find(|role| role.as_str() == value)
+    }
+
+    pub fn grants(&self, permission: Permission) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Moderator => permission == Permission::ModeratePosts,
+            Role::User => false,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ToSql<Text, Pg> for Role {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for Role {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Role::parse(&value).ok_or_else(|| format!("Unknown role {value}").into())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::model::user::User;
+use chrono::NaiveDateTime;
 use diesel::{Associations, Insertable, Queryable, Selectable};
 
 #[derive(Insertable, Queryable, Selectable, Associations)]
@@ -9,4 +10,7 @@ use diesel::{Associations, Insertable, Queryable, Selectable};
 pub struct Session {
     pub session_id: String,
     pub username: String,
+    /// Synchronizer token that cookie-authenticated mutations must echo in `X-CSRF-Token`.
+    pub csrf_token: String,
+    pub created_at: NaiveDateTime,
 }
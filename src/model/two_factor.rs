@@ -0,0 +1,76 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Identifiable, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Associations, Identifiable)]
+#[diesel(table_name = crate::schema::recovery_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(User, foreign_key = username))]
+pub struct RecoveryCode {
+    pub id: i32,
+    pub username: String,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::recovery_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewRecoveryCode {
+    pub username: String,
+    pub code_hash: String,
+}
+
+#[derive(Insertable, Queryable, Selectable, Associations)]
+#[diesel(table_name = crate::schema::pending_logins)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(token_hash))]
+#[diesel(belongs_to(User, foreign_key = username))]
+pub struct PendingLogin {
+    pub token_hash: String,
+    pub username: String,
+    pub expires_at: NaiveDateTime,
+    pub attempts: i32,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodeForm {
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorLoginForm {
+    pub pending_token: String,
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableTwoFactorForm {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorEnrollmentDTO {
+    pub secret: String,
+    pub otpauth_uri: String,
+    /// Base64 encoded PNG of the QR code for `otpauth_uri`.
+    pub qr_png: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesDTO {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingLoginDTO {
+    pub two_factor_required: bool,
+    pub pending_token: String,
+    pub expires_in: i64,
+}
@@ -1,12 +1,16 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::{AsChangeset, Identifiable, Insertable, Queryable, Selectable};
 use serde::{Deserialize, Serialize};
+use crate::error::AppError::ForbiddenError;
+use crate::error::AppResult;
 use crate::model::post::Post;
+use crate::model::role::Role;
 
 #[derive(Serialize, Deserialize, Queryable, Selectable, Insertable, Identifiable, Default)]
 #[diesel(table_name = crate::schema::users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[diesel(primary_key(username))]
+#[serde(rename_all = "camelCase")]
 pub struct User {
     pub username: String,
     #[serde(skip_serializing)]
@@ -14,6 +18,90 @@ pub struct User {
     #[serde(skip_serializing)]
     pub avatar: Option<Vec<u8>>,
     pub joined: NaiveDate,
+    #[serde(skip_serializing)]
+    pub email: Option<String>,
+    #[serde(skip_serializing)]
+    pub email_verified: bool,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    #[serde(skip_serializing)]
+    pub totp_enabled: bool,
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    pub role: Role,
+    #[serde(skip_serializing)]
+    pub suspended_until: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub suspension_reason: Option<String>,
+    #[serde(skip_serializing)]
+    pub banned: bool,
+    #[serde(skip_serializing)]
+    pub deletion_scheduled_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub anonymize_posts: bool,
+    pub display_name: Option<String>,
+    /// Markdown, rendered by the frontend.
+    pub bio: Option<String>,
+    pub links: Vec<String>,
+    pub location: Option<String>,
+    pub pronouns: Option<String>,
+    #[serde(skip_serializing)]
+    pub approval_pending: bool,
+    #[serde(skip_serializing)]
+    pub invited_by: Option<i32>,
+}
+
+impl User {
+    pub fn is_suspended(&self, now: NaiveDateTime) -> bool {
+        self.banned || self.suspended_until.is_some_and(|until| until > now)
+    }
+
+    /// Fails with `403` while the account is banned, suspended or awaiting approval.
+    pub fn ensure_active(&self, now: NaiveDateTime) -> AppResult<()> {
+        if self.approval_pending {
+            return Err(ForbiddenError(
+                "This account is waiting for an admin to approve it".to_string(),
+            ));
+        }
+        if self.banned {
+            return Err(ForbiddenError("This account has been banned".to_string()));
+        }
+
+        match self.suspended_until {
+            Some(until) if until > now => Err(ForbiddenError(format!(
+                "This account is suspended until {} UTC",
+                until.format("%Y-%m-%d %H:%M")
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What the logged-in user sees about their own account.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDTO {
+    #[serde(flatten)]
+    pub user: User,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+    pub csrf_token: String,
+    /// Set while the account waits to be purged; logging in and restoring it cancels that.
+    pub deletion_scheduled_at: Option<NaiveDateTime>,
+}
+
+impl AccountDTO {
+    pub fn new(user: User, csrf_token: String) -> Self {
+        Self {
+            email: user.email.clone(),
+            email_verified: user.email_verified,
+            two_factor_enabled: user.totp_enabled,
+            csrf_token,
+            deletion_scheduled_at: user.deletion_scheduled_at,
+            user,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -33,4 +121,172 @@ pub struct UpdateUser {
     pub username: String,
     pub password: Option<String>,
     pub avatar: Option<Vec<u8>>,
-}
\ No newline at end of file
+}
+
+/// Profile fields accepted by `update_user`. Fields that are left out stay as they are, empty
+/// ones are cleared.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfileForm {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub links: Option<Vec<String>>,
+    pub location: Option<String>,
+    pub pronouns: Option<String>,
+}
+
+/// `None` skips a column, `Some(None)` clears it.
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = crate::schema::users)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateProfile {
+    pub display_name: Option<Option<String>>,
+    pub bio: Option<Option<String>>,
+    pub links: Option<Vec<String>>,
+    pub location: Option<Option<String>>,
+    pub pronouns: Option<Option<String>>,
+}
+
+impl UpdateProfile {
+    pub fn is_empty(&self) -> bool {
+        self.display_name.is_none()
+            && self.bio.is_none()
+            && self.links.is_none()
+            && self.location.is_none()
+            && self.pronouns.is_none()
+    }
+}
+
+impl From<UpdateProfileForm> for UpdateProfile {
+    fn from(form: UpdateProfileForm) -> Self {
+        let clean = |value: Option<String>| {
+            value.map(|value| Some(value.trim().to_string()).filter(|value| !value.is_empty()))
+        };
+
+        Self {
+            display_name: clean(form.display_name),
+            bio: clean(form.bio),
+            links: form.links.map(|links| {
+                links
+                    .into_iter()
+                    .map(|link| link.trim().to_string())
+                    .filter(|link| !link.is_empty())
+                    .collect()
+            }),
+            location: clean(form.location),
+            pronouns: clean(form.pronouns),
+        }
+    }
+}
+
+/// What admins see about an account in the user management API.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminUserDTO {
+    #[serde(flatten)]
+    pub user: User,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub suspended_until: Option<NaiveDateTime>,
+    pub suspension_reason: Option<String>,
+    pub banned: bool,
+    pub approval_pending: bool,
+    /// Id of the invite the account signed up with.
+    pub invited_by: Option<i32>,
+}
+
+impl From<User> for AdminUserDTO {
+    fn from(user: User) -> Self {
+        Self {
+            email: user.email.clone(),
+            email_verified: user.email_verified,
+            suspended_until: user.suspended_until,
+            suspension_reason: user.suspension_reason.clone(),
+            banned: user.banned,
+            approval_pending: user.approval_pending,
+            invited_by: user.invited_by,
+            user,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminUserSearch {
+    pub page: Option<i32>,
+    pub q: Option<String>,
+}
+
+/// Orders of the public user directory. `relevance` falls back to `joined` without a query.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UserSort {
+    #[default]
+    Relevance,
+    Joined,
+    Posts,
+}
+
+#[derive(Deserialize)]
+pub struct UserDirectorySearch {
+    pub page: Option<i32>,
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: UserSort,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryUserDTO {
+    #[serde(flatten)]
+    pub user: User,
+    pub total_posts: i64,
+}
+
+#[derive(Deserialize)]
+pub struct AutocompleteSearch {
+    pub q: String,
+}
+
+/// Just enough to show a suggestion in a mention picker.
+#[derive(Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSuggestionDTO {
+    pub username: String,
+    pub display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuspendForm {
+    pub duration_hours: i64,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// What happens to the posts of a deleted account.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PostDisposal {
+    #[default]
+    Delete,
+    /// Keep the posts without an author.
+    Anonymize,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeUsernameForm {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountForm {
+    pub password: String,
+    #[serde(default)]
+    pub posts: PostDisposal,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDeletionDTO {
+    pub deletion_scheduled_at: NaiveDateTime,
+}
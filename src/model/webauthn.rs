@@ -0,0 +1,77 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
+    RequestChallengeResponse,
+};
+
+#[derive(Insertable, Queryable, Selectable, Associations, Serialize, Clone)]
+#[diesel(table_name = crate::schema::webauthn_credentials)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(credential_id))]
+#[diesel(belongs_to(User, foreign_key = username))]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnCredential {
+    #[serde(rename = "id")]
+    pub credential_id: String,
+    #[serde(skip_serializing)]
+    pub username: String,
+    pub name: String,
+    /// Serialized `webauthn_rs::prelude::Passkey`, which holds the public key.
+    #[serde(skip_serializing)]
+    pub passkey: String,
+    pub sign_count: i64,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Queryable, Selectable, Associations)]
+#[diesel(table_name = crate::schema::webauthn_challenges)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(challenge_id))]
+#[diesel(belongs_to(User, foreign_key = username))]
+pub struct WebauthnChallenge {
+    pub challenge_id: String,
+    pub username: String,
+    pub ceremony: String,
+    pub state: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationChallengeDTO {
+    pub challenge_id: String,
+    #[serde(flatten)]
+    pub options: CreationChallengeResponse,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationChallengeDTO {
+    pub challenge_id: String,
+    #[serde(flatten)]
+    pub options: RequestChallengeResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishRegistrationForm {
+    pub challenge_id: String,
+    pub name: String,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct StartAuthenticationForm {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyLoginForm {
+    pub challenge_id: String,
+    pub credential: PublicKeyCredential,
+}
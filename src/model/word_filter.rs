@@ -0,0 +1,64 @@
+use crate::model::user::User;
+use chrono::NaiveDateTime;
+use diesel::{Associations, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// What happens to a post that matches a word filter, stored in `word_filters.action`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// The post is rejected.
+    Block,
+    /// The post is held for review.
+    Flag,
+}
+
+impl FilterAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterAction::Block => "block",
+            FilterAction::Flag => "flag",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<FilterAction> {
+        [FilterAction::Block, FilterAction::Flag]
+            .into_iter()
+            .find(|action| action.as_str() == value)
+    }
+}
+
+#[derive(Queryable, Selectable, Associations, Serialize)]
+#[diesel(table_name = crate::schema::word_filters)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(User, foreign_key = created_by))]
+#[serde(rename_all = "camelCase")]
+pub struct WordFilter {
+    pub id: i32,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub action: String,
+    pub created_by: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::word_filters)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewWordFilter {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub action: String,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Plain patterns match whole words, regular expressions anywhere. Both ignore case.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWordFilterForm {
+    pub pattern: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    pub action: FilterAction,
+}
@@ -0,0 +1,90 @@
+use crate::error::AppResult;
+use crate::model::api_token::{ApiToken, NewApiToken};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper,
+};
+
+pub struct ApiTokenRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl ApiTokenRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    pub async fn get_tokens_of_user(&self, user: String) -> AppResult<Vec<ApiToken>> {
+        use crate::schema::api_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                api_tokens
+                    .filter(username.eq(user))
+                    .select(ApiToken::as_select())
+                    .order_by(created_at.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn add_token(&self, token: NewApiToken) -> AppResult<ApiToken> {
+        use crate::schema::api_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::insert_into(api_tokens::table())
+                    .values(token)
+                    .returning(ApiToken::as_returning())
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Returns `false` if `user` has no token with the given id.
+    pub async fn delete_token(&self, user: String, token_id: i32) -> AppResult<bool> {
+        use crate::schema::api_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let deleted = conn
+            .interact(move |conn| {
+                diesel::delete(api_tokens::table())
+                    .filter(id.eq(token_id))
+                    .filter(username.eq(user))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(deleted > 0)
+    }
+
+    /// Looks up an unexpired token by its hash and records the use in the same statement.
+    pub async fn touch_valid_token(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<ApiToken>> {
+        use crate::schema::api_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::update(api_tokens::table())
+                    .filter(token_hash.eq(hash))
+                    .filter(expires_at.is_null().or(expires_at.gt(now)))
+                    .set(last_used_at.eq(Some(now)))
+                    .returning(ApiToken::as_returning())
+                    .get_result(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
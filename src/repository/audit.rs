@@ -0,0 +1,191 @@
+use crate::error::AppResult;
+use crate::model::audit::{AuditEvent, AuditSearch, NewAuditEvent};
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    QueryResult, RunQueryDsl, SelectableHelper,
+};
+
+const EVENTS_PER_PAGE: i64 = 50;
+
+pub struct AuditRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl AuditRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    pub async fn add_event(&self, new_event: NewAuditEvent) -> AppResult<()> {
+        use crate::schema::audit_events::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            let new_event = NewAuditEvent {
+                actor_id: account_id(conn, new_event.actor.as_deref())?,
+                target_id: account_id(conn, new_event.target.as_deref())?,
+                ..new_event
+            };
+            diesel::insert_into(audit_events::table())
+                .values(new_event)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Newest first.
+    pub async fn search_events(
+        &self,
+        search: AuditSearch,
+        page: u32,
+    ) -> AppResult<Vec<AuditEvent>> {
+        use crate::schema::audit_events::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * EVENTS_PER_PAGE;
+                let mut statement = audit_events.select(AuditEvent::as_select()).into_boxed();
+
+                if let Some(kind) = search.event {
+                    statement = statement.filter(event.eq(kind));
+                }
+                if let Some(user) = search.actor {
+                    statement = statement.filter(actor.eq(user));
+                }
+                if let Some(user) = search.target {
+                    statement = statement.filter(target.eq(user));
+                }
+                if let Some(since) = search.since {
+                    statement = statement.filter(created_at.ge(since));
+                }
+                if let Some(until) = search.until {
+                    statement = statement.filter(created_at.lt(until));
+                }
+
+                statement
+                    .order_by(id.desc())
+                    .offset(offset_count)
+                    .limit(EVENTS_PER_PAGE)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Every event done by or to the account now called `user`, oldest first. Events are matched
+    /// on the account rather than the name, so they follow renames and aren't inherited by
+    /// whoever takes the name over later.
+    pub async fn get_all_events_of_user(&self, user: String) -> AppResult<Vec<AuditEvent>> {
+        use crate::schema::audit_events::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let Some(account) = account_id(conn, Some(&user))? else {
+                    return Ok(Vec::new());
+                };
+
+                audit_events
+                    .filter(actor_id.eq(account).or(target_id.eq(account)))
+                    .select(AuditEvent::as_select())
+                    .order_by(id.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Events done by or to the account now called `user`, newest first.
+    pub async fn get_events_of_user(&self, user: String, page: u32) -> AppResult<Vec<AuditEvent>> {
+        use crate::schema::audit_events::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let Some(account) = account_id(conn, Some(&user))? else {
+                    return Ok(Vec::new());
+                };
+                let offset_count: i64 = (page - 1) as i64 * EVENTS_PER_PAGE;
+
+                audit_events
+                    .filter(actor_id.eq(account).or(target_id.eq(account)))
+                    .select(AuditEvent::as_select())
+                    .order_by(id.desc())
+                    .offset(offset_count)
+                    .limit(EVENTS_PER_PAGE)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
+
+fn account_id(conn: &mut PgConnection, username: Option<&str>) -> QueryResult<Option<i64>> {
+    use crate::schema::users;
+    match username {
+        Some(username) => users::table
+            .find(username)
+            .select(users::id)
+            .first(conn)
+            .optional(),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::audit::AuditKind;
+    use crate::repository::user::UserRepository;
+    use crate::repository::{test_pool, test_user};
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn events_follow_the_account_not_the_name() {
+        let pool = test_pool();
+        let audit = AuditRepository::new(pool.clone());
+        let users = UserRepository::new(pool.clone());
+        let now = chrono::Utc::now().naive_utc();
+
+        let owner = test_user(&pool, "audit").await;
+        audit
+            .add_event(NewAuditEvent::system(AuditKind::SignUp).actor(&owner))
+            .await
+            .unwrap();
+
+        let renamed = format!("{owner}_r");
+        users
+            .rename_user(owner.clone(), renamed.clone(), now)
+            .await
+            .unwrap();
+        let events = audit.get_all_events_of_user(renamed.clone()).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].actor.as_deref(), Some(owner.as_str()));
+
+        users.delete_user(renamed.clone()).await.unwrap();
+        users
+            .create_new_user(crate::model::user::User {
+                username: renamed.clone(),
+                password: String::new(),
+                joined: chrono::Utc::now().date_naive(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(audit
+            .get_all_events_of_user(renamed.clone())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(audit
+            .get_events_of_user(renamed, 1)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::AppResult;
+use crate::model::email_verification::EmailVerificationToken;
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{ExpressionMethods, OptionalExtension, RunQueryDsl, SelectableHelper};
+
+pub struct EmailVerificationRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl EmailVerificationRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    /// Stores a fresh token, discarding any token previously issued to the same user.
+    pub async fn replace_token(&self, token: EmailVerificationToken) -> AppResult<()> {
+        use crate::schema::email_verification_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(email_verification_tokens::table())
+                .filter(username.eq(&token.username))
+                .execute(conn)?;
+
+            diesel::insert_into(email_verification_tokens::table())
+                .values(&token)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Deletes the token and returns it if it had not expired yet.
+    pub async fn consume_token(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<EmailVerificationToken>> {
+        use crate::schema::email_verification_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::delete(email_verification_tokens::table())
+                    .filter(token_hash.eq(hash))
+                    .filter(expires_at.gt(now))
+                    .returning(EmailVerificationToken::as_returning())
+                    .get_result(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,230 @@
+use crate::error::AppResult;
+use crate::model::export::{DataExport, NewDataExport, EXPORT_FAILED, EXPORT_PENDING};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct ExportRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl ExportRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    /// Returns `None` without adding anything if the user already has an export being prepared.
+    pub async fn add_export(&self, export: NewDataExport) -> AppResult<Option<DataExport>> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                // The only unique index a fresh token can run into is the one on pending exports.
+                diesel::insert_into(data_exports::table())
+                    .values(export)
+                    .on_conflict_do_nothing()
+                    .returning(DataExport::as_returning())
+                    .get_result(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn get_exports_of_user(&self, user: String) -> AppResult<Vec<DataExport>> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                data_exports
+                    .filter(username.eq(user))
+                    .select(DataExport::as_select())
+                    .order_by(created_at.desc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Exports of `user` that are being prepared or can still be downloaded.
+    pub async fn count_unexpired_exports(
+        &self,
+        user: String,
+        now: NaiveDateTime,
+    ) -> AppResult<i64> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                data_exports
+                    .filter(username.eq(user))
+                    .filter(status.ne(EXPORT_FAILED))
+                    .filter(expires_at.gt(now))
+                    .count()
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Marks exports that have been pending since before `started_before` as failed; their
+    /// build was cut short, e.g. by a restart.
+    pub async fn fail_stale_exports(
+        &self,
+        started_before: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> AppResult<()> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::update(data_exports::table())
+                .filter(status.eq(EXPORT_PENDING))
+                .filter(created_at.lt(started_before))
+                .set((status.eq(EXPORT_FAILED), completed_at.eq(now)))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Stores the outcome of building an export; `data` is `None` when it failed. Exports that
+    /// were given up on as stale in the meantime are left alone.
+    pub async fn finish_export(
+        &self,
+        export_id: i32,
+        new_status: &'static str,
+        data: Option<Vec<u8>>,
+        now: NaiveDateTime,
+        expiry: NaiveDateTime,
+    ) -> AppResult<()> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::update(data_exports.find(export_id))
+                .filter(status.eq(EXPORT_PENDING))
+                .set((
+                    status.eq(new_status),
+                    archive.eq(data),
+                    completed_at.eq(now),
+                    expires_at.eq(expiry),
+                ))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// The export behind a download link along with its archive, unless the link expired.
+    pub async fn find_export(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<(DataExport, Option<Vec<u8>>)>> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                data_exports
+                    .filter(token_hash.eq(hash))
+                    .filter(expires_at.gt(now))
+                    .select((DataExport::as_select(), archive))
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn delete_expired_exports(&self, now: NaiveDateTime) -> AppResult<()> {
+        use crate::schema::data_exports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(data_exports::table())
+                .filter(expires_at.le(now))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::export::EXPORT_READY;
+    use crate::repository::{test_pool, test_user};
+    use chrono::{Duration, Utc};
+
+    fn export(username: &str, now: NaiveDateTime) -> NewDataExport {
+        NewDataExport {
+            username: username.to_string(),
+            token_hash: crate::token::generate_token(),
+            status: EXPORT_PENDING.to_string(),
+            created_at: now,
+            expires_at: now + Duration::hours(1),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn builds_one_export_at_a_time() {
+        let pool = test_pool();
+        let exports = ExportRepository::new(pool.clone());
+        let username = test_user(&pool, "export").await;
+        let now = Utc::now().naive_utc();
+
+        let first = exports.add_export(export(&username, now)).await.unwrap();
+        assert!(first.is_some());
+        assert!(exports
+            .add_export(export(&username, now))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            exports
+                .count_unexpired_exports(username.clone(), now)
+                .await
+                .unwrap(),
+            1
+        );
+
+        exports
+            .finish_export(
+                first.unwrap().id,
+                EXPORT_READY,
+                Some(Vec::new()),
+                now,
+                now + Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        assert!(exports
+            .add_export(export(&username, now))
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            exports
+                .count_unexpired_exports(username.clone(), now)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            exports
+                .count_unexpired_exports(username, now + Duration::hours(2))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::error::AppResult;
+use crate::model::invite::{Invite, NewInvite};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct InviteRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl InviteRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    pub async fn get_invites_of_user(&self, user: String) -> AppResult<Vec<Invite>> {
+        use crate::schema::invite_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                invite_codes
+                    .filter(created_by.eq(user))
+                    .select(Invite::as_select())
+                    .order_by(created_at.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Every invite, newest first.
+    pub async fn get_all_invites(&self, page: u32) -> AppResult<Vec<Invite>> {
+        let invites_per_page: i64 = 50;
+
+        use crate::schema::invite_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * invites_per_page;
+                invite_codes
+                    .select(Invite::as_select())
+                    .order_by(id.desc())
+                    .offset(offset_count)
+                    .limit(invites_per_page)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Invites of `user` that can still be redeemed.
+    pub async fn count_usable_invites(&self, user: String, now: NaiveDateTime) -> AppResult<i64> {
+        use crate::schema::invite_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                invite_codes
+                    .filter(created_by.eq(user))
+                    .filter(uses.lt(max_uses))
+                    .filter(expires_at.is_null().or(expires_at.gt(now)))
+                    .count()
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn add_invite(&self, invite: NewInvite) -> AppResult<Invite> {
+        use crate::schema::invite_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::insert_into(invite_codes::table())
+                    .values(invite)
+                    .returning(Invite::as_returning())
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Deletes the invite if `owner` created it, or regardless of its creator if `owner` is
+    /// `None`. Returns `false` if there is no such invite.
+    pub async fn delete_invite(&self, invite_id: i32, owner: Option<String>) -> AppResult<bool> {
+        use crate::schema::invite_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let deleted = conn
+            .interact(move |conn| {
+                let mut statement = diesel::delete(invite_codes::table())
+                    .filter(id.eq(invite_id))
+                    .into_boxed();
+                if let Some(owner) = owner {
+                    statement = statement.filter(created_by.eq(owner));
+                }
+                statement.execute(conn)
+            })
+            .await??;
+
+        Ok(deleted > 0)
+    }
+}
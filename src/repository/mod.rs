@@ -1,3 +1,55 @@
 pub mod user;
 pub mod post;
-pub mod session;
\ No newline at end of file
+pub mod session;
+pub mod password_reset;
+pub mod email_verification;
+pub mod two_factor;
+pub mod webauthn;
+pub mod api_token;
+pub mod oidc;
+pub mod moderation;
+pub mod audit;
+pub mod export;
+pub mod invite;
+pub mod report;
+pub mod word_filter;
+
+/// Connects to `DATABASE_URL` for tests, running the migrations first. Tests using it are
+/// marked `#[ignore]` and run with `cargo test -- --ignored`.
+#[cfg(test)]
+pub fn test_pool() -> deadpool_diesel::postgres::Pool {
+    use diesel::Connection;
+    use diesel_migrations::MigrationHarness;
+    use std::sync::OnceLock;
+
+    static MIGRATED: OnceLock<()> = OnceLock::new();
+
+    dotenv::dotenv().ok();
+    let url =
+        std::env::var("DATABASE_URL").expect("tests that need the database need DATABASE_URL");
+
+    MIGRATED.get_or_init(|| {
+        let mut conn = diesel::PgConnection::establish(&url).unwrap();
+        conn.run_pending_migrations(crate::MIGRATIONS).unwrap();
+    });
+
+    let manager = deadpool_diesel::postgres::Manager::new(url, deadpool_diesel::Runtime::Tokio1);
+    deadpool_diesel::postgres::Pool::builder(manager).build().unwrap()
+}
+
+/// Creates an account with a unique name starting with `prefix` and returns the name.
+#[cfg(test)]
+pub async fn test_user(pool: &deadpool_diesel::postgres::Pool, prefix: &str) -> String {
+    let username = format!("{prefix}_{}", &crate::token::generate_token()[..12]);
+    user::UserRepository::new(pool.clone())
+        .create_new_user(crate::model::user::User {
+            username: username.clone(),
+            password: String::new(),
+            joined: chrono::Utc::now().date_naive(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    username
+}
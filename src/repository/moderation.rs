@@ -0,0 +1,176 @@
+use crate::error::AppResult;
+use crate::model::moderation::{ModerationAction, NewModerationAction, PostChange};
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use chrono::NaiveDateTime;
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct ModerationRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl ModerationRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    /// Applies `change` to the post and logs `new_action` in one transaction, so the log
+    /// neither misses an action that took effect nor lists one that failed. Returns `false`,
+    /// logging nothing, if the post is gone.
+    pub async fn apply_action(
+        &self,
+        change: PostChange,
+        new_action: NewModerationAction,
+    ) -> AppResult<bool> {
+        use crate::schema::{moderation_actions, posts};
+        let conn = self.connection_pool.get().await?;
+
+        let applied = conn
+            .interact(move |conn| {
+                conn.transaction(|conn| {
+                    let post = posts::table.find(new_action.post_id);
+                    let changed = match change {
+                        PostChange::Hide { at, by } => diesel::update(post)
+                            .set((posts::hidden_at.eq(Some(at)), posts::hidden_by.eq(Some(by))))
+                            .execute(conn)?,
+                        PostChange::Unhide => diesel::update(post)
+                            .set((
+                                posts::hidden_at.eq(None::<NaiveDateTime>),
+                                posts::hidden_by.eq(None::<String>),
+                            ))
+                            .execute(conn)?,
+                        PostChange::Release => diesel::update(post)
+                            .set((
+                                posts::held_at.eq(None::<NaiveDateTime>),
+                                posts::held_reason.eq(None::<String>),
+                            ))
+                            .execute(conn)?,
+                        PostChange::Delete => diesel::delete(post).execute(conn)?,
+                    };
+                    if changed == 0 {
+                        return Ok(false);
+                    }
+
+                    diesel::insert_into(moderation_actions::table)
+                        .values(new_action)
+                        .execute(conn)?;
+                    Ok::<_, diesel::result::Error>(true)
+                })
+            })
+            .await??;
+
+        Ok(applied)
+    }
+
+    pub async fn fetch_actions_on_page(&self, page: u32) -> AppResult<Vec<ModerationAction>> {
+        let actions_per_page: i64 = 50;
+
+        use crate::schema::moderation_actions::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * actions_per_page;
+
+                moderation_actions
+                    .select(ModerationAction::as_select())
+                    .order_by(created_at.desc())
+                    .offset(offset_count)
+                    .limit(actions_per_page)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::moderation::{ACTION_DELETE, ACTION_HIDE};
+    use crate::model::post::NewPost;
+    use crate::repository::post::PostRepository;
+    use crate::repository::test_pool;
+    use crate::repository::user::UserRepository;
+
+    fn action(moderator: &str, name: &str, post_id: i32) -> NewModerationAction {
+        NewModerationAction {
+            moderator: moderator.to_string(),
+            action: name.to_string(),
+            post_id,
+            post_author: Some(moderator.to_string()),
+            post_title: "Title".to_string(),
+            reason: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    async fn logged_actions(pool: &deadpool_diesel::postgres::Pool, post: i32) -> Vec<String> {
+        use crate::schema::moderation_actions::dsl::*;
+        let conn = pool.get().await.unwrap();
+        conn.interact(move |conn| {
+            moderation_actions
+                .filter(post_id.eq(post))
+                .order_by(id.asc())
+                .select(action)
+                .load(conn)
+        })
+        .await
+        .unwrap()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn changes_the_post_and_logs_the_action_together() {
+        let pool = test_pool();
+        let moderator = crate::repository::test_user(&pool, "moderator").await;
+        let repository = ModerationRepository::new(pool.clone());
+        let posts = PostRepository::new(pool.clone());
+
+        let post = posts
+            .create_post(NewPost {
+                title: "Title".to_string(),
+                body: "Body".to_string(),
+                date: chrono::Utc::now().naive_utc(),
+                image: None,
+                username: moderator.clone(),
+                held_at: None,
+                held_reason: None,
+                content_hash: String::new(),
+            })
+            .await
+            .unwrap();
+
+        let hide = PostChange::Hide {
+            at: chrono::Utc::now().naive_utc(),
+            by: moderator.clone(),
+        };
+        assert!(repository
+            .apply_action(hide, action(&moderator, ACTION_HIDE, post))
+            .await
+            .unwrap());
+        let hidden = posts.fetch_post(post).await.unwrap().unwrap();
+        assert_eq!(hidden.hidden_by, Some(moderator.clone()));
+
+        assert!(repository
+            .apply_action(PostChange::Delete, action(&moderator, ACTION_DELETE, post))
+            .await
+            .unwrap());
+        // The post is gone, so a second deletion changes nothing and isn't logged either.
+        assert!(!repository
+            .apply_action(PostChange::Delete, action(&moderator, ACTION_DELETE, post))
+            .await
+            .unwrap());
+
+        assert_eq!(
+            logged_actions(&pool, post).await,
+            [ACTION_HIDE, ACTION_DELETE]
+        );
+
+        UserRepository::new(pool)
+            .delete_user(moderator)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::AppResult;
+use crate::model::oidc::{OidcIdentity, OidcState};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct OidcRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl OidcRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    /// Stores a new authorization request, dropping the expired ones on the way.
+    pub async fn add_state(&self, state: OidcState) -> AppResult<()> {
+        use crate::schema::oidc_states::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(oidc_states::table())
+                .filter(expires_at.lt(chrono::Utc::now().naive_utc()))
+                .execute(conn)?;
+
+            diesel::insert_into(oidc_states::table())
+                .values(state)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Deletes and returns the state if it hasn't expired, so every state is used at most once.
+    pub async fn consume_state(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<OidcState>> {
+        use crate::schema::oidc_states::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::delete(oidc_states::table())
+                    .filter(state_hash.eq(hash))
+                    .filter(expires_at.gt(now))
+                    .returning(OidcState::as_returning())
+                    .get_result(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn find_identity(
+        &self,
+        provider_id: String,
+        subject_id: String,
+    ) -> AppResult<Option<OidcIdentity>> {
+        use crate::schema::oidc_identities::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                oidc_identities
+                    .find((provider_id, subject_id))
+                    .select(OidcIdentity::as_select())
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn get_identities_of_user(&self, user: String) -> AppResult<Vec<OidcIdentity>> {
+        use crate::schema::oidc_identities::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                oidc_identities
+                    .filter(username.eq(user))
+                    .select(OidcIdentity::as_select())
+                    .order_by(created_at.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn add_identity(&self, identity: OidcIdentity) -> AppResult<()> {
+        use crate::schema::oidc_identities::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::insert_into(oidc_identities::table())
+                .values(identity)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn record_login(
+        &self,
+        provider_id: String,
+        subject_id: String,
+        address: Option<String>,
+        now: NaiveDateTime,
+    ) -> AppResult<()> {
+        use crate::schema::oidc_identities::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::update(oidc_identities.find((provider_id, subject_id)))
+                .set((email.eq(address), last_login_at.eq(Some(now))))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Returns `false` if `user` has no identity at the given provider.
+    pub async fn delete_identity(&self, user: String, provider_id: String) -> AppResult<bool> {
+        use crate::schema::oidc_identities::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let deleted = conn
+            .interact(move |conn| {
+                diesel::delete(oidc_identities::table())
+                    .filter(provider.eq(provider_id))
+                    .filter(username.eq(user))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(deleted > 0)
+    }
+}
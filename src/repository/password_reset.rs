@@ -0,0 +1,165 @@
+use crate::error::AppResult;
+use crate::model::password_reset::PasswordResetToken;
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+pub struct PasswordResetRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl PasswordResetRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    /// Stores a fresh token, discarding any token previously issued to the same user.
+    pub async fn replace_token(&self, token: PasswordResetToken) -> AppResult<()> {
+        use crate::schema::password_reset_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(password_reset_tokens::table())
+                .filter(username.eq(&token.username))
+                .execute(conn)?;
+
+            diesel::insert_into(password_reset_tokens::table())
+                .values(&token)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn find_valid_token(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<PasswordResetToken>> {
+        use crate::schema::password_reset_tokens::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                password_reset_tokens
+                    .find(hash)
+                    .filter(used_at.is_null())
+                    .filter(expires_at.gt(now))
+                    .select(PasswordResetToken::as_select())
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Redeems the token, sets `password_hash` as the owner's password and ends all of the
+    /// owner's sessions in one transaction, returning the owner. Nothing changes unless the
+    /// token is still unused and has not expired; marking it used in the same statement that
+    /// checks it keeps it single-use under concurrency.
+    pub async fn reset_password(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+        password_hash: String,
+    ) -> AppResult<Option<String>> {
+        use crate::schema::{password_reset_tokens, sessions, users};
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                conn.transaction(|conn| {
+                    let owner = diesel::update(password_reset_tokens::table)
+                        .filter(password_reset_tokens::token_hash.eq(hash))
+                        .filter(password_reset_tokens::used_at.is_null())
+                        .filter(password_reset_tokens::expires_at.gt(now))
+                        .set(password_reset_tokens::used_at.eq(now))
+                        .returning(password_reset_tokens::username)
+                        .get_result::<String>(conn)
+                        .optional()?;
+                    let Some(owner) = owner else {
+                        return Ok(None);
+                    };
+
+                    diesel::update(users::table.find(&owner))
+                        .set(users::password.eq(password_hash))
+                        .execute(conn)?;
+                    diesel::delete(sessions::table.filter(sessions::username.eq(&owner)))
+                        .execute(conn)?;
+
+                    Ok::<_, diesel::result::Error>(Some(owner))
+                })
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::session::Session;
+    use crate::repository::session::SessionRepository;
+    use crate::repository::user::UserRepository;
+    use crate::repository::{test_pool, test_user};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn resets_the_password_and_ends_sessions_once() {
+        let pool = test_pool();
+        let resets = PasswordResetRepository::new(pool.clone());
+        let sessions = SessionRepository::new(pool.clone());
+        let users = UserRepository::new(pool.clone());
+        let now = Utc::now().naive_utc();
+
+        let username = test_user(&pool, "reset").await;
+        resets
+            .replace_token(PasswordResetToken {
+                token_hash: format!("{username}-token"),
+                username: username.clone(),
+                expires_at: now + Duration::minutes(5),
+                used_at: None,
+            })
+            .await
+            .unwrap();
+        sessions
+            .add_session(Session {
+                session_id: format!("{username}-session"),
+                username: username.clone(),
+                csrf_token: String::new(),
+                created_at: now,
+            })
+            .await
+            .unwrap();
+
+        let owner = resets
+            .reset_password(format!("{username}-token"), now, "new hash".to_string())
+            .await
+            .unwrap();
+        assert_eq!(owner, Some(username.clone()));
+        let user = users
+            .get_user_by_username(username.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.password, "new hash");
+        assert!(sessions
+            .get_sessions_of_user(username.clone())
+            .await
+            .unwrap()
+            .is_empty());
+
+        let again = resets
+            .reset_password(format!("{username}-token"), now, "other hash".to_string())
+            .await
+            .unwrap();
+        assert_eq!(again, None);
+        let user = users.get_user_by_username(username).await.unwrap().unwrap();
+        assert_eq!(user.password, "new hash");
+    }
+}
@@ -1,13 +1,18 @@
 use crate::error::AppResult;
 use crate::model::post::{NewPost, Post};
 use crate::model::user::User;
+use chrono::NaiveDateTime;
 use deadpool_diesel::postgres::{Manager, Object};
 use deadpool_diesel::Pool;
 use diesel::associations::HasTable;
+use diesel::sql_types::{Bytea, Nullable};
 use diesel::{
-    BelongingToDsl, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+    BelongingToDsl, ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl,
+    RunQueryDsl, SelectableHelper,
 };
 
+diesel::define_sql_function!(fn octet_length(a: Nullable<Bytea>) -> Nullable<Int4>);
+
 pub struct PostRepository {
     connection_pool: Pool<Manager, Object>,
 }
@@ -26,7 +31,13 @@ impl PostRepository {
             .interact(move |conn| {
                 let offset_count: i64 = (page - 1) as i64 * posts_per_page;
 
+                // Posts of accounts scheduled for deletion go away with the account, anonymized
+                // posts have no author and stay.
                 posts::table()
+                    .left_join(crate::schema::users::table)
+                    .filter(crate::schema::users::deletion_scheduled_at.is_null())
+                    .filter(hidden_at.is_null())
+                    .filter(held_at.is_null())
                     .select(Post::as_select())
                     .order_by(date.desc())
                     .offset(offset_count)
@@ -55,16 +66,45 @@ impl PostRepository {
         Ok(result)
     }
 
-    pub async fn create_post(&self, post: NewPost) -> AppResult<()> {
+    /// Like [`PostRepository::fetch_post`], also telling whether the author's account is
+    /// scheduled for deletion.
+    pub async fn fetch_post_with_author_leaving(
+        &self,
+        post_id: i32,
+    ) -> AppResult<Option<(Post, bool)>> {
+        use crate::schema::{posts, users};
+        let conn = self.connection_pool.get().await?;
+
+        let result = conn
+            .interact(move |conn| {
+                posts::table
+                    .left_join(users::table)
+                    .filter(posts::id.eq(post_id))
+                    .select((
+                        Post::as_select(),
+                        users::deletion_scheduled_at.nullable().is_not_null(),
+                    ))
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Returns the id of the new post.
+    pub async fn create_post(&self, post: NewPost) -> AppResult<i32> {
         use crate::schema::posts::dsl::*;
         let conn = self.connection_pool.get().await?;
-        conn.interact(move |conn| {
-            diesel::insert_into(posts::table())
-                .values(post)
-                .execute(conn)
-        })
-        .await??;
-        Ok(())
+        let result = conn
+            .interact(move |conn| {
+                diesel::insert_into(posts::table())
+                    .values(post)
+                    .returning(id)
+                    .get_result(conn)
+            })
+            .await??;
+        Ok(result)
     }
 
     pub async fn get_post_count_by_username(&self, username: String) -> AppResult<i64> {
@@ -74,6 +114,8 @@ impl PostRepository {
             .interact(move |conn| {
                 posts
                     .filter(crate::schema::posts::dsl::username.eq(username))
+                    .filter(crate::schema::posts::dsl::hidden_at.is_null())
+                    .filter(crate::schema::posts::dsl::held_at.is_null())
                     .count()
                     .get_result(conn)
             })
@@ -96,6 +138,8 @@ impl PostRepository {
                     ..Default::default()
                 };
                 Post::belonging_to(&user)
+                    .filter(crate::schema::posts::dsl::hidden_at.is_null())
+                    .filter(crate::schema::posts::dsl::held_at.is_null())
                     .select(Post::as_select())
                     .order_by(crate::schema::posts::dsl::date.desc())
                     .offset(offset_count)
@@ -107,6 +151,101 @@ impl PostRepository {
         Ok(result)
     }
 
+    /// Posts waiting for review, oldest first.
+    pub async fn fetch_held_posts(&self, page: u32) -> AppResult<Vec<Post>> {
+        let posts_per_page: i64 = 20;
+
+        use crate::schema::posts::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * posts_per_page;
+
+                posts
+                    .filter(held_at.is_not_null())
+                    .select(Post::as_select())
+                    .order_by(held_at.asc())
+                    .offset(offset_count)
+                    .limit(posts_per_page)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Posts `user` created since `since`, held ones included.
+    pub async fn count_posts_since(&self, user: String, since: NaiveDateTime) -> AppResult<i64> {
+        use crate::schema::posts::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                posts
+                    .filter(username.eq(user))
+                    .filter(date.gt(since))
+                    .count()
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Authors of the posts with the given content digest since `since`, each one once.
+    pub async fn get_authors_of_content(
+        &self,
+        hash: String,
+        since: NaiveDateTime,
+    ) -> AppResult<Vec<Option<String>>> {
+        use crate::schema::posts::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                posts
+                    .filter(content_hash.eq(hash))
+                    .filter(date.gt(since))
+                    .select(username)
+                    .distinct()
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Bytes taken up by the images of `user`'s posts.
+    pub async fn get_image_bytes_of_user(&self, user: String) -> AppResult<i64> {
+        use crate::schema::posts::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                posts
+                    .filter(username.eq(user))
+                    .select(diesel::dsl::sum(octet_length(image)))
+                    .first::<Option<i64>>(conn)
+            })
+            .await??;
+
+        Ok(result.unwrap_or(0))
+    }
+
+    /// Every post of `user`, hidden ones included, oldest first.
+    pub async fn get_all_posts_by_username(&self, user: String) -> AppResult<Vec<Post>> {
+        use crate::schema::posts::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                posts
+                    .filter(username.eq(user))
+                    .select(Post::as_select())
+                    .order_by(date.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
     pub async fn delete_post_belonging_to_username(
         &self,
         post_id: i32,
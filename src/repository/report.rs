@@ -0,0 +1,159 @@
+use crate::error::AppResult;
+use crate::model::report::{NewReport, OpenReportSummary, Report, ReportTarget};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct ReportRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl ReportRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    /// Returns `false` if the reporter already has an open report about the same target.
+    pub async fn add_report(&self, report: NewReport) -> AppResult<bool> {
+        use crate::schema::reports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let inserted = conn
+            .interact(move |conn| {
+                diesel::insert_into(reports::table())
+                    .values(report)
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(inserted > 0)
+    }
+
+    /// Posts and users with open reports, the most reported first.
+    pub async fn get_open_summaries(&self, page: u32) -> AppResult<Vec<OpenReportSummary>> {
+        let targets_per_page: i64 = 20;
+
+        use crate::schema::reports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * targets_per_page;
+                reports
+                    .filter(resolved_at.is_null())
+                    .group_by((post_id, reported_user))
+                    .select((
+                        post_id,
+                        reported_user,
+                        diesel::dsl::count_star(),
+                        diesel::dsl::min(created_at),
+                        diesel::dsl::max(created_at),
+                    ))
+                    .order_by((
+                        diesel::dsl::count_star().desc(),
+                        diesel::dsl::max(created_at).desc(),
+                    ))
+                    .offset(offset_count)
+                    .limit(targets_per_page)
+                    .load::<OpenReportSummary>(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Open reports about any of the given posts or users, oldest first.
+    pub async fn get_open_reports(
+        &self,
+        post_ids: Vec<i32>,
+        usernames: Vec<String>,
+    ) -> AppResult<Vec<Report>> {
+        use crate::schema::reports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                reports
+                    .filter(resolved_at.is_null())
+                    .filter(post_id.eq_any(post_ids).or(reported_user.eq_any(usernames)))
+                    .select(Report::as_select())
+                    .order_by(created_at.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Title and author of each of the given posts.
+    pub async fn get_post_summaries(
+        &self,
+        post_ids: Vec<i32>,
+    ) -> AppResult<Vec<(i32, String, Option<String>)>> {
+        use crate::schema::posts::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                posts
+                    .filter(id.eq_any(post_ids))
+                    .select((id, title, username))
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn count_open_reports(&self, target: ReportTarget) -> AppResult<i64> {
+        use crate::schema::reports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let mut query = reports.filter(resolved_at.is_null()).into_boxed();
+                query = match target {
+                    ReportTarget::Post(target_id) => query.filter(post_id.eq(target_id)),
+                    ReportTarget::User(target_name) => query.filter(reported_user.eq(target_name)),
+                };
+                query.count().get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Closes every open report about `target`. Returns how many were closed.
+    pub async fn resolve_reports(
+        &self,
+        target: ReportTarget,
+        moderator: String,
+        action: &'static str,
+        now: NaiveDateTime,
+    ) -> AppResult<usize> {
+        use crate::schema::reports::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let resolved = conn
+            .interact(move |conn| {
+                let changes = (
+                    resolved_at.eq(now),
+                    resolved_by.eq(moderator),
+                    resolution.eq(action),
+                );
+                let open = reports.filter(resolved_at.is_null());
+                match target {
+                    ReportTarget::Post(target_id) => {
+                        diesel::update(open.filter(post_id.eq(target_id)))
+                            .set(changes)
+                            .execute(conn)
+                    }
+                    ReportTarget::User(target_name) => {
+                        diesel::update(open.filter(reported_user.eq(target_name)))
+                            .set(changes)
+                            .execute(conn)
+                    }
+                }
+            })
+            .await??;
+
+        Ok(resolved)
+    }
+}
@@ -6,7 +6,7 @@ use deadpool_diesel::Pool;
 use diesel::associations::HasTable;
 use diesel::QueryDsl;
 use diesel::{ExpressionMethods, SelectableHelper};
-use diesel::RunQueryDsl;
+use diesel::{OptionalExtension, RunQueryDsl};
 
 pub struct SessionRepository {
     connection_pool: Pool<Manager, Object>,
@@ -32,6 +32,22 @@ impl SessionRepository {
         Ok(())
     }
     
+    pub async fn get_sessions_of_user(&self, user: String) -> AppResult<Vec<Session>> {
+        use crate::schema::sessions::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                sessions
+                    .filter(username.eq(user))
+                    .select(Session::as_select())
+                    .order_by(created_at.desc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
     pub async fn delete_session(&self, session_id: String) -> AppResult<()> {
         use crate::schema::sessions::dsl::sessions;
         let conn = self.connection_pool.get().await?;
@@ -43,6 +59,42 @@ impl SessionRepository {
         Ok(())
     }
 
+    /// Removes every session of `user`, optionally keeping the one identified by `keep`.
+    pub async fn delete_sessions_of_user(
+        &self,
+        user: String,
+        keep: Option<String>,
+    ) -> AppResult<()> {
+        use crate::schema::sessions::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            let query = diesel::delete(sessions::table()).filter(username.eq(user));
+            match keep {
+                Some(kept) => query.filter(session_id.ne(kept)).execute(conn),
+                None => query.execute(conn),
+            }
+        })
+        .await??;
+        Ok(())
+    }
+
+    pub async fn get_session(&self, session_id: String) -> AppResult<Option<Session>> {
+        use crate::schema::sessions::dsl::sessions;
+        let conn = self.connection_pool.get().await?;
+
+        let result = conn
+            .interact(|conn| {
+                sessions
+                    .find(session_id)
+                    .select(Session::as_select())
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
     pub async fn get_user_by_session(&self, session_id: String) -> AppResult<Option<User>> {
         use crate::schema::sessions::dsl::sessions;
         use crate::schema::users::dsl::users;
@@ -56,9 +108,10 @@ impl SessionRepository {
                     .filter(crate::schema::sessions::session_id.eq(session_id))
                     .select((Session::as_select(), Option::<User>::as_select()))
                     .first::<(Session, Option<User>)>(conn)
+                    .optional()
             })
             .await??;
-        
-        Ok(result.1)
+
+        Ok(result.and_then(|(_, user)| user))
     }
 }
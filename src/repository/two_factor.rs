@@ -0,0 +1,141 @@
+use crate::error::AppResult;
+use crate::model::two_factor::{NewRecoveryCode, PendingLogin};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+pub struct TwoFactorRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl TwoFactorRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    pub async fn replace_recovery_codes(
+        &self,
+        user: String,
+        codes: Vec<NewRecoveryCode>,
+    ) -> AppResult<()> {
+        use crate::schema::recovery_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::delete(recovery_codes::table())
+                    .filter(username.eq(user))
+                    .execute(conn)?;
+
+                diesel::insert_into(recovery_codes::table())
+                    .values(&codes)
+                    .execute(conn)
+            })
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn delete_recovery_codes(&self, user: String) -> AppResult<()> {
+        use crate::schema::recovery_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(recovery_codes::table())
+                .filter(username.eq(user))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Burns an unused recovery code. Returns `false` if no such code was available.
+    pub async fn use_recovery_code(
+        &self,
+        user: String,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<bool> {
+        use crate::schema::recovery_codes::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(recovery_codes::table())
+                    .filter(username.eq(user))
+                    .filter(code_hash.eq(hash))
+                    .filter(used_at.is_null())
+                    .set(used_at.eq(now))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    pub async fn create_pending_login(&self, pending: PendingLogin) -> AppResult<()> {
+        use crate::schema::pending_logins::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(pending_logins::table())
+                .filter(username.eq(&pending.username))
+                .execute(conn)?;
+
+            diesel::insert_into(pending_logins::table())
+                .values(&pending)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn find_pending_login(
+        &self,
+        hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<PendingLogin>> {
+        use crate::schema::pending_logins::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                pending_logins
+                    .find(hash)
+                    .filter(expires_at.gt(now))
+                    .select(PendingLogin::as_select())
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Counts a wrong code against the pending login and returns the new number of attempts.
+    pub async fn record_failed_attempt(&self, hash: String) -> AppResult<i32> {
+        use crate::schema::pending_logins::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::update(pending_logins.find(hash))
+                    .set(attempts.eq(attempts + 1))
+                    .returning(attempts)
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn delete_pending_login(&self, hash: String) -> AppResult<()> {
+        use crate::schema::pending_logins::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| diesel::delete(pending_logins.find(hash)).execute(conn))
+            .await??;
+
+        Ok(())
+    }
+}
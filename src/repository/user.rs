@@ -1,12 +1,19 @@
-use crate::error::AppError::SignUpError;
 use crate::error::AppResult;
-use crate::model::user::{UpdateUser, User};
+use crate::model::role::Role;
+use crate::model::user::{UpdateProfile, UpdateUser, User, UserSort, UserSuggestionDTO};
 use deadpool_diesel::postgres::{Manager, Object};
 use deadpool_diesel::Pool;
-use diesel::ExpressionMethods;
+use chrono::NaiveDateTime;
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, PgTextExpressionMethods};
 use diesel::OptionalExtension;
 use diesel::QueryDsl;
-use diesel::{RunQueryDsl, SelectableHelper};
+use diesel::sql_types::{Float4, Nullable, Text};
+use diesel::{IntoSql, JoinOnDsl, NullableExpressionMethods, RunQueryDsl, SelectableHelper};
+
+diesel::infix_operator!(TrigramMatch, " % ", backend: diesel::pg::Pg);
+diesel::define_sql_function!(fn similarity(a: Nullable<Text>, b: Text) -> Nullable<Float4>);
+diesel::define_sql_function!(fn char_length(a: Text) -> Int4);
+diesel::define_sql_function!(fn greatest(a: Nullable<Float4>, b: Nullable<Float4>) -> Nullable<Float4>);
 
 pub struct UserRepository {
     connection_pool: Pool<Manager, Object>,
@@ -17,18 +24,7 @@ impl UserRepository {
         Self { connection_pool }
     }
 
-    fn is_correct_username(username: &str) -> bool {
-        !username.is_empty()
-            && username
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '_')
-    }
-
     pub async fn create_new_user(&self, user: User) -> AppResult<()> {
-        if !Self::is_correct_username(&user.username) {
-            return Err(SignUpError("Username is invalid".to_string()));
-        }
-
         use crate::schema::users::dsl::*;
         let conn = self.connection_pool.get().await?;
 
@@ -37,6 +33,47 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Redeems one use of the invite with the given digest and creates the account in the same
+    /// transaction. Returns `false`, creating nothing, if the invite is unknown, expired or used
+    /// up.
+    pub async fn create_invited_user(
+        &self,
+        mut user: User,
+        invite_hash: String,
+        now: NaiveDateTime,
+    ) -> AppResult<bool> {
+        use crate::schema::{invite_codes, users};
+        let conn = self.connection_pool.get().await?;
+
+        let created = conn
+            .interact(move |conn| {
+                conn.transaction(|conn| {
+                    let invite = diesel::update(invite_codes::table)
+                        .filter(invite_codes::code_hash.eq(invite_hash))
+                        .filter(invite_codes::uses.lt(invite_codes::max_uses))
+                        .filter(
+                            invite_codes::expires_at
+                                .is_null()
+                                .or(invite_codes::expires_at.gt(now)),
+                        )
+                        .set(invite_codes::uses.eq(invite_codes::uses + 1))
+                        .returning(invite_codes::id)
+                        .get_result::<i32>(conn)
+                        .optional()?;
+                    let Some(invite) = invite else {
+                        return Ok(false);
+                    };
+
+                    user.invited_by = Some(invite);
+                    diesel::insert_into(users::table).values(user).execute(conn)?;
+                    Ok::<_, diesel::result::Error>(true)
+                })
+            })
+            .await??;
+
+        Ok(created)
+    }
+
     pub async fn get_user_by_username(&self, username: String) -> AppResult<Option<User>> {
         use crate::schema::users::dsl::users;
         let conn = self.connection_pool.get().await?;
@@ -67,4 +104,535 @@ impl UserRepository {
 
         Ok(())
     }
+
+    pub async fn update_profile(&self, user: String, profile: UpdateProfile) -> AppResult<()> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| diesel::update(users.find(user)).set(&profile).execute(conn))
+            .await??;
+
+        Ok(())
+    }
+
+    /// Replaces the email address of `user`; a new address always starts out unverified.
+    pub async fn set_email(&self, user: String, address: String) -> AppResult<()> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| {
+            diesel::update(users.find(user))
+                .set((email.eq(address), email_verified.eq(false)))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Flags the address as verified, unless the user changed it in the meantime.
+    pub async fn mark_email_verified(&self, user: String, address: String) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(users.find(user))
+                    .filter(email.eq(address))
+                    .set(email_verified.eq(true))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    /// Returns `false` if there is no such user.
+    pub async fn set_role(&self, user: String, new_role: Role) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(users.find(user))
+                    .set(role.eq(new_role))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    /// Stores a freshly generated TOTP secret. It stays inactive until the user confirms it.
+    pub async fn store_totp_secret(&self, user: String, secret: String) -> AppResult<()> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| {
+            diesel::update(users.find(user))
+                .set((
+                    totp_secret.eq(Some(secret)),
+                    totp_enabled.eq(false),
+                    totp_last_step.eq(None::<i64>),
+                ))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn enable_totp(&self, user: String) -> AppResult<()> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| {
+            diesel::update(users.find(user))
+                .set(totp_enabled.eq(true))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn disable_totp(&self, user: String) -> AppResult<()> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| {
+            diesel::update(users.find(user))
+                .set((
+                    totp_secret.eq(None::<String>),
+                    totp_enabled.eq(false),
+                    totp_last_step.eq(None::<i64>),
+                ))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Remembers the last accepted TOTP time step. Returns `false` if the same or a later step
+    /// was already used, which rejects replayed codes.
+    pub async fn record_totp_step(&self, user: String, step: i64) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(users.find(user))
+                    .filter(totp_last_step.is_null().or(totp_last_step.lt(step)))
+                    .set(totp_last_step.eq(step))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    /// Users whose name or email contains `query`, ordered by name.
+    pub async fn search_users(&self, query: Option<String>, page: u32) -> AppResult<Vec<User>> {
+        let users_per_page: i64 = 20;
+
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * users_per_page;
+                let mut statement = users.select(User::as_select()).into_boxed();
+
+                if let Some(query) = query {
+                    let pattern = format!("%{}%", escape_like(&query));
+                    statement = statement
+                        .filter(username.ilike(pattern.clone()).or(email.ilike(pattern)));
+                }
+
+                statement
+                    .order_by(username.asc())
+                    .offset(offset_count)
+                    .limit(users_per_page)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Public directory: users whose name or display name starts with or resembles `query`,
+    /// paired with their number of visible posts. Banned and leaving accounts are left out.
+    pub async fn search_directory(
+        &self,
+        query: Option<String>,
+        sort: UserSort,
+        page: u32,
+    ) -> AppResult<Vec<(User, i64)>> {
+        let users_per_page: i64 = 20;
+
+        use crate::schema::{posts, users};
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * users_per_page;
+                let post_count = diesel::dsl::count(posts::id.nullable());
+                let mut statement = users::table
+                    .left_join(
+                        posts::table.on(posts::username
+                            .eq(users::username.nullable())
+                            .and(posts::hidden_at.is_null())
+                            .and(posts::held_at.is_null())),
+                    )
+                    .filter(users::deletion_scheduled_at.is_null())
+                    .filter(users::banned.eq(false))
+                    .filter(users::approval_pending.eq(false))
+                    .group_by(users::username)
+                    .select((User::as_select(), post_count))
+                    .into_boxed();
+
+                if let Some(query) = &query {
+                    let prefix = format!("{}%", escape_like(query));
+                    statement = statement.filter(
+                        users::username
+                            .ilike(prefix.clone())
+                            .nullable()
+                            .or(users::display_name.ilike(prefix.clone()))
+                            .or(TrigramMatch::new(
+                                users::username,
+                                query.clone().into_sql::<Text>(),
+                            )
+                            .nullable())
+                            .or(TrigramMatch::new(
+                                users::display_name,
+                                query.clone().into_sql::<Text>(),
+                            )
+                            .nullable()),
+                    );
+                }
+
+                statement = match (sort, query) {
+                    (UserSort::Posts, _) => statement.order_by(post_count.desc()),
+                    (UserSort::Relevance, Some(query)) => statement
+                        .order_by(
+                            users::username
+                                .ilike(format!("{}%", escape_like(&query)))
+                                .desc(),
+                        )
+                        .then_order_by(
+                            greatest(
+                                similarity(users::username.nullable(), query.clone()),
+                                similarity(users::display_name, query),
+                            )
+                            .desc(),
+                        ),
+                    (UserSort::Relevance | UserSort::Joined, _) => {
+                        statement.order_by(users::joined.desc())
+                    }
+                };
+
+                statement
+                    .then_order_by(users::username.asc())
+                    .offset(offset_count)
+                    .limit(users_per_page)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Up to `limit` users whose name or display name starts with `prefix`, shortest names
+    /// first.
+    pub async fn suggest_users(
+        &self,
+        prefix: String,
+        limit: i64,
+    ) -> AppResult<Vec<UserSuggestionDTO>> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let pattern = format!("{}%", escape_like(&prefix));
+                users
+                    .filter(deletion_scheduled_at.is_null())
+                    .filter(banned.eq(false))
+                    .filter(approval_pending.eq(false))
+                    .filter(
+                        username
+                            .ilike(pattern.clone())
+                            .nullable()
+                            .or(display_name.ilike(pattern.clone())),
+                    )
+                    .order_by(username.ilike(pattern).desc())
+                    .then_order_by(char_length(username))
+                    .then_order_by(username.asc())
+                    .select((username, display_name))
+                    .limit(limit)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Accounts waiting for an admin to approve their registration, oldest first.
+    pub async fn get_pending_users(&self, page: u32) -> AppResult<Vec<User>> {
+        let users_per_page: i64 = 20;
+
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                let offset_count: i64 = (page - 1) as i64 * users_per_page;
+                users
+                    .filter(approval_pending.eq(true))
+                    .select(User::as_select())
+                    .order_by((joined.asc(), username.asc()))
+                    .offset(offset_count)
+                    .limit(users_per_page)
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Returns `false` if `user` does not exist or was not waiting for approval.
+    pub async fn approve_user(&self, user: String) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(users.find(user))
+                    .filter(approval_pending.eq(true))
+                    .set(approval_pending.eq(false))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    /// A ban ignores `until`. Passing `None` and `false` lifts both. Returns `false` if there
+    /// is no such user.
+    pub async fn set_suspension(
+        &self,
+        user: String,
+        until: Option<NaiveDateTime>,
+        ban: bool,
+        reason: Option<String>,
+    ) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(users.find(user))
+                    .set((
+                        suspended_until.eq(until),
+                        banned.eq(ban),
+                        suspension_reason.eq(reason),
+                    ))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    pub async fn remove_avatar(&self, user: String) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(users.find(user))
+                    .set(avatar.eq(None::<Vec<u8>>))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated > 0)
+    }
+
+    /// Deletes the user; their posts, sessions and credentials go with them through the
+    /// foreign keys. Returns `false` if there is no such user.
+    pub async fn delete_user(&self, user: String) -> AppResult<bool> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let deleted = conn
+            .interact(move |conn| diesel::delete(users.find(user)).execute(conn))
+            .await??;
+
+        Ok(deleted > 0)
+    }
+
+    /// Schedules the account for deletion at `at`, or cancels that with `None`.
+    pub async fn schedule_deletion(
+        &self,
+        user: String,
+        at: Option<NaiveDateTime>,
+        anonymize: bool,
+    ) -> AppResult<()> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| {
+            diesel::update(users.find(user))
+                .set((deletion_scheduled_at.eq(at), anonymize_posts.eq(anonymize)))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn get_users_due_for_deletion(&self, now: NaiveDateTime) -> AppResult<Vec<User>> {
+        use crate::schema::users::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let result = conn
+            .interact(move |conn| {
+                users
+                    .filter(deletion_scheduled_at.le(now))
+                    .select(User::as_select())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Deletes an account whose grace period is over, first detaching its posts if they are
+    /// to be kept. Returns `false` if the deletion was cancelled in the meantime.
+    pub async fn purge_user(&self, user: String, now: NaiveDateTime) -> AppResult<bool> {
+        use crate::schema::{posts, users};
+        let conn = self.connection_pool.get().await?;
+
+        let deleted = conn
+            .interact(move |conn| {
+                conn.transaction(|conn| {
+                    let anonymize = users::table
+                        .find(&user)
+                        .filter(users::deletion_scheduled_at.le(now))
+                        .select(users::anonymize_posts)
+                        .for_update()
+                        .first::<bool>(conn)
+                        .optional()?;
+
+                    match anonymize {
+                        None => Ok(0),
+                        Some(anonymize) => {
+                            if anonymize {
+                                diesel::update(posts::table.filter(posts::username.eq(&user)))
+                                    .set(posts::username.eq(None::<String>))
+                                    .execute(conn)?;
+                            }
+
+                            diesel::delete(users::table.find(&user)).execute(conn)
+                        }
+                    }
+                })
+            })
+            .await??;
+
+        Ok(deleted > 0)
+    }
+
+    /// Whether nobody else uses `name`, counting former names changed after `reserved_since`.
+    /// `requester` may take back a name they gave up themselves.
+    pub async fn is_username_available(
+        &self,
+        name: String,
+        reserved_since: NaiveDateTime,
+        requester: Option<String>,
+    ) -> AppResult<bool> {
+        use crate::schema::{username_history, users};
+        let conn = self.connection_pool.get().await?;
+
+        let available = conn
+            .interact(move |conn| {
+                let taken = diesel::select(diesel::dsl::exists(users::table.find(&name)))
+                    .get_result::<bool>(conn)?;
+                let reserved_by = username_history::table
+                    .find(&name)
+                    .filter(username_history::changed_at.gt(reserved_since))
+                    .select(username_history::username)
+                    .first::<String>(conn)
+                    .optional()?;
+
+                Ok::<_, diesel::result::Error>(
+                    !taken && reserved_by.is_none_or(|owner| Some(owner) == requester),
+                )
+            })
+            .await??;
+
+        Ok(available)
+    }
+
+    /// Renames `user`; references follow through `ON UPDATE CASCADE` and the old name is kept
+    /// in the history.
+    pub async fn rename_user(
+        &self,
+        user: String,
+        new_name: String,
+        now: NaiveDateTime,
+    ) -> AppResult<()> {
+        use crate::schema::{username_history, users};
+        let conn = self.connection_pool.get().await?;
+
+        conn.interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::update(users::table.find(&user))
+                    .set(users::username.eq(&new_name))
+                    .execute(conn)?;
+
+                diesel::delete(username_history::table.find(&new_name)).execute(conn)?;
+                diesel::insert_into(username_history::table)
+                    .values((
+                        username_history::old_username.eq(&user),
+                        username_history::username.eq(&new_name),
+                        username_history::changed_at.eq(now),
+                    ))
+                    .on_conflict(username_history::old_username)
+                    .do_update()
+                    .set((
+                        username_history::username.eq(&new_name),
+                        username_history::changed_at.eq(now),
+                    ))
+                    .execute(conn)
+            })
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// The current name of whoever last went by `old_name`.
+    pub async fn find_renamed_user(&self, old_name: String) -> AppResult<Option<String>> {
+        use crate::schema::username_history::dsl::*;
+        let conn = self.connection_pool.get().await?;
+
+        let result = conn
+            .interact(move |conn| {
+                username_history
+                    .find(old_name)
+                    .select(username)
+                    .first(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
+
+/// Escapes the wildcards of a `LIKE` pattern so user input only matches literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
@@ -0,0 +1,131 @@
+use crate::error::AppResult;
+use crate::model::webauthn::{WebauthnChallenge, WebauthnCredential};
+use chrono::NaiveDateTime;
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct WebauthnRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl WebauthnRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    pub async fn get_credentials_of_user(&self, user: String) -> AppResult<Vec<WebauthnCredential>> {
+        use crate::schema::webauthn_credentials::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                webauthn_credentials
+                    .filter(username.eq(user))
+                    .select(WebauthnCredential::as_select())
+                    .order_by(created_at.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn add_credential(&self, credential: WebauthnCredential) -> AppResult<()> {
+        use crate::schema::webauthn_credentials::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::insert_into(webauthn_credentials::table())
+                .values(credential)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn update_credential_usage(
+        &self,
+        id: String,
+        updated_passkey: String,
+        counter: i64,
+        used_at: NaiveDateTime,
+    ) -> AppResult<()> {
+        use crate::schema::webauthn_credentials::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::update(webauthn_credentials.find(id))
+                .set((
+                    passkey.eq(updated_passkey),
+                    sign_count.eq(counter),
+                    last_used_at.eq(Some(used_at)),
+                ))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Returns `false` if `user` has no credential with the given id.
+    pub async fn delete_credential(&self, user: String, id: String) -> AppResult<bool> {
+        use crate::schema::webauthn_credentials::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let deleted = conn
+            .interact(move |conn| {
+                diesel::delete(webauthn_credentials::table())
+                    .filter(credential_id.eq(id))
+                    .filter(username.eq(user))
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(deleted > 0)
+    }
+
+    /// Stores a new challenge and sweeps the ones that expired without being finished.
+    pub async fn add_challenge(
+        &self,
+        challenge: WebauthnChallenge,
+        now: NaiveDateTime,
+    ) -> AppResult<()> {
+        use crate::schema::webauthn_challenges::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(webauthn_challenges::table())
+                .filter(expires_at.lt(now))
+                .execute(conn)?;
+
+            diesel::insert_into(webauthn_challenges::table())
+                .values(&challenge)
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Deletes and returns an unexpired challenge so every ceremony can only be finished once.
+    pub async fn take_challenge(
+        &self,
+        id: String,
+        kind: &'static str,
+        now: NaiveDateTime,
+    ) -> AppResult<Option<WebauthnChallenge>> {
+        use crate::schema::webauthn_challenges::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::delete(webauthn_challenges::table())
+                    .filter(challenge_id.eq(id))
+                    .filter(ceremony.eq(kind))
+                    .filter(expires_at.gt(now))
+                    .returning(WebauthnChallenge::as_returning())
+                    .get_result(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(result)
+    }
+}
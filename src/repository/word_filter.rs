@@ -0,0 +1,57 @@
+use crate::error::AppResult;
+use crate::model::word_filter::{NewWordFilter, WordFilter};
+use deadpool_diesel::postgres::{Manager, Object};
+use deadpool_diesel::Pool;
+use diesel::associations::HasTable;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+pub struct WordFilterRepository {
+    connection_pool: Pool<Manager, Object>,
+}
+
+impl WordFilterRepository {
+    pub fn new(connection_pool: Pool<Manager, Object>) -> Self {
+        Self { connection_pool }
+    }
+
+    pub async fn get_filters(&self) -> AppResult<Vec<WordFilter>> {
+        use crate::schema::word_filters::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                word_filters
+                    .select(WordFilter::as_select())
+                    .order_by(id.asc())
+                    .load(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    pub async fn add_filter(&self, filter: NewWordFilter) -> AppResult<WordFilter> {
+        use crate::schema::word_filters::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let result = conn
+            .interact(move |conn| {
+                diesel::insert_into(word_filters::table())
+                    .values(filter)
+                    .returning(WordFilter::as_returning())
+                    .get_result(conn)
+            })
+            .await??;
+
+        Ok(result)
+    }
+
+    /// Returns `false` if there is no such filter.
+    pub async fn delete_filter(&self, filter_id: i32) -> AppResult<bool> {
+        use crate::schema::word_filters::dsl::*;
+        let conn = self.connection_pool.get().await?;
+        let deleted = conn
+            .interact(move |conn| diesel::delete(word_filters.find(filter_id)).execute(conn))
+            .await??;
+
+        Ok(deleted > 0)
+    }
+}
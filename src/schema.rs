@@ -1,5 +1,120 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_tokens (id) {
+        id -> Int4,
+        username -> Varchar,
+        name -> Varchar,
+        token_hash -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    audit_events (id) {
+        id -> Int8,
+        event -> Varchar,
+        actor -> Nullable<Varchar>,
+        target -> Nullable<Varchar>,
+        ip -> Nullable<Varchar>,
+        details -> Nullable<Text>,
+        created_at -> Timestamp,
+        actor_id -> Nullable<Int8>,
+        target_id -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    data_exports (id) {
+        id -> Int4,
+        username -> Varchar,
+        token_hash -> Varchar,
+        status -> Varchar,
+        archive -> Nullable<Bytea>,
+        created_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    email_verification_tokens (token_hash) {
+        token_hash -> Varchar,
+        username -> Varchar,
+        email -> Varchar,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    invite_codes (id) {
+        id -> Int4,
+        created_by -> Varchar,
+        code_hash -> Varchar,
+        max_uses -> Int4,
+        uses -> Int4,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    moderation_actions (id) {
+        id -> Int4,
+        moderator -> Nullable<Varchar>,
+        action -> Varchar,
+        post_id -> Int4,
+        post_author -> Nullable<Varchar>,
+        post_title -> Text,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oidc_identities (provider, subject) {
+        provider -> Varchar,
+        subject -> Varchar,
+        username -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_login_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    oidc_states (state_hash) {
+        state_hash -> Varchar,
+        provider -> Varchar,
+        nonce -> Varchar,
+        pkce_verifier -> Varchar,
+        link_username -> Nullable<Varchar>,
+        redirect_to -> Varchar,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    password_reset_tokens (token_hash) {
+        token_hash -> Varchar,
+        username -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    pending_logins (token_hash) {
+        token_hash -> Varchar,
+        username -> Varchar,
+        expires_at -> Timestamp,
+        attempts -> Int4,
+    }
+}
+
 diesel::table! {
     posts (id) {
         id -> Int4,
@@ -7,7 +122,36 @@ diesel::table! {
         body -> Text,
         date -> Timestamp,
         image -> Nullable<Bytea>,
+        username -> Nullable<Varchar>,
+        hidden_at -> Nullable<Timestamp>,
+        hidden_by -> Nullable<Varchar>,
+        held_at -> Nullable<Timestamp>,
+        held_reason -> Nullable<Text>,
+        content_hash -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Int4,
         username -> Varchar,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    reports (id) {
+        id -> Int4,
+        reporter -> Varchar,
+        post_id -> Nullable<Int4>,
+        reported_user -> Nullable<Varchar>,
+        reason -> Varchar,
+        details -> Nullable<Text>,
+        created_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        resolved_by -> Nullable<Varchar>,
+        resolution -> Nullable<Varchar>,
     }
 }
 
@@ -15,6 +159,16 @@ diesel::table! {
     sessions (session_id) {
         session_id -> Varchar,
         username -> Varchar,
+        csrf_token -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    username_history (old_username) {
+        old_username -> Varchar,
+        username -> Varchar,
+        changed_at -> Timestamp,
     }
 }
 
@@ -24,14 +178,97 @@ diesel::table! {
         password -> Varchar,
         avatar -> Nullable<Bytea>,
         joined -> Date,
+        email -> Nullable<Varchar>,
+        email_verified -> Bool,
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled -> Bool,
+        totp_last_step -> Nullable<Int8>,
+        role -> Varchar,
+        suspended_until -> Nullable<Timestamp>,
+        suspension_reason -> Nullable<Text>,
+        banned -> Bool,
+        deletion_scheduled_at -> Nullable<Timestamp>,
+        anonymize_posts -> Bool,
+        display_name -> Nullable<Varchar>,
+        bio -> Nullable<Text>,
+        links -> Array<Text>,
+        location -> Nullable<Varchar>,
+        pronouns -> Nullable<Varchar>,
+        approval_pending -> Bool,
+        invited_by -> Nullable<Int4>,
+        id -> Int8,
+    }
+}
+
+diesel::table! {
+    webauthn_challenges (challenge_id) {
+        challenge_id -> Varchar,
+        username -> Varchar,
+        ceremony -> Varchar,
+        state -> Text,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    webauthn_credentials (credential_id) {
+        credential_id -> Varchar,
+        username -> Varchar,
+        name -> Varchar,
+        passkey -> Text,
+        sign_count -> Int8,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    word_filters (id) {
+        id -> Int4,
+        pattern -> Varchar,
+        is_regex -> Bool,
+        action -> Varchar,
+        created_by -> Nullable<Varchar>,
+        created_at -> Timestamp,
     }
 }
 
+diesel::joinable!(api_tokens -> users (username));
+diesel::joinable!(data_exports -> users (username));
+diesel::joinable!(email_verification_tokens -> users (username));
+diesel::joinable!(invite_codes -> users (created_by));
+diesel::joinable!(moderation_actions -> users (moderator));
+diesel::joinable!(oidc_identities -> users (username));
+diesel::joinable!(oidc_states -> users (link_username));
+diesel::joinable!(password_reset_tokens -> users (username));
+diesel::joinable!(pending_logins -> users (username));
 diesel::joinable!(posts -> users (username));
+diesel::joinable!(recovery_codes -> users (username));
+diesel::joinable!(reports -> posts (post_id));
 diesel::joinable!(sessions -> users (username));
+diesel::joinable!(username_history -> users (username));
+diesel::joinable!(webauthn_challenges -> users (username));
+diesel::joinable!(webauthn_credentials -> users (username));
+diesel::joinable!(word_filters -> users (created_by));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    audit_events,
+    data_exports,
+    email_verification_tokens,
+    invite_codes,
+    moderation_actions,
+    oidc_identities,
+    oidc_states,
+    password_reset_tokens,
+    pending_logins,
     posts,
+    recovery_codes,
+    reports,
     sessions,
+    username_history,
     users,
+    webauthn_challenges,
+    webauthn_credentials,
+    word_filters,
 );
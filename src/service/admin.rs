@@ -0,0 +1,187 @@
+use crate::error::AppError::{ForbiddenError, NotFoundError};
+use crate::error::AppResult;
+use crate::model::user::{AdminUserDTO, SuspendForm, User};
+use crate::repository::session::SessionRepository;
+use crate::repository::user::UserRepository;
+use crate::validation::Validator;
+use chrono::{Duration, Utc};
+
+/// Suspensions may last up to ten years; anything longer is a ban.
+const MAX_SUSPENSION_HOURS: i64 = 10 * 365 * 24;
+
+pub struct AdminService {
+    user_repository: UserRepository,
+    session_repository: SessionRepository,
+}
+
+impl AdminService {
+    pub fn new(user_repository: UserRepository, session_repository: SessionRepository) -> Self {
+        Self {
+            user_repository,
+            session_repository,
+        }
+    }
+
+    pub async fn search_users(
+        &self,
+        query: Option<String>,
+        page: u32,
+    ) -> AppResult<Vec<AdminUserDTO>> {
+        let query = query
+            .map(|query| query.trim().to_string())
+            .filter(|query| !query.is_empty());
+
+        let users = self.user_repository.search_users(query, page).await?;
+        Ok(users.into_iter().map(AdminUserDTO::from).collect())
+    }
+
+    /// Suspending also ends every session of the user.
+    pub async fn suspend_user(
+        &self,
+        actor: &User,
+        username: String,
+        form: SuspendForm,
+    ) -> AppResult<()> {
+        ensure_not_self(actor, &username)?;
+
+        let mut validator = Validator::new();
+        validator.check(
+            "durationHours",
+            (1..=MAX_SUSPENSION_HOURS).contains(&form.duration_hours),
+            format!("Must be between 1 and {MAX_SUSPENSION_HOURS} hours"),
+        );
+        validator.finish()?;
+
+        let until = Utc::now().naive_utc() + Duration::hours(form.duration_hours);
+        self.restrict(username, Some(until), false, form.reason)
+            .await
+    }
+
+    pub async fn ban_user(
+        &self,
+        actor: &User,
+        username: String,
+        reason: Option<String>,
+    ) -> AppResult<()> {
+        ensure_not_self(actor, &username)?;
+
+        self.restrict(username, None, true, reason).await
+    }
+
+    /// Lifts a suspension or a ban.
+    pub async fn reinstate_user(&self, username: String) -> AppResult<()> {
+        if !self
+            .user_repository
+            .set_suspension(username, None, false, None)
+            .await?
+        {
+            return Err(user_not_found());
+        }
+
+        Ok(())
+    }
+
+    pub async fn force_logout(&self, username: String) -> AppResult<()> {
+        self.find_user(&username).await?;
+
+        self.session_repository
+            .delete_sessions_of_user(username, None)
+            .await
+    }
+
+    pub async fn reset_avatar(&self, username: String) -> AppResult<()> {
+        if !self.user_repository.remove_avatar(username).await? {
+            return Err(user_not_found());
+        }
+
+        Ok(())
+    }
+
+    /// Removes the account and all of its posts for good.
+    pub async fn delete_user(&self, actor: &User, username: String) -> AppResult<()> {
+        ensure_not_self(actor, &username)?;
+
+        if !self.user_repository.delete_user(username).await? {
+            return Err(user_not_found());
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_pending_registrations(&self, page: u32) -> AppResult<Vec<AdminUserDTO>> {
+        let users = self.user_repository.get_pending_users(page).await?;
+
+        Ok(users.into_iter().map(AdminUserDTO::from).collect())
+    }
+
+    pub async fn approve_registration(&self, username: String) -> AppResult<()> {
+        if !self.user_repository.approve_user(username).await? {
+            return Err(NotFoundError(
+                "Could not find a registration awaiting approval".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes an account that was never approved.
+    pub async fn reject_registration(&self, username: String) -> AppResult<()> {
+        let pending = self
+            .user_repository
+            .get_user_by_username(username.clone())
+            .await?
+            .is_some_and(|user| user.approval_pending);
+        if !pending || !self.user_repository.delete_user(username).await? {
+            return Err(NotFoundError(
+                "Could not find a registration awaiting approval".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn restrict(
+        &self,
+        username: String,
+        until: Option<chrono::NaiveDateTime>,
+        ban: bool,
+        reason: Option<String>,
+    ) -> AppResult<()> {
+        let reason = reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+
+        if !self
+            .user_repository
+            .set_suspension(username.clone(), until, ban, reason)
+            .await?
+        {
+            return Err(user_not_found());
+        }
+
+        self.session_repository
+            .delete_sessions_of_user(username, None)
+            .await
+    }
+
+    async fn find_user(&self, username: &str) -> AppResult<User> {
+        self.user_repository
+            .get_user_by_username(username.to_string())
+            .await?
+            .ok_or_else(user_not_found)
+    }
+}
+
+fn ensure_not_self(actor: &User, username: &str) -> AppResult<()> {
+    if actor.username == username {
+        return Err(ForbiddenError(
+            "You can't do this to your own account".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn user_not_found() -> crate::error::AppError {
+    NotFoundError("Could not find user".to_string())
+}
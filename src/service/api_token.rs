@@ -0,0 +1,125 @@
+use crate::error::AppError::NotFoundError;
+use crate::error::AppResult;
+use crate::model::api_token::{
+    ApiToken, CreateApiTokenForm, CreatedApiTokenDTO, NewApiToken, Scope,
+};
+use crate::model::user::User;
+use crate::repository::api_token::ApiTokenRepository;
+use crate::repository::user::UserRepository;
+use crate::token::{generate_token, hash_token};
+use crate::validation::Validator;
+use chrono::{Duration, Utc};
+
+/// Makes leaked tokens easy to recognise, e.g. by secret scanners.
+const TOKEN_PREFIX: &str = "rpat_";
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+pub struct ApiTokenService {
+    user_repository: UserRepository,
+    api_token_repository: ApiTokenRepository,
+}
+
+impl ApiTokenService {
+    pub fn new(user_repository: UserRepository, api_token_repository: ApiTokenRepository) -> Self {
+        Self {
+            user_repository,
+            api_token_repository,
+        }
+    }
+
+    pub async fn get_tokens(&self, username: String) -> AppResult<Vec<ApiToken>> {
+        self.api_token_repository.get_tokens_of_user(username).await
+    }
+
+    pub async fn create_token(
+        &self,
+        username: String,
+        form: CreateApiTokenForm,
+    ) -> AppResult<CreatedApiTokenDTO> {
+        let name = form.name.trim().to_string();
+        let mut validator = Validator::new();
+        validator
+            .not_blank("name", &name, "A name is required")
+            .max_length("name", &name, MAX_NAME_LENGTH)
+            .check(
+                "scopes",
+                !form.scopes.is_empty(),
+                "At least one scope is required",
+            );
+        for scope in &form.scopes {
+            validator.check(
+                "scopes",
+                Scope::parse(scope).is_some(),
+                format!("Unknown scope {scope}"),
+            );
+        }
+        if let Some(days) = form.expires_in_days {
+            validator.check(
+                "expiresInDays",
+                (1..=MAX_EXPIRY_DAYS).contains(&days),
+                format!("Must be between 1 and {MAX_EXPIRY_DAYS} days"),
+            );
+        }
+        validator.finish()?;
+
+        let mut scopes = form.scopes;
+        scopes.sort();
+        scopes.dedup();
+
+        let token = format!("{TOKEN_PREFIX}{}", generate_token());
+        let now = Utc::now().naive_utc();
+        let api_token = self
+            .api_token_repository
+            .add_token(NewApiToken {
+                username,
+                name,
+                token_hash: hash_token(&token),
+                scopes,
+                created_at: now,
+                expires_at: form.expires_in_days.map(|days| now + Duration::days(days)),
+            })
+            .await?;
+
+        Ok(CreatedApiTokenDTO { token, api_token })
+    }
+
+    pub async fn delete_token(&self, username: String, token_id: i32) -> AppResult<()> {
+        let deleted = self
+            .api_token_repository
+            .delete_token(username, token_id)
+            .await?;
+
+        if deleted {
+            Ok(())
+        } else {
+            Err(NotFoundError("Could not find token".to_string()))
+        }
+    }
+
+    /// Resolves a bearer token to its owner and the scopes it grants.
+    pub async fn authenticate(&self, token: &str) -> AppResult<Option<(User, Vec<String>)>> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+
+        let api_token = self
+            .api_token_repository
+            .touch_valid_token(hash_token(token), Utc::now().naive_utc())
+            .await?;
+        let Some(api_token) = api_token else {
+            return Ok(None);
+        };
+
+        let user = self
+            .user_repository
+            .get_user_by_username(api_token.username)
+            .await?;
+
+        Ok(user
+            .filter(|user| {
+                !user.is_suspended(Utc::now().naive_utc()) && user.deletion_scheduled_at.is_none()
+            })
+            .map(|user| (user, api_token.scopes)))
+    }
+}
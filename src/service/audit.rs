@@ -0,0 +1,33 @@
+use crate::error::AppResult;
+use crate::model::audit::{AuditEvent, AuditSearch, NewAuditEvent};
+use crate::repository::audit::AuditRepository;
+
+/// Append-only record of security and moderation events. Controllers record events once the
+/// action went through, since only they know the client address.
+pub struct AuditService {
+    audit_repository: AuditRepository,
+}
+
+impl AuditService {
+    pub fn new(audit_repository: AuditRepository) -> Self {
+        Self { audit_repository }
+    }
+
+    pub async fn record(&self, event: NewAuditEvent) -> AppResult<()> {
+        self.audit_repository.add_event(event).await
+    }
+
+    pub async fn search(&self, search: AuditSearch, page: u32) -> AppResult<Vec<AuditEvent>> {
+        self.audit_repository.search_events(search, page).await
+    }
+
+    pub async fn get_account_activity(
+        &self,
+        username: String,
+        page: u32,
+    ) -> AppResult<Vec<AuditEvent>> {
+        self.audit_repository
+            .get_events_of_user(username, page)
+            .await
+    }
+}
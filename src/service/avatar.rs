@@ -0,0 +1,107 @@
+use crate::config::env_or;
+use crate::error::AppError::InternalError;
+use crate::error::AppResult;
+use crate::identicon::Identicon;
+use axum::body::Bytes;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Edge length of generated PNG avatars.
+const PNG_SIZE: u32 = 240;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AvatarFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl AvatarFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AvatarFormat::Png => "image/png",
+            AvatarFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+/// Renders the identicons shown for users without an uploaded avatar. They only depend on the
+/// username, so rendered images are kept in memory until the cache fills up.
+pub struct AvatarService {
+    cache: Mutex<HashMap<(String, AvatarFormat), Bytes>>,
+    cache_capacity: usize,
+}
+
+impl AvatarService {
+    pub fn new() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::new()),
+            cache_capacity: env_or("AVATAR_CACHE_ENTRIES", 1000),
+        }
+    }
+
+    pub fn default_avatar(&self, username: &str, format: AvatarFormat) -> AppResult<Bytes> {
+        let key = (username.to_string(), format);
+        if let Some(image) = self.cache.lock().unwrap().get(&key) {
+            return Ok(image.clone());
+        }
+
+        let identicon = Identicon::new(username);
+        let image = Bytes::from(match format {
+            AvatarFormat::Png => identicon
+                .to_png(PNG_SIZE)
+                .map_err(|err| InternalError(err.to_string()))?,
+            AvatarFormat::Svg => identicon.to_svg().into_bytes(),
+        });
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.cache_capacity {
+            cache.clear();
+        }
+        cache.insert(key, image.clone());
+
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(cache_capacity: usize) -> AvatarService {
+        AvatarService {
+            cache: Mutex::new(HashMap::new()),
+            cache_capacity,
+        }
+    }
+
+    #[test]
+    fn serves_cached_avatars() {
+        let service = service(10);
+        let first = service.default_avatar("alice", AvatarFormat::Svg).unwrap();
+        let second = service.default_avatar("alice", AvatarFormat::Svg).unwrap();
+        assert_eq!(first.as_ptr(), second.as_ptr());
+
+        let png = service.default_avatar("alice", AvatarFormat::Png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(service.cache.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn clears_the_cache_once_full() {
+        let service = service(2);
+        let first = service.default_avatar("alice", AvatarFormat::Svg).unwrap();
+        service.default_avatar("bob", AvatarFormat::Svg).unwrap();
+        assert_eq!(service.cache.lock().unwrap().len(), 2);
+
+        service.default_avatar("carol", AvatarFormat::Svg).unwrap();
+        assert_eq!(service.cache.lock().unwrap().len(), 1);
+
+        let again = service.default_avatar("alice", AvatarFormat::Svg).unwrap();
+        assert_eq!(first, again);
+        assert_ne!(first.as_ptr(), again.as_ptr());
+        assert_eq!(service.cache.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::config::env_or;
+use crate::error::AppError::ValidationError;
+use crate::error::{AppError, AppResult, FieldError};
+use crate::model::challenge::{
+    ChallengeDTO, ChallengePurpose, DifficultyDTO, UpdateDifficultyForm,
+};
+use crate::model::user::User;
+use crate::token::generate_token;
+use crate::validation::Validator;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+/// Anything above this takes minutes in a browser.
+const MAX_DIFFICULTY: u32 = 32;
+/// Spent challenges are swept once the table grows past this many entries.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Issues and checks proof-of-work challenges that make mass sign-ups and posting from fresh
+/// accounts expensive. Challenges carry their own purpose, difficulty and expiry and are signed
+/// with `POW_SECRET`, so nothing has to be stored until one is redeemed. Redeemed challenges are
+/// remembered until they expire so a solution can't be replayed.
+///
+/// A difficulty of 0 turns the challenge off for that purpose. Admins can change difficulties
+/// at runtime, which lasts until the next restart.
+pub struct ChallengeService {
+    secret: Vec<u8>,
+    signup_difficulty: AtomicU32,
+    post_difficulty: AtomicU32,
+    new_account_days: i64,
+    ttl: Duration,
+    spent: Mutex<HashMap<String, i64>>,
+}
+
+impl ChallengeService {
+    pub fn new() -> Self {
+        // Without a configured secret, challenges issued before a restart become invalid.
+        let secret = match std::env::var("POW_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                let mut bytes = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut bytes);
+                bytes
+            }
+        };
+
+        Self {
+            secret,
+            signup_difficulty: AtomicU32::new(
+                env_or("POW_SIGNUP_DIFFICULTY", 18u32).min(MAX_DIFFICULTY),
+            ),
+            post_difficulty: AtomicU32::new(
+                env_or("POW_POST_DIFFICULTY", 16u32).min(MAX_DIFFICULTY),
+            ),
+            new_account_days: env_or("POW_NEW_ACCOUNT_DAYS", 7),
+            ttl: Duration::seconds(env_or("POW_CHALLENGE_TTL_SECONDS", 300)),
+            spent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn difficulty(&self) -> DifficultyDTO {
+        DifficultyDTO {
+            signup: self.signup_difficulty.load(Ordering::Relaxed),
+            post: self.post_difficulty.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn set_difficulty(&self, form: UpdateDifficultyForm) -> AppResult<DifficultyDTO> {
+        let mut validator = Validator::new();
+        for (field, value) in [("signup", form.signup), ("post", form.post)] {
+            if let Some(value) = value {
+                validator.check(
+                    field,
+                    value <= MAX_DIFFICULTY,
+                    format!("Must be between 0 and {MAX_DIFFICULTY}"),
+                );
+            }
+        }
+        validator.finish()?;
+
+        if let Some(signup) = form.signup {
+            self.signup_difficulty.store(signup, Ordering::Relaxed);
+        }
+        if let Some(post) = form.post {
+            self.post_difficulty.store(post, Ordering::Relaxed);
+        }
+
+        Ok(self.difficulty())
+    }
+
+    /// Whether `user` has to solve a challenge for `purpose`. Every sign-up does, posts only
+    /// from accounts younger than `POW_NEW_ACCOUNT_DAYS`. `None` stands for someone not logged
+    /// in.
+    pub fn required_for(&self, purpose: ChallengePurpose, user: Option<&User>) -> bool {
+        if self.current_difficulty(purpose) == 0 {
+            return false;
+        }
+
+        match (purpose, user) {
+            (ChallengePurpose::Signup, _) => true,
+            (ChallengePurpose::Post, Some(user)) => {
+                Utc::now().date_naive() - user.joined < Duration::days(self.new_account_days)
+            }
+            (ChallengePurpose::Post, None) => true,
+        }
+    }
+
+    pub fn issue(&self, purpose: ChallengePurpose, user: Option<&User>) -> ChallengeDTO {
+        let difficulty = self.current_difficulty(purpose);
+        let expires_at = Utc::now() + self.ttl;
+        let payload = format!(
+            "{}.{}.{}.{}",
+            purpose.as_str(),
+            expires_at.timestamp(),
+            difficulty,
+            &generate_token()[..32]
+        );
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+
+        ChallengeDTO {
+            challenge: format!("{payload}.{signature}"),
+            difficulty,
+            expires_at: expires_at.naive_utc(),
+            required: self.required_for(purpose, user),
+        }
+    }
+
+    /// Checks a solved challenge if `user` needs one for `purpose` and reserves it. The claim
+    /// has to be redeemed once the action succeeded, otherwise the challenge is released again
+    /// so a request that fails for another reason doesn't use up the solution. Reserving it
+    /// right away keeps concurrent requests from redeeming the same solution twice.
+    pub fn verify(
+        &self,
+        purpose: ChallengePurpose,
+        user: Option<&User>,
+        challenge: Option<&str>,
+        solution: Option<&str>,
+    ) -> AppResult<ChallengeClaim<'_>> {
+        if !self.required_for(purpose, user) {
+            return Ok(ChallengeClaim {
+                service: self,
+                challenge: None,
+            });
+        }
+
+        let (Some(challenge), Some(solution)) = (challenge, solution) else {
+            return Err(challenge_error("Solve the proof-of-work challenge first"));
+        };
+        let (expires, difficulty) = self.validate(purpose, challenge)?;
+
+        let digest = Sha256::new()
+            .chain_update(challenge.as_bytes())
+            .chain_update(solution.as_bytes())
+            .finalize();
+        if leading_zero_bits(&digest) < difficulty {
+            return Err(challenge_error("The challenge solution is wrong"));
+        }
+
+        let now = Utc::now().timestamp();
+        let mut spent = self.spent.lock().unwrap();
+        if spent.len() > PRUNE_THRESHOLD {
+            spent.retain(|_, expires| *expires > now);
+        }
+        if spent.insert(challenge.to_string(), expires).is_some() {
+            return Err(challenge_error("The challenge was already used"));
+        }
+
+        Ok(ChallengeClaim {
+            service: self,
+            challenge: Some(challenge.to_string()),
+        })
+    }
+
+    /// Checks the signature, purpose, expiry and difficulty of `challenge` and returns when it
+    /// expires along with the difficulty it was issued with.
+    fn validate(&self, purpose: ChallengePurpose, challenge: &str) -> AppResult<(i64, u32)> {
+        let invalid = || challenge_error("The challenge is invalid");
+
+        let (payload, signature) = challenge.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let mut parts = payload.split('.');
+        let issued_for = parts.next().and_then(ChallengePurpose::parse);
+        let expires = parts.next().and_then(|value| value.parse::<i64>().ok());
+        let difficulty = parts.next().and_then(|value| value.parse::<u32>().ok());
+        let (Some(issued_for), Some(expires), Some(difficulty)) = (issued_for, expires, difficulty)
+        else {
+            return Err(invalid());
+        };
+
+        if issued_for != purpose {
+            return Err(invalid());
+        }
+        if DateTime::from_timestamp(expires, 0).is_none_or(|expires| expires <= Utc::now()) {
+            return Err(challenge_error("The challenge has expired"));
+        }
+        // Raising the difficulty invalidates easier challenges that are still out there.
+        if difficulty < self.current_difficulty(purpose) {
+            return Err(challenge_error("The challenge is outdated"));
+        }
+
+        Ok((expires, difficulty))
+    }
+
+    fn current_difficulty(&self, purpose: ChallengePurpose) -> u32 {
+        match purpose {
+            ChallengePurpose::Signup => self.signup_difficulty.load(Ordering::Relaxed),
+            ChallengePurpose::Post => self.post_difficulty.load(Ordering::Relaxed),
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// A challenge reserved by [`ChallengeService::verify`], released when dropped unless it was
+/// redeemed.
+pub struct ChallengeClaim<'a> {
+    service: &'a ChallengeService,
+    challenge: Option<String>,
+}
+
+impl ChallengeClaim<'_> {
+    /// Keeps the challenge spent for good.
+    pub fn redeem(mut self) {
+        self.challenge = None;
+    }
+}
+
+impl Drop for ChallengeClaim<'_> {
+    fn drop(&mut self) {
+        if let Some(challenge) = self.challenge.take() {
+            self.service.spent.lock().unwrap().remove(&challenge);
+        }
+    }
+}
+
+fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn challenge_error(message: &str) -> AppError {
+    ValidationError(vec![FieldError::new("challenge", message)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(difficulty: u32, ttl: Duration) -> ChallengeService {
+        ChallengeService {
+            secret: b"secret".to_vec(),
+            signup_difficulty: AtomicU32::new(difficulty),
+            post_difficulty: AtomicU32::new(difficulty),
+            new_account_days: 7,
+            ttl,
+            spent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn solve(challenge: &str, difficulty: u32) -> String {
+        (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                let digest = Sha256::new()
+                    .chain_update(challenge.as_bytes())
+                    .chain_update(nonce.as_bytes())
+                    .finalize();
+                leading_zero_bits(&digest) >= difficulty
+            })
+            .unwrap()
+    }
+
+    fn error_message(result: AppResult<ChallengeClaim<'_>>) -> String {
+        match result {
+            Err(ValidationError(errors)) => errors[0].message.clone(),
+            Err(_) => panic!("expected a validation error"),
+            Ok(_) => panic!("expected the challenge to be rejected"),
+        }
+    }
+
+    #[test]
+    fn redeemed_challenges_cant_be_replayed() {
+        let service = service(4, Duration::minutes(5));
+        let challenge = service.issue(ChallengePurpose::Signup, None).challenge;
+        let solution = solve(&challenge, 4);
+
+        service
+            .verify(
+                ChallengePurpose::Signup,
+                None,
+                Some(&challenge),
+                Some(&solution),
+            )
+            .unwrap()
+            .redeem();
+        let replayed = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&challenge),
+            Some(&solution),
+        );
+        assert_eq!(error_message(replayed), "The challenge was already used");
+    }
+
+    #[test]
+    fn unredeemed_challenges_are_released() {
+        let service = service(4, Duration::minutes(5));
+        let challenge = service.issue(ChallengePurpose::Signup, None).challenge;
+        let solution = solve(&challenge, 4);
+
+        let claim = service
+            .verify(
+                ChallengePurpose::Signup,
+                None,
+                Some(&challenge),
+                Some(&solution),
+            )
+            .unwrap();
+        let concurrent = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&challenge),
+            Some(&solution),
+        );
+        assert_eq!(error_message(concurrent), "The challenge was already used");
+
+        drop(claim);
+        service
+            .verify(
+                ChallengePurpose::Signup,
+                None,
+                Some(&challenge),
+                Some(&solution),
+            )
+            .unwrap()
+            .redeem();
+    }
+
+    #[test]
+    fn rejects_wrong_solutions() {
+        let service = service(16, Duration::minutes(5));
+        let challenge = service.issue(ChallengePurpose::Signup, None).challenge;
+        let solution = (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                let digest = Sha256::new()
+                    .chain_update(challenge.as_bytes())
+                    .chain_update(nonce.as_bytes())
+                    .finalize();
+                leading_zero_bits(&digest) < 16
+            })
+            .unwrap();
+
+        let result = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&challenge),
+            Some(&solution),
+        );
+        assert_eq!(error_message(result), "The challenge solution is wrong");
+    }
+
+    #[test]
+    fn rejects_expired_challenges() {
+        let service = service(4, Duration::seconds(-1));
+        let challenge = service.issue(ChallengePurpose::Signup, None).challenge;
+        let solution = solve(&challenge, 4);
+
+        let result = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&challenge),
+            Some(&solution),
+        );
+        assert_eq!(error_message(result), "The challenge has expired");
+    }
+
+    #[test]
+    fn rejects_challenges_for_another_purpose() {
+        let service = service(4, Duration::minutes(5));
+        let challenge = service.issue(ChallengePurpose::Post, None).challenge;
+        let solution = solve(&challenge, 4);
+
+        let result = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&challenge),
+            Some(&solution),
+        );
+        assert_eq!(error_message(result), "The challenge is invalid");
+    }
+
+    #[test]
+    fn rejects_tampered_challenges() {
+        let service = service(4, Duration::minutes(5));
+        let challenge = service.issue(ChallengePurpose::Signup, None).challenge;
+
+        // Lowering the difficulty in the payload breaks the signature.
+        let mut parts: Vec<&str> = challenge.split('.').collect();
+        parts[2] = "0";
+        let tampered = parts.join(".");
+        let result = service.verify(ChallengePurpose::Signup, None, Some(&tampered), Some("0"));
+        assert_eq!(error_message(result), "The challenge is invalid");
+
+        let other = ChallengeService {
+            secret: b"other".to_vec(),
+            ..self::service(4, Duration::minutes(5))
+        };
+        let foreign = other.issue(ChallengePurpose::Signup, None).challenge;
+        let solution = solve(&foreign, 4);
+        let result = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&foreign),
+            Some(&solution),
+        );
+        assert_eq!(error_message(result), "The challenge is invalid");
+    }
+
+    #[test]
+    fn rejects_challenges_issued_before_a_difficulty_raise() {
+        let service = service(4, Duration::minutes(5));
+        let challenge = service.issue(ChallengePurpose::Signup, None).challenge;
+        let solution = solve(&challenge, 4);
+
+        service
+            .set_difficulty(UpdateDifficultyForm {
+                signup: Some(8),
+                post: None,
+            })
+            .unwrap();
+        let result = service.verify(
+            ChallengePurpose::Signup,
+            None,
+            Some(&challenge),
+            Some(&solution),
+        );
+        assert_eq!(error_message(result), "The challenge is outdated");
+    }
+}
@@ -0,0 +1,114 @@
+use crate::config::{env_or, public_url};
+use crate::error::AppError::ValidationError;
+use crate::error::{AppResult, FieldError};
+use crate::mailer::{Mail, Mailer};
+use crate::model::email_verification::EmailVerificationToken;
+use crate::repository::email_verification::EmailVerificationRepository;
+use crate::repository::user::UserRepository;
+use crate::token::{generate_token, hash_token};
+use crate::validation::{normalize_email, ValidationRules};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+
+pub struct EmailService {
+    user_repository: UserRepository,
+    verification_repository: EmailVerificationRepository,
+    mailer: Arc<dyn Mailer>,
+    validation_rules: Arc<ValidationRules>,
+    verification_token_ttl: Duration,
+    public_url: String,
+}
+
+impl EmailService {
+    pub fn new(
+        user_repository: UserRepository,
+        verification_repository: EmailVerificationRepository,
+        mailer: Arc<dyn Mailer>,
+        validation_rules: Arc<ValidationRules>,
+    ) -> Self {
+        Self {
+            user_repository,
+            verification_repository,
+            mailer,
+            validation_rules,
+            verification_token_ttl: Duration::hours(env_or("EMAIL_VERIFICATION_TTL_HOURS", 24)),
+            public_url: public_url(),
+        }
+    }
+
+    pub async fn change_email(&self, username: String, email: String) -> AppResult<()> {
+        let email = normalize_email(&email);
+        self.validation_rules.validate_email(&email)?;
+
+        self.user_repository
+            .set_email(username.clone(), email)
+            .await?;
+        self.send_verification(username).await
+    }
+
+    /// Mails a verification link for the current address of `username`, if it still needs one.
+    pub async fn send_verification(&self, username: String) -> AppResult<()> {
+        let user = self
+            .user_repository
+            .get_user_by_username(username)
+            .await?;
+
+        let Some(user) = user else {
+            return Ok(());
+        };
+        let Some(email) = user.email.filter(|_| !user.email_verified) else {
+            return Ok(());
+        };
+
+        let token = generate_token();
+        self.verification_repository
+            .replace_token(EmailVerificationToken {
+                token_hash: hash_token(&token),
+                username: user.username.clone(),
+                email: email.clone(),
+                expires_at: (Utc::now() + self.verification_token_ttl).naive_utc(),
+            })
+            .await?;
+
+        self.mailer
+            .send(Mail {
+                to: email,
+                subject: "Confirm your RustyPosts email address".to_string(),
+                body: format!(
+                    "Hi {},\n\n\
+                     Please confirm this address by opening the following link:\n\
+                     {}/verify-email?token={}\n\n\
+                     If you didn't add this address to a RustyPosts account, you can ignore \
+                     this message.",
+                    user.username, self.public_url, token
+                ),
+            })
+            .await
+    }
+
+    pub async fn verify(&self, token: String) -> AppResult<()> {
+        let invalid_token = || {
+            ValidationError(vec![FieldError::new(
+                "token",
+                "Verification link is invalid or has expired",
+            )])
+        };
+
+        let pending = self
+            .verification_repository
+            .consume_token(hash_token(&token), Utc::now().naive_utc())
+            .await?
+            .ok_or_else(invalid_token)?;
+
+        let verified = self
+            .user_repository
+            .mark_email_verified(pending.username, pending.email)
+            .await?;
+
+        if verified {
+            Ok(())
+        } else {
+            Err(invalid_token())
+        }
+    }
+}
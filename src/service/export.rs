@@ -0,0 +1,391 @@
+use crate::config::{env_or, public_url};
+use crate::error::AppError::{ConflictError, InternalError, NotFoundError};
+use crate::error::{AppError, AppResult};
+use crate::model::audit::AuditEvent;
+use crate::model::export::{
+    CreatedExportDTO, DataExport, NewDataExport, EXPORT_FAILED, EXPORT_PENDING, EXPORT_READY,
+};
+use crate::model::post::Post;
+use crate::model::session::Session;
+use crate::model::user::User;
+use crate::repository::audit::AuditRepository;
+use crate::repository::export::ExportRepository;
+use crate::repository::post::PostRepository;
+use crate::repository::session::SessionRepository;
+use crate::repository::user::UserRepository;
+use crate::token::{generate_token, hash_token};
+use crate::validation::detect_image_type;
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Events that show up in `history/logins.json` of the archive.
+const LOGIN_EVENTS: [&str; 3] = ["login.success", "login.failure", "logout"];
+
+/// Builds takeout archives of everything stored about a user. Archives are built in the
+/// background and can be downloaded through a secret link until they expire.
+pub struct ExportService {
+    user_repository: UserRepository,
+    post_repository: PostRepository,
+    audit_repository: AuditRepository,
+    session_repository: SessionRepository,
+    export_repository: ExportRepository,
+    export_ttl: Duration,
+    build_timeout: Duration,
+    /// Archives are built in memory and stored in the database, so they can't grow forever.
+    max_bytes: usize,
+    max_unexpired: i64,
+}
+
+impl ExportService {
+    pub fn new(
+        user_repository: UserRepository,
+        post_repository: PostRepository,
+        audit_repository: AuditRepository,
+        session_repository: SessionRepository,
+        export_repository: ExportRepository,
+    ) -> Self {
+        Self {
+            user_repository,
+            post_repository,
+            audit_repository,
+            session_repository,
+            export_repository,
+            export_ttl: Duration::hours(env_or("DATA_EXPORT_TTL_HOURS", 48)),
+            build_timeout: Duration::minutes(env_or("DATA_EXPORT_BUILD_TIMEOUT_MINUTES", 30)),
+            max_bytes: env_or("DATA_EXPORT_MAX_BYTES", 256 * 1024 * 1024),
+            max_unexpired: env_or("DATA_EXPORTS_PER_USER", 3),
+        }
+    }
+
+    /// Starts building an archive and returns the link it can be downloaded from once ready.
+    pub async fn request_export(self: &Arc<Self>, username: String) -> AppResult<CreatedExportDTO> {
+        let now = Utc::now().naive_utc();
+        self.clean_up(now).await?;
+
+        // Every archive stays in the database until it expires.
+        if self
+            .export_repository
+            .count_unexpired_exports(username.clone(), now)
+            .await?
+            >= self.max_unexpired
+        {
+            return Err(ConflictError(format!(
+                "You already have {} exports, wait for one to expire",
+                self.max_unexpired
+            )));
+        }
+
+        // Images make up nearly all of an archive and are stored as they are, so their size
+        // tells early whether it would fit.
+        let user = self
+            .user_repository
+            .get_user_by_username(username.clone())
+            .await?
+            .ok_or(NotFoundError("Could not find user".to_string()))?;
+        let image_bytes = self
+            .post_repository
+            .get_image_bytes_of_user(username.clone())
+            .await?;
+        let avatar_bytes = user.avatar.as_ref().map_or(0, Vec::len);
+        if image_bytes as usize + avatar_bytes > self.max_bytes {
+            return Err(self.too_large());
+        }
+
+        let token = generate_token();
+        let export = self
+            .export_repository
+            .add_export(NewDataExport {
+                username: username.clone(),
+                token_hash: hash_token(&token),
+                status: EXPORT_PENDING.to_string(),
+                created_at: now,
+                expires_at: now + self.export_ttl,
+            })
+            .await?
+            .ok_or_else(|| ConflictError("An export is already being prepared".to_string()))?;
+
+        let service = Arc::clone(self);
+        let export_id = export.id;
+        tokio::spawn(async move { service.build_export(export_id, username).await });
+
+        Ok(CreatedExportDTO {
+            export,
+            download_url: format!("{}/api/exports/{}", public_url(), token),
+        })
+    }
+
+    pub async fn get_exports(&self, username: String) -> AppResult<Vec<DataExport>> {
+        let now = Utc::now().naive_utc();
+        self.clean_up(now).await?;
+
+        self.export_repository.get_exports_of_user(username).await
+    }
+
+    /// The archive behind a download link, along with the name of its owner.
+    pub async fn download(&self, token: &str) -> AppResult<(String, Vec<u8>)> {
+        let export = self
+            .export_repository
+            .find_export(hash_token(token), Utc::now().naive_utc())
+            .await?;
+
+        match export {
+            Some((export, Some(archive))) if export.status == EXPORT_READY => {
+                Ok((export.username, archive))
+            }
+            Some((export, _)) if export.status == EXPORT_PENDING => Err(ConflictError(
+                "The export is still being prepared".to_string(),
+            )),
+            Some(_) => Err(ConflictError(
+                "The export failed, please request a new one".to_string(),
+            )),
+            None => Err(NotFoundError(
+                "This download link is invalid or has expired".to_string(),
+            )),
+        }
+    }
+
+    async fn clean_up(&self, now: NaiveDateTime) -> AppResult<()> {
+        self.export_repository.delete_expired_exports(now).await?;
+        self.export_repository
+            .fail_stale_exports(now - self.build_timeout, now)
+            .await
+    }
+
+    fn too_large(&self) -> AppError {
+        ConflictError(format!(
+            "Your data takes up more than the {} MB an export can hold",
+            self.max_bytes.div_ceil(1024 * 1024)
+        ))
+    }
+
+    async fn build_export(&self, export_id: i32, username: String) {
+        let (status, archive) = match self.collect_archive(username).await {
+            Ok(archive) => (EXPORT_READY, Some(archive)),
+            Err(err) => {
+                eprintln!("Building data export {export_id} failed: {err}");
+                (EXPORT_FAILED, None)
+            }
+        };
+
+        let now = Utc::now().naive_utc();
+        if let Err(err) = self
+            .export_repository
+            .finish_export(export_id, status, archive, now, now + self.export_ttl)
+            .await
+        {
+            eprintln!("Storing data export {export_id} failed: {err}");
+        }
+    }
+
+    async fn collect_archive(&self, username: String) -> AppResult<Vec<u8>> {
+        let user = self
+            .user_repository
+            .get_user_by_username(username.clone())
+            .await?
+            .ok_or(NotFoundError("Could not find user".to_string()))?;
+        let posts = self
+            .post_repository
+            .get_all_posts_by_username(username.clone())
+            .await?;
+        let events = self
+            .audit_repository
+            .get_all_events_of_user(username.clone())
+            .await?;
+        let sessions = self
+            .session_repository
+            .get_sessions_of_user(username)
+            .await?;
+
+        let archive = tokio::task::spawn_blocking(move || {
+            write_archive(&user, &posts, &events, &sessions)
+        })
+        .await
+        .map_err(|err| InternalError(err.to_string()))??;
+
+        if archive.len() > self.max_bytes {
+            return Err(self.too_large());
+        }
+        Ok(archive)
+    }
+}
+
+/// Lays out the archive as `profile.json`, `avatar.*`, `posts/*.md`, `images/*` and
+/// `history/*.json`.
+fn write_archive(
+    user: &User,
+    posts: &[Post],
+    events: &[AuditEvent],
+    sessions: &[Session],
+) -> AppResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let text = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Images are compressed already.
+    let binary = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let profile = serde_json::json!({
+        "username": user.username,
+        "joined": user.joined,
+        "email": user.email,
+        "emailVerified": user.email_verified,
+        "role": user.role,
+        "displayName": user.display_name,
+        "bio": user.bio,
+        "links": user.links,
+        "location": user.location,
+        "pronouns": user.pronouns,
+        "twoFactorEnabled": user.totp_enabled,
+        "exportedAt": Utc::now().naive_utc(),
+    });
+    add_json(&mut zip, "profile.json", &profile, text)?;
+
+    if let Some(avatar) = &user.avatar {
+        add_file(
+            &mut zip,
+            &format!("avatar.{}", extension(avatar)),
+            avatar,
+            binary,
+        )?;
+    }
+
+    for post in posts {
+        let image = post
+            .image
+            .as_ref()
+            .map(|image| (format!("images/{}.{}", post.id, extension(image)), image));
+
+        let mut markdown = String::from("---\n");
+        markdown.push_str(&format!("id: {}\n", post.id));
+        // JSON strings are valid YAML, which takes care of quoting.
+        markdown.push_str(&format!(
+            "title: {}\n",
+            serde_json::Value::from(post.title.as_str())
+        ));
+        markdown.push_str(&format!(
+            "date: {}\n",
+            post.date.format("%Y-%m-%dT%H:%M:%S")
+        ));
+        if let Some(hidden_at) = post.hidden_at {
+            markdown.push_str(&format!(
+                "hidden: {}\n",
+                hidden_at.format("%Y-%m-%dT%H:%M:%S")
+            ));
+        }
+        if let Some(held_at) = post.held_at {
+            markdown.push_str(&format!(
+                "held: {}\n",
+                held_at.format("%Y-%m-%dT%H:%M:%S")
+            ));
+        }
+        if let Some((path, _)) = &image {
+            markdown.push_str(&format!("image: ../{path}\n"));
+        }
+        markdown.push_str("---\n\n");
+        markdown.push_str(&post.body);
+        markdown.push('\n');
+
+        add_file(
+            &mut zip,
+            &format!("posts/{}-{}.md", post.date.format("%Y-%m-%d"), post.id),
+            markdown.as_bytes(),
+            text,
+        )?;
+        if let Some((path, data)) = image {
+            add_file(&mut zip, &path, data, binary)?;
+        }
+    }
+
+    let logins: Vec<&AuditEvent> = events
+        .iter()
+        .filter(|event| LOGIN_EVENTS.contains(&event.event.as_str()))
+        .collect();
+    add_json(&mut zip, "history/logins.json", &logins, text)?;
+    // Session ids are credentials, the archive only names them by a prefix of their hash.
+    let sessions: Vec<serde_json::Value> = sessions
+        .iter()
+        .map(|session| {
+            serde_json::json!({
+                "id": &hash_token(&session.session_id)[..12],
+                "createdAt": session.created_at,
+            })
+        })
+        .collect();
+    add_json(&mut zip, "history/sessions.json", &sessions, text)?;
+    add_json(&mut zip, "history/activity.json", &events, text)?;
+
+    let archive = zip.finish().map_err(archive_error)?;
+    Ok(archive.into_inner())
+}
+
+fn add_file(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    path: &str,
+    data: &[u8],
+    options: SimpleFileOptions,
+) -> AppResult<()> {
+    zip.start_file(path, options).map_err(archive_error)?;
+    zip.write_all(data).map_err(archive_error)
+}
+
+fn add_json(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    path: &str,
+    value: &impl serde::Serialize,
+    options: SimpleFileOptions,
+) -> AppResult<()> {
+    let data = serde_json::to_vec_pretty(value).map_err(archive_error)?;
+    add_file(zip, path, &data, options)
+}
+
+fn extension(image: &[u8]) -> &'static str {
+    match detect_image_type(image) {
+        Some("jpeg") => "jpg",
+        Some(kind) => kind,
+        None => "bin",
+    }
+}
+
+fn archive_error(err: impl std::fmt::Display) -> AppError {
+    InternalError(format!("Could not write archive: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn read(archive: &[u8], path: &str) -> String {
+        let mut zip = ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut content = String::new();
+        zip.by_name(path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn lists_sessions_without_their_ids() {
+        let user = User {
+            username: "exporter".to_string(),
+            ..Default::default()
+        };
+        let session = Session {
+            session_id: uuid::Uuid::new_v4().to_string(),
+            username: "exporter".to_string(),
+            csrf_token: generate_token(),
+            created_at: Utc::now().naive_utc(),
+        };
+
+        let archive = write_archive(&user, &[], &[], std::slice::from_ref(&session)).unwrap();
+        let sessions = read(&archive, "history/sessions.json");
+
+        assert!(sessions.contains(&hash_token(&session.session_id)[..12]));
+        assert!(!sessions.contains(&session.session_id));
+        assert!(!sessions.contains(&session.csrf_token));
+        assert!(read(&archive, "profile.json").contains("exporter"));
+    }
+}
@@ -1,2 +1,18 @@
 pub mod user;
-pub mod post;
\ No newline at end of file
+pub mod post;
+pub mod password;
+pub mod email;
+pub mod two_factor;
+pub mod webauthn;
+pub mod throttle;
+pub mod api_token;
+pub mod oidc;
+pub mod admin;
+pub mod audit;
+pub mod export;
+pub mod avatar;
+pub mod registration;
+pub mod challenge;
+pub mod rate_limit;
+pub mod report;
+pub mod screening;
@@ -0,0 +1,794 @@
+use crate::config::{env_list_or, env_or, public_url};
+use crate::error::AppError::{
+    ForbiddenError, LoginError, NotFoundError, OidcError, ValidationError,
+};
+use crate::error::AppResult;
+use crate::hashing::PasswordHasher;
+use crate::model::invite::RegistrationMode;
+use crate::model::oidc::{OidcCallbackQuery, OidcIdentity, OidcProviderDTO, OidcState};
+use crate::model::user::User;
+use crate::repository::oidc::OidcRepository;
+use crate::repository::user::UserRepository;
+use crate::token::{constant_time_eq, generate_token, hash_token};
+use crate::validation::{normalize_email, ValidationRules};
+use chrono::{Duration, NaiveDateTime, Utc};
+use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use openidconnect::{
+    reqwest, AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, Nonce, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Discovery documents (and the signing keys they point to) are fetched again after this long,
+/// so key rotations at the provider are picked up.
+const METADATA_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+type OidcClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+/// One configured identity provider. Every setting is read from `OIDC_<ID>_*`.
+struct OidcProvider {
+    id: String,
+    name: String,
+    issuer: IssuerUrl,
+    client_id: ClientId,
+    client_secret: Option<ClientSecret>,
+    scopes: Vec<String>,
+    allow_signup: bool,
+    metadata: Mutex<Option<(Instant, CoreProviderMetadata)>>,
+}
+
+impl OidcProvider {
+    fn from_env(id: &str) -> Option<Self> {
+        let prefix = format!("OIDC_{}", id.to_uppercase().replace('-', "_"));
+        let var = |key: &str| {
+            std::env::var(format!("{prefix}_{key}"))
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+
+        let issuer = IssuerUrl::new(var("ISSUER")?).ok()?;
+        let client_id = ClientId::new(var("CLIENT_ID")?);
+
+        Some(Self {
+            id: id.to_string(),
+            name: var("NAME").unwrap_or_else(|| id.to_string()),
+            issuer,
+            client_id,
+            client_secret: var("CLIENT_SECRET").map(ClientSecret::new),
+            scopes: env_list_or(&format!("{prefix}_SCOPES"), &["email", "profile"]),
+            allow_signup: env_or(&format!("{prefix}_ALLOW_SIGNUP"), true),
+            metadata: Mutex::new(None),
+        })
+    }
+}
+
+/// An authorization request the browser is about to be sent off with.
+pub struct OidcAuthorization {
+    pub url: String,
+    /// Hash of the request's `state`. The browser keeps it in a cookie, so only the browser
+    /// that started a login can finish it.
+    pub state_hash: String,
+    pub expires_in: Duration,
+}
+
+/// Where to send the browser once the provider redirected back.
+pub struct OidcLogin {
+    pub username: String,
+    /// `false` when an identity was linked to an already logged-in account.
+    pub start_session: bool,
+    /// The account has two-factor authentication on, the session may only be opened once the
+    /// second factor was checked.
+    pub two_factor_required: bool,
+    pub redirect_to: String,
+}
+
+/// OpenID Connect relying party: authorization code flow with PKCE against every provider
+/// listed in `OIDC_PROVIDERS`.
+pub struct OidcService {
+    providers: Vec<OidcProvider>,
+    http_client: reqwest::Client,
+    user_repository: UserRepository,
+    oidc_repository: OidcRepository,
+    validation_rules: Arc<ValidationRules>,
+    password_hasher: Arc<PasswordHasher>,
+    registration_mode: RegistrationMode,
+    state_ttl: Duration,
+}
+
+impl OidcService {
+    pub fn new(
+        user_repository: UserRepository,
+        oidc_repository: OidcRepository,
+        validation_rules: Arc<ValidationRules>,
+        password_hasher: Arc<PasswordHasher>,
+        registration_mode: RegistrationMode,
+    ) -> Self {
+        let providers = env_list_or("OIDC_PROVIDERS", &[])
+            .iter()
+            .map(|id| {
+                OidcProvider::from_env(id)
+                    .unwrap_or_else(|| panic!("OIDC provider {id} lacks an issuer or client id"))
+            })
+            .collect();
+
+        // The token endpoint must not be able to bounce requests elsewhere.
+        let http_client = reqwest::ClientBuilder::new()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+
+        Self {
+            providers,
+            http_client,
+            user_repository,
+            oidc_repository,
+            validation_rules,
+            password_hasher,
+            registration_mode,
+            state_ttl: Duration::seconds(env_or("OIDC_STATE_TTL_SECONDS", 600)),
+        }
+    }
+
+    pub fn get_providers(&self) -> Vec<OidcProviderDTO> {
+        self.providers
+            .iter()
+            .map(|provider| OidcProviderDTO {
+                id: provider.id.clone(),
+                name: provider.name.clone(),
+            })
+            .collect()
+    }
+
+    pub async fn get_identities(&self, username: String) -> AppResult<Vec<OidcIdentity>> {
+        self.oidc_repository.get_identities_of_user(username).await
+    }
+
+    pub async fn unlink(&self, username: String, provider_id: String) -> AppResult<()> {
+        let deleted = self
+            .oidc_repository
+            .delete_identity(username, provider_id)
+            .await?;
+
+        if deleted {
+            Ok(())
+        } else {
+            Err(NotFoundError(
+                "No identity is linked at this provider".to_string(),
+            ))
+        }
+    }
+
+    /// Starts an authorization request and returns the provider URL to send the browser to.
+    /// With `link_username` the resulting identity is attached to that account instead of
+    /// logging in.
+    pub async fn authorization_url(
+        &self,
+        provider_id: &str,
+        link_username: Option<String>,
+        redirect_to: Option<String>,
+    ) -> AppResult<OidcAuthorization> {
+        let provider = self.provider(provider_id)?;
+        let client = self.client(provider).await?;
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let mut request = client.authorize_url(
+            AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        );
+        for scope in &provider.scopes {
+            request = request.add_scope(Scope::new(scope.clone()));
+        }
+        let (url, csrf_token, nonce) = request.set_pkce_challenge(pkce_challenge).url();
+
+        let state_hash = hash_token(csrf_token.secret());
+        self.oidc_repository
+            .add_state(OidcState {
+                state_hash: state_hash.clone(),
+                provider: provider.id.clone(),
+                nonce: nonce.secret().clone(),
+                pkce_verifier: pkce_verifier.secret().clone(),
+                link_username,
+                redirect_to: sanitize_redirect(redirect_to),
+                expires_at: (Utc::now() + self.state_ttl).naive_utc(),
+            })
+            .await?;
+
+        Ok(OidcAuthorization {
+            url: url.to_string(),
+            state_hash,
+            expires_in: self.state_ttl,
+        })
+    }
+
+    /// Handles the provider's redirect: redeems the code, validates the ID token and resolves
+    /// it to a local account, creating one on first login. `state_cookie` is the state hash
+    /// the browser kept from [`OidcService::authorization_url`].
+    pub async fn complete(
+        &self,
+        provider_id: &str,
+        query: OidcCallbackQuery,
+        state_cookie: Option<&str>,
+    ) -> AppResult<OidcLogin> {
+        let provider = self.provider(provider_id)?;
+
+        if let Some(error) = query.error {
+            return Err(OidcError(query.error_description.unwrap_or(error)));
+        }
+        let (Some(code), Some(state)) = (query.code, query.state) else {
+            return Err(LoginError);
+        };
+
+        // A callback URL started in another browser, e.g. one an attacker lured the victim
+        // to, must not log the victim into the attacker's account. Checked before the state
+        // is consumed, so such a request can't burn the state of a genuine login either.
+        let state_hash = hash_token(&state);
+        if !state_cookie.is_some_and(|cookie| constant_time_eq(cookie, &state_hash)) {
+            return Err(LoginError);
+        }
+
+        let now = Utc::now().naive_utc();
+        let stored = self
+            .oidc_repository
+            .consume_state(state_hash, now)
+            .await?
+            .filter(|stored| stored.provider == provider.id)
+            .ok_or(LoginError)?;
+
+        let client = self.client(provider).await?;
+        let token_response = client
+            .exchange_code(AuthorizationCode::new(code))
+            .map_err(|err| OidcError(err.to_string()))?
+            .set_pkce_verifier(PkceCodeVerifier::new(stored.pkce_verifier))
+            .request_async(&self.http_client)
+            .await
+            .map_err(|err| OidcError(format!("Token exchange failed: {err}")))?;
+
+        let id_token = token_response
+            .id_token()
+            .ok_or_else(|| OidcError("The provider returned no ID token".to_string()))?;
+        let nonce = Nonce::new(stored.nonce);
+        let claims = match id_token.claims(&client.id_token_verifier(), &nonce) {
+            Ok(claims) => claims.clone(),
+            Err(_) => {
+                // The provider may have rotated its signing keys since discovery, so check
+                // once more against freshly fetched ones before giving up.
+                provider.metadata.lock().unwrap().take();
+                let client = self.client(provider).await?;
+                let verifier = client.id_token_verifier();
+                let claims = id_token
+                    .claims(&verifier, &nonce)
+                    .map_err(|_| LoginError)?
+                    .clone();
+                claims
+            }
+        };
+
+        let subject = claims.subject().to_string();
+        // Unverified addresses could belong to anyone, so they are never copied over.
+        let email = claims
+            .email()
+            .filter(|_| claims.email_verified() == Some(true))
+            .map(|address| normalize_email(address));
+        let preferred_username = claims.preferred_username().map(|name| name.to_string());
+
+        let identity = self
+            .oidc_repository
+            .find_identity(provider.id.clone(), subject.clone())
+            .await?;
+
+        let (username, start_session) = match (identity, stored.link_username) {
+            (Some(identity), Some(link)) if identity.username != link => {
+                return Err(ForbiddenError(
+                    "This identity is already linked to another account".to_string(),
+                ));
+            }
+            (Some(identity), link) => (identity.username, link.is_none()),
+            (None, Some(link)) => {
+                self.add_identity(provider, &subject, &link, email.clone(), now)
+                    .await?;
+                (link, false)
+            }
+            (None, None) => {
+                if !provider.allow_signup {
+                    return Err(ForbiddenError(
+                        "No account is linked to this identity".to_string(),
+                    ));
+                }
+                let username = self
+                    .create_user(preferred_username.as_deref(), email.clone())
+                    .await?;
+                self.add_identity(provider, &subject, &username, email.clone(), now)
+                    .await?;
+                (username, true)
+            }
+        };
+
+        self.oidc_repository
+            .record_login(provider.id.clone(), subject, email, now)
+            .await?;
+
+        // The provider stands in for the password only, just like a password login the
+        // second factor is still asked for.
+        let two_factor_required = if start_session {
+            let user = self
+                .user_repository
+                .get_user_by_username(username.clone())
+                .await?
+                .ok_or(LoginError)?;
+            user.ensure_active(now)?;
+            user.totp_enabled
+        } else {
+            false
+        };
+
+        Ok(OidcLogin {
+            username,
+            start_session,
+            two_factor_required,
+            redirect_to: stored.redirect_to,
+        })
+    }
+
+    fn provider(&self, provider_id: &str) -> AppResult<&OidcProvider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.id == provider_id)
+            .ok_or_else(|| NotFoundError("Unknown identity provider".to_string()))
+    }
+
+    async fn metadata(&self, provider: &OidcProvider) -> AppResult<CoreProviderMetadata> {
+        let cached = {
+            let guard = provider.metadata.lock().unwrap();
+            guard
+                .as_ref()
+                .filter(|(fetched, _)| fetched.elapsed() < METADATA_TTL)
+                .map(|(_, metadata)| metadata.clone())
+        };
+        if let Some(metadata) = cached {
+            return Ok(metadata);
+        }
+
+        let metadata =
+            CoreProviderMetadata::discover_async(provider.issuer.clone(), &self.http_client)
+                .await
+                .map_err(|err| OidcError(format!("Discovery failed: {err}")))?;
+        *provider.metadata.lock().unwrap() = Some((Instant::now(), metadata.clone()));
+
+        Ok(metadata)
+    }
+
+    async fn client(&self, provider: &OidcProvider) -> AppResult<OidcClient> {
+        let metadata = self.metadata(provider).await?;
+        let redirect_url = format!("{}/api/auth/oidc/{}/callback", public_url(), provider.id);
+
+        Ok(CoreClient::from_provider_metadata(
+            metadata,
+            provider.client_id.clone(),
+            provider.client_secret.clone(),
+        )
+        .set_redirect_uri(
+            RedirectUrl::new(redirect_url).map_err(|err| OidcError(err.to_string()))?,
+        ))
+    }
+
+    async fn add_identity(
+        &self,
+        provider: &OidcProvider,
+        subject: &str,
+        username: &str,
+        email: Option<String>,
+        now: NaiveDateTime,
+    ) -> AppResult<()> {
+        self.oidc_repository
+            .add_identity(OidcIdentity {
+                provider: provider.id.clone(),
+                subject: subject.to_string(),
+                username: username.to_string(),
+                email,
+                created_at: now,
+                last_login_at: None,
+            })
+            .await
+    }
+
+    /// Creates a local account for a first-time login. The username is derived from the
+    /// provider's claims and suffixed until it is free; the password is random and unknown,
+    /// so the account can only be used through the provider until a password is reset.
+    /// There is no way to pass an invite along, so invite-only registration refuses these.
+    async fn create_user(
+        &self,
+        preferred_username: Option<&str>,
+        email: Option<String>,
+    ) -> AppResult<String> {
+        let approval_pending = match self.registration_mode {
+            RegistrationMode::Open => false,
+            RegistrationMode::Approval => true,
+            RegistrationMode::Invite => {
+                return Err(ForbiddenError(
+                    "An invite code is required to sign up".to_string(),
+                ))
+            }
+            RegistrationMode::Closed => {
+                return Err(ForbiddenError("Registration is closed".to_string()))
+            }
+        };
+
+        let base = username_base(
+            preferred_username
+                .or_else(|| {
+                    email
+                        .as_deref()
+                        .and_then(|address| address.split('@').next())
+                })
+                .unwrap_or("user"),
+            self.validation_rules.username_min_length,
+            self.validation_rules.username_max_length.saturating_sub(4),
+        );
+
+        let reserved_since = Utc::now().naive_utc()
+            - Duration::days(self.validation_rules.username_reservation_days);
+        let mut username = None;
+        for attempt in 1..=100 {
+            let candidate = match attempt {
+                1 => base.clone(),
+                _ => format!("{base}_{attempt}"),
+            };
+            if self.validation_rules.validate_username(&candidate).is_ok()
+                && self
+                    .user_repository
+                    .is_username_available(candidate.clone(), reserved_since, None)
+                    .await?
+            {
+                username = Some(candidate);
+                break;
+            }
+        }
+        let username =
+            username.ok_or_else(|| OidcError("Could not derive a free username".to_string()))?;
+
+        let password = self.password_hasher.hash(&generate_token()).await?;
+        let user = |email: Option<String>| User {
+            username: username.clone(),
+            password: password.clone(),
+            joined: Utc::now().date_naive(),
+            email_verified: email.is_some(),
+            email,
+            approval_pending,
+            ..Default::default()
+        };
+
+        match self
+            .user_repository
+            .create_new_user(user(email.clone()))
+            .await
+        {
+            // The address already belongs to another account, create this one without it.
+            Err(ValidationError(_)) if email.is_some() => {
+                self.user_repository.create_new_user(user(None)).await?
+            }
+            result => result?,
+        }
+
+        Ok(username)
+    }
+}
+
+/// Only local paths are accepted so the callback can't be turned into an open redirect.
+fn sanitize_redirect(redirect_to: Option<String>) -> String {
+    redirect_to
+        .filter(|path| path.starts_with('/') && !path.starts_with("//") && !path.contains('\\'))
+        .unwrap_or_else(|| "/".to_string())
+}
+
+fn username_base(source: &str, min_length: usize, max_length: usize) -> String {
+    let mut base = source
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(max_length.max(1))
+        .collect::<String>();
+    while base.len() < min_length {
+        base.push('_');
+    }
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::test_pool;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::{Form, Json};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use openidconnect::url::Url;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use openssl::sign::Signer;
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+
+    const CLIENT_ID: &str = "blog";
+
+    /// What the provider remembers about an authorization between redirect and token request.
+    struct Grant {
+        code_challenge: String,
+        nonce: String,
+        subject: String,
+    }
+
+    /// A minimal OpenID provider: discovery, keys and a token endpoint that checks PKCE.
+    struct MockProvider {
+        issuer: String,
+        key: PKey<Private>,
+        grants: Mutex<HashMap<String, Grant>>,
+    }
+
+    impl MockProvider {
+        async fn start() -> Arc<Self> {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let provider = Arc::new(Self {
+                issuer: format!("http://{}", listener.local_addr().unwrap()),
+                key: PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(),
+                grants: Mutex::new(HashMap::new()),
+            });
+
+            let app = axum::Router::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    axum::routing::get(discovery),
+                )
+                .route("/jwks", axum::routing::get(jwks))
+                .route("/token", axum::routing::post(token))
+                .with_state(provider.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            provider
+        }
+
+        /// Plays the user logging in at the provider: takes the authorization URL and returns
+        /// the callback the browser would be redirected to. `nonce` overrides the nonce the
+        /// ID token will carry.
+        fn authorize(&self, url: &str, subject: &str, nonce: Option<&str>) -> OidcCallbackQuery {
+            let url = Url::parse(url).unwrap();
+            let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+            assert_eq!(params["client_id"], CLIENT_ID);
+            assert_eq!(params["code_challenge_method"], "S256");
+
+            let code = generate_token();
+            self.grants.lock().unwrap().insert(
+                code.clone(),
+                Grant {
+                    code_challenge: params["code_challenge"].clone(),
+                    nonce: nonce.unwrap_or(&params["nonce"]).to_string(),
+                    subject: subject.to_string(),
+                },
+            );
+
+            OidcCallbackQuery {
+                code: Some(code),
+                state: Some(params["state"].clone()),
+                error: None,
+                error_description: None,
+            }
+        }
+
+        fn id_token(&self, grant: &Grant) -> String {
+            let now = Utc::now().timestamp();
+            let header = json!({"alg": "RS256", "kid": "test", "typ": "JWT"});
+            let claims = json!({
+                "iss": self.issuer,
+                "aud": CLIENT_ID,
+                "sub": grant.subject,
+                "iat": now,
+                "exp": now + 300,
+                "nonce": grant.nonce,
+                "preferred_username": "mock_user",
+            });
+            let signing_input = format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(header.to_string()),
+                URL_SAFE_NO_PAD.encode(claims.to_string())
+            );
+
+            let mut signer = Signer::new(MessageDigest::sha256(), &self.key).unwrap();
+            signer.update(signing_input.as_bytes()).unwrap();
+            let signature = signer.sign_to_vec().unwrap();
+
+            format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature))
+        }
+    }
+
+    async fn discovery(State(provider): State<Arc<MockProvider>>) -> Json<serde_json::Value> {
+        let issuer = &provider.issuer;
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+            "jwks_uri": format!("{issuer}/jwks"),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        }))
+    }
+
+    async fn jwks(State(provider): State<Arc<MockProvider>>) -> Json<serde_json::Value> {
+        let rsa = provider.key.rsa().unwrap();
+        Json(json!({
+            "keys": [{
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": "test",
+                "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+            }]
+        }))
+    }
+
+    async fn token(
+        State(provider): State<Arc<MockProvider>>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        let grant = provider.grants.lock().unwrap().remove(&form["code"]);
+        let verified = grant.as_ref().is_some_and(|grant| {
+            let verifier = form.get("code_verifier").map(String::as_str).unwrap_or("");
+            URL_SAFE_NO_PAD.encode(Sha256::digest(verifier)) == grant.code_challenge
+        });
+
+        match grant {
+            Some(grant) if verified => Json(json!({
+                "access_token": generate_token(),
+                "token_type": "Bearer",
+                "expires_in": 300,
+                "id_token": provider.id_token(&grant),
+            }))
+            .into_response(),
+            _ => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_grant"})),
+            )
+                .into_response(),
+        }
+    }
+
+    fn service(pool: &deadpool_diesel::postgres::Pool, mock: &MockProvider) -> OidcService {
+        OidcService {
+            providers: vec![OidcProvider {
+                id: "mock".to_string(),
+                name: "Mock".to_string(),
+                issuer: IssuerUrl::new(mock.issuer.clone()).unwrap(),
+                client_id: ClientId::new(CLIENT_ID.to_string()),
+                client_secret: Some(ClientSecret::new("secret".to_string())),
+                scopes: vec!["profile".to_string()],
+                allow_signup: true,
+                metadata: Mutex::new(None),
+            }],
+            http_client: reqwest::Client::new(),
+            user_repository: UserRepository::new(pool.clone()),
+            oidc_repository: OidcRepository::new(pool.clone()),
+            validation_rules: Arc::new(ValidationRules::from_env()),
+            password_hasher: Arc::new(PasswordHasher::from_env()),
+            registration_mode: RegistrationMode::Open,
+            state_ttl: Duration::minutes(5),
+        }
+    }
+
+    async fn log_in(
+        service: &OidcService,
+        mock: &MockProvider,
+        subject: &str,
+    ) -> AppResult<OidcLogin> {
+        let authorization = service.authorization_url("mock", None, None).await?;
+        let callback = mock.authorize(&authorization.url, subject, None);
+        service
+            .complete("mock", callback, Some(&authorization.state_hash))
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn signs_up_and_logs_in_through_the_provider() {
+        let pool = test_pool();
+        let mock = MockProvider::start().await;
+        let service = service(&pool, &mock);
+        let subject = generate_token();
+
+        let first = log_in(&service, &mock, &subject).await.unwrap();
+        assert!(first.start_session);
+        assert!(!first.two_factor_required);
+        assert!(first.username.starts_with("mock_user"));
+        assert_eq!(first.redirect_to, "/");
+
+        let second = log_in(&service, &mock, &subject).await.unwrap();
+        assert_eq!(second.username, first.username);
+
+        // With a second factor on, the provider only replaces the password.
+        let users = UserRepository::new(pool);
+        users.enable_totp(first.username.clone()).await.unwrap();
+        let third = log_in(&service, &mock, &subject).await.unwrap();
+        assert!(third.two_factor_required);
+
+        users.delete_user(first.username).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn rejects_a_callback_without_the_state_cookie() {
+        let pool = test_pool();
+        let mock = MockProvider::start().await;
+        let service = service(&pool, &mock);
+
+        let authorization = service.authorization_url("mock", None, None).await.unwrap();
+        let other = service.authorization_url("mock", None, None).await.unwrap();
+        let callback = || mock.authorize(&authorization.url, &generate_token(), None);
+
+        for cookie in [None, Some(other.state_hash.as_str())] {
+            let result = service.complete("mock", callback(), cookie).await;
+            assert!(matches!(result, Err(LoginError)));
+        }
+
+        // The forged callbacks didn't use the state up, its own browser can still finish.
+        let login = service
+            .complete("mock", callback(), Some(&authorization.state_hash))
+            .await
+            .unwrap();
+        UserRepository::new(pool)
+            .delete_user(login.username)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn rejects_an_id_token_with_another_nonce() {
+        let pool = test_pool();
+        let mock = MockProvider::start().await;
+        let service = service(&pool, &mock);
+
+        let authorization = service.authorization_url("mock", None, None).await.unwrap();
+        let callback = mock.authorize(&authorization.url, &generate_token(), Some("replayed"));
+        let result = service
+            .complete("mock", callback, Some(&authorization.state_hash))
+            .await;
+
+        assert!(matches!(result, Err(LoginError)));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn rejects_a_code_redeemed_without_the_pkce_verifier() {
+        let pool = test_pool();
+        let mock = MockProvider::start().await;
+        let service = service(&pool, &mock);
+
+        let authorization = service.authorization_url("mock", None, None).await.unwrap();
+        let callback = mock.authorize(&authorization.url, &generate_token(), None);
+        // The code leaked to someone who didn't start the flow and so lacks the verifier.
+        mock.grants
+            .lock()
+            .unwrap()
+            .get_mut(callback.code.as_deref().unwrap())
+            .unwrap()
+            .code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest("another verifier"));
+
+        let result = service
+            .complete("mock", callback, Some(&authorization.state_hash))
+            .await;
+
+        assert!(matches!(result, Err(OidcError(_))));
+    }
+}
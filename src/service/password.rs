@@ -0,0 +1,166 @@
+use crate::config::{env_or, public_url};
+use crate::error::AppError::{LoginError, ValidationError};
+use crate::error::{AppResult, FieldError};
+use crate::hashing::PasswordHasher;
+use crate::mailer::{Mail, Mailer};
+use crate::model::password_reset::PasswordResetToken;
+use crate::model::user::UpdateUser;
+use crate::repository::password_reset::PasswordResetRepository;
+use crate::repository::session::SessionRepository;
+use crate::repository::user::UserRepository;
+use crate::token::{generate_token, hash_token};
+use crate::validation::ValidationRules;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+
+pub struct PasswordService {
+    user_repository: UserRepository,
+    session_repository: SessionRepository,
+    reset_repository: PasswordResetRepository,
+    mailer: Arc<dyn Mailer>,
+    validation_rules: Arc<ValidationRules>,
+    password_hasher: Arc<PasswordHasher>,
+    reset_token_ttl: Duration,
+    public_url: String,
+}
+
+impl PasswordService {
+    pub fn new(
+        user_repository: UserRepository,
+        session_repository: SessionRepository,
+        reset_repository: PasswordResetRepository,
+        mailer: Arc<dyn Mailer>,
+        validation_rules: Arc<ValidationRules>,
+        password_hasher: Arc<PasswordHasher>,
+    ) -> Self {
+        Self {
+            user_repository,
+            session_repository,
+            reset_repository,
+            mailer,
+            validation_rules,
+            password_hasher,
+            reset_token_ttl: Duration::minutes(env_or("PASSWORD_RESET_TTL_MINUTES", 30)),
+            public_url: public_url(),
+        }
+    }
+
+    /// Changes the password of a logged-in user and signs out every other session.
+    pub async fn change_password(
+        &self,
+        username: String,
+        session_id: String,
+        current_password: String,
+        new_password: String,
+    ) -> AppResult<()> {
+        let user = self
+            .user_repository
+            .get_user_by_username(username.clone())
+            .await?
+            .ok_or(LoginError)?;
+
+        if !self
+            .password_hasher
+            .verify(&current_password, &user.password)
+            .await?
+        {
+            return Err(ValidationError(vec![FieldError::new(
+                "currentPassword",
+                "Current password is incorrect",
+            )]));
+        }
+
+        self.set_password(username.clone(), new_password).await?;
+        self.session_repository
+            .delete_sessions_of_user(username, Some(session_id))
+            .await
+    }
+
+    /// Mails a single-use reset token to the verified address of the account. Unknown usernames
+    /// and accounts without a verified address are silently ignored so the endpoint can't be
+    /// used to probe which accounts exist.
+    pub async fn request_reset(&self, username: String) -> AppResult<()> {
+        let user = self
+            .user_repository
+            .get_user_by_username(username)
+            .await?;
+
+        let Some(user) = user else {
+            return Ok(());
+        };
+        // Only verified addresses are trusted with account recovery.
+        let Some(email) = user.email.filter(|_| user.email_verified) else {
+            return Ok(());
+        };
+
+        let token = generate_token();
+        self.reset_repository
+            .replace_token(PasswordResetToken {
+                token_hash: hash_token(&token),
+                username: user.username.clone(),
+                expires_at: (Utc::now() + self.reset_token_ttl).naive_utc(),
+                used_at: None,
+            })
+            .await?;
+
+        self.mailer
+            .send(Mail {
+                to: email,
+                subject: "Reset your RustyPosts password".to_string(),
+                body: format!(
+                    "Someone asked to reset the password of your account {}.\n\n\
+                     Use the following link within {} minutes to choose a new one:\n\
+                     {}/reset-password?token={}\n\n\
+                     If this wasn't you, you can ignore this message.",
+                    user.username,
+                    self.reset_token_ttl.num_minutes(),
+                    self.public_url,
+                    token
+                ),
+            })
+            .await
+    }
+
+    /// Redeems a reset token, sets the new password and signs the user out everywhere.
+    pub async fn confirm_reset(&self, token: String, new_password: String) -> AppResult<()> {
+        let invalid_token = || {
+            ValidationError(vec![FieldError::new(
+                "token",
+                "Reset link is invalid or has expired",
+            )])
+        };
+        let hash = hash_token(&token);
+        let now = Utc::now().naive_utc();
+
+        let pending = self
+            .reset_repository
+            .find_valid_token(hash.clone(), now)
+            .await?
+            .ok_or_else(invalid_token)?;
+
+        // Validate before redeeming the token, so a rejected password can be retried.
+        self.validation_rules
+            .validate_new_password(&pending.username, &new_password)?;
+        let hashed_pass = self.password_hasher.hash(&new_password).await?;
+
+        self.reset_repository
+            .reset_password(hash, now, hashed_pass)
+            .await?
+            .ok_or_else(invalid_token)?;
+        Ok(())
+    }
+
+    async fn set_password(&self, username: String, new_password: String) -> AppResult<()> {
+        self.validation_rules
+            .validate_new_password(&username, &new_password)?;
+
+        let hashed_pass = self.password_hasher.hash(&new_password).await?;
+        self.user_repository
+            .update_user(UpdateUser {
+                username,
+                password: Some(hashed_pass),
+                avatar: None,
+            })
+            .await
+    }
+}
@@ -1,50 +1,95 @@
+use crate::error::AppError::{ConflictError, NotFoundError};
 use crate::error::AppResult;
-use crate::model::post::{NewPost, Post};
+use crate::model::moderation::{
+    ModerationAction, NewModerationAction, PostChange, ACTION_DELETE, ACTION_HIDE,
+    ACTION_RELEASE, ACTION_UNHIDE,
+};
+use crate::model::post::{CreatedPostDTO, NewPost, Post};
+use crate::model::role::Permission;
 use crate::model::user::User;
+use crate::repository::moderation::ModerationRepository;
 use crate::repository::post::PostRepository;
+use crate::service::screening::ScreeningService;
+use crate::validation::ValidationRules;
+use std::sync::Arc;
 
 pub struct PostService {
     post_repository: PostRepository,
+    moderation_repository: ModerationRepository,
+    validation_rules: Arc<ValidationRules>,
+    screening_service: Arc<ScreeningService>,
 }
 
 impl PostService {
-    pub fn new(post_repository: PostRepository) -> Self {
-        Self { post_repository }
+    pub fn new(
+        post_repository: PostRepository,
+        moderation_repository: ModerationRepository,
+        validation_rules: Arc<ValidationRules>,
+        screening_service: Arc<ScreeningService>,
+    ) -> Self {
+        Self {
+            post_repository,
+            moderation_repository,
+            validation_rules,
+            screening_service,
+        }
     }
     pub async fn get_posts_on_page(&self, page: u32) -> AppResult<Vec<Post>> {
         self.post_repository.fetch_posts_on_page(page).await
     }
     
-    pub async fn get_post(&self, id: i32) -> AppResult<Option<Post>> {
-        let result = self.post_repository.fetch_post(id).await?;
-        Ok(result)
+    /// Hidden and held posts are only returned to their author and to moderators, posts of
+    /// accounts scheduled for deletion only to moderators.
+    pub async fn get_post(&self, id: i32, viewer: Option<&User>) -> AppResult<Option<Post>> {
+        let result = self.post_repository.fetch_post_with_author_leaving(id).await?;
+        let is_moderator =
+            viewer.is_some_and(|viewer| viewer.role.grants(Permission::ModeratePosts));
+
+        Ok(result
+            .filter(|(post, author_leaving)| {
+                Self::is_visible_to(post, viewer) && (!author_leaving || is_moderator)
+            })
+            .map(|(post, _)| post))
     }
 
-    pub async fn get_post_image(&self, id: i32) -> AppResult<Option<Vec<u8>>> {
-        let result = self.post_repository.fetch_post(id).await?;
+    pub async fn get_post_image(&self, id: i32, viewer: Option<&User>) -> AppResult<Option<Vec<u8>>> {
+        let result = self.get_post(id, viewer).await?;
         match result {
             None => Ok(None),
             Some(post) => Ok(post.image),
         }
     }
 
+    /// Posts that screening finds suspicious are saved but held back until a moderator
+    /// releases them.
     pub async fn create_post(
         &self,
         title: String,
         body: String,
         image: Option<Vec<u8>>,
-        username: String,
-    ) -> AppResult<()> {
+        author: &User,
+    ) -> AppResult<CreatedPostDTO> {
+        self.validation_rules
+            .validate_post(&title, &body, image.as_deref())?;
+        let screening = self.screening_service.screen(author, &title, &body).await?;
+        let now = chrono::Utc::now().naive_utc();
+
         let post = NewPost {
             title,
             body,
             image,
-            username,
-            date: chrono::Utc::now().naive_utc(),
+            username: author.username.clone(),
+            date: now,
+            held_at: screening.held_reason.as_ref().map(|_| now),
+            held_reason: screening.held_reason.clone(),
+            content_hash: screening.content_hash,
         };
 
-        self.post_repository.create_post(post).await?;
-        Ok(())
+        let id = self.post_repository.create_post(post).await?;
+        Ok(CreatedPostDTO {
+            id,
+            held_reason: screening.held_reason,
+        })
     }
     
     pub async fn get_posts_of_user(&self, user: &User, page: i32) -> AppResult<Vec<Post>> {
@@ -58,4 +103,132 @@ impl PostService {
     pub async fn delete_post_of_user(&self, username: String, post_id: i32) -> AppResult<()> {
         self.post_repository.delete_post_belonging_to_username(post_id, username).await
     }
+
+    /// Whether `post` belongs to `username`; `None` if there is no such post.
+    pub async fn is_post_of_user(&self, username: &str, post_id: i32) -> AppResult<Option<bool>> {
+        let result = self.post_repository.fetch_post(post_id).await?;
+        Ok(result.map(|post| post.username.as_deref() == Some(username)))
+    }
+
+    pub async fn hide_post(
+        &self,
+        moderator: &User,
+        post_id: i32,
+        reason: Option<String>,
+    ) -> AppResult<Post> {
+        let post = self.find_post(post_id).await?;
+        let change = PostChange::Hide {
+            at: chrono::Utc::now().naive_utc(),
+            by: moderator.username.clone(),
+        };
+
+        self.apply(moderator, ACTION_HIDE, change, &post, reason).await?;
+        Ok(post)
+    }
+
+    pub async fn unhide_post(
+        &self,
+        moderator: &User,
+        post_id: i32,
+        reason: Option<String>,
+    ) -> AppResult<Post> {
+        let post = self.find_post(post_id).await?;
+
+        self.apply(moderator, ACTION_UNHIDE, PostChange::Unhide, &post, reason)
+            .await?;
+        Ok(post)
+    }
+
+    /// Deletes the post of another user; authors remove their own through
+    /// [`PostService::delete_post_of_user`], which stays out of the moderation log. Returns the
+    /// deleted post.
+    pub async fn moderate_delete(
+        &self,
+        moderator: &User,
+        post_id: i32,
+        reason: Option<String>,
+    ) -> AppResult<Post> {
+        let post = self.find_post(post_id).await?;
+
+        self.apply(moderator, ACTION_DELETE, PostChange::Delete, &post, reason)
+            .await?;
+        Ok(post)
+    }
+
+    pub async fn get_held_posts(&self, page: u32) -> AppResult<Vec<Post>> {
+        self.post_repository.fetch_held_posts(page).await
+    }
+
+    /// Publishes a post that screening held back.
+    pub async fn release_post(
+        &self,
+        moderator: &User,
+        post_id: i32,
+        reason: Option<String>,
+    ) -> AppResult<Post> {
+        let post = self.find_post(post_id).await?;
+        if post.held_at.is_none() {
+            return Err(ConflictError("The post isn't held for review".to_string()));
+        }
+
+        self.apply(moderator, ACTION_RELEASE, PostChange::Release, &post, reason)
+            .await?;
+        Ok(post)
+    }
+
+    pub async fn get_moderation_actions(&self, page: u32) -> AppResult<Vec<ModerationAction>> {
+        self.moderation_repository.fetch_actions_on_page(page).await
+    }
+
+    fn is_visible_to(post: &Post, viewer: Option<&User>) -> bool {
+        (post.hidden_at.is_none() && post.held_at.is_none())
+            || viewer.is_some_and(|viewer| {
+                post.username.as_ref() == Some(&viewer.username)
+                    || viewer.role.grants(Permission::ModeratePosts)
+            })
+    }
+
+    async fn find_post(&self, post_id: i32) -> AppResult<Post> {
+        self.post_repository
+            .fetch_post(post_id)
+            .await?
+            .ok_or(NotFoundError("Could not find post".to_string()))
+    }
+
+    /// Changes the post and logs the action together. Fails with `404` if the post was deleted
+    /// in the meantime.
+    async fn apply(
+        &self,
+        moderator: &User,
+        action: &str,
+        change: PostChange,
+        post: &Post,
+        reason: Option<String>,
+    ) -> AppResult<()> {
+        let reason = reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+
+        let applied = self
+            .moderation_repository
+            .apply_action(
+                change,
+                NewModerationAction {
+                moderator: moderator.username.clone(),
+                action: action.to_string(),
+                post_id: post.id,
+                post_author: post.username.clone(),
+                post_title: post.title.clone(),
+                reason,
+                created_at: chrono::Utc::now().naive_utc(),
+                },
+            )
+            .await?;
+
+        if applied {
+            Ok(())
+        } else {
+            Err(NotFoundError("Could not find post".to_string()))
+        }
+    }
 }
@@ -0,0 +1,221 @@
+use crate::config::env_or;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Buckets are swept once the table grows past this many keys.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Routes that share a rate limit. Each client gets one bucket per group.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RouteGroup {
+    /// Logging in, signing up and the other endpoints that take credentials.
+    Auth,
+    /// Every other request that changes something.
+    Writes,
+    Reads,
+    /// Avatars and post images.
+    Media,
+}
+
+impl RouteGroup {
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            RouteGroup::Auth => "RATE_LIMIT_AUTH",
+            RouteGroup::Writes => "RATE_LIMIT_WRITES",
+            RouteGroup::Reads => "RATE_LIMIT_READS",
+            RouteGroup::Media => "RATE_LIMIT_MEDIA",
+        }
+    }
+}
+
+/// Who a bucket belongs to. Every request is counted against its IP. Requests with a session
+/// cookie or API token are also counted per credential, so a user can't get around the limit
+/// by switching networks. The credential isn't looked up, which is why it never replaces the
+/// IP: a made-up one would otherwise come with a fresh bucket.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum RateLimitKey {
+    Ip(IpAddr),
+    /// Hash of the session id or API token.
+    Credential(String),
+}
+
+struct BucketPolicy {
+    /// Requests that can be made in a burst.
+    capacity: f64,
+    /// Requests added back per second.
+    refill_rate: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Outcome of taking a token, along with what the `RateLimit-*` headers report.
+pub struct RateLimitDecision {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request is let through, if this one wasn't.
+    pub retry_after: Option<u64>,
+}
+
+/// Token buckets for every API request, configured per [`RouteGroup`] through
+/// `RATE_LIMIT_<GROUP>_BURST` and `RATE_LIMIT_<GROUP>_PER_MINUTE`. A rate of 0 turns the limit
+/// off for that group.
+pub struct RateLimitService {
+    policies: HashMap<RouteGroup, BucketPolicy>,
+    buckets: Mutex<HashMap<(RouteGroup, RateLimitKey), Bucket>>,
+}
+
+impl RateLimitService {
+    pub fn new() -> Self {
+        let policies = [
+            (RouteGroup::Auth, 10, 20),
+            (RouteGroup::Writes, 30, 60),
+            (RouteGroup::Reads, 300, 600),
+            (RouteGroup::Media, 200, 1200),
+        ]
+        .into_iter()
+        .filter_map(|(group, burst, per_minute)| {
+            let prefix = group.env_prefix();
+            let per_minute: u32 = env_or(&format!("{prefix}_PER_MINUTE"), per_minute);
+            let burst: u32 = env_or(&format!("{prefix}_BURST"), burst);
+            (per_minute > 0).then(|| {
+                let policy = BucketPolicy {
+                    capacity: f64::from(burst.max(1)),
+                    refill_rate: f64::from(per_minute) / 60.0,
+                };
+                (group, policy)
+            })
+        })
+        .collect();
+
+        Self {
+            policies,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of every key in `group`, or from none of them if one is
+    /// empty, and reports on the emptiest. Returns `None` if the group isn't limited.
+    pub fn acquire(&self, group: RouteGroup, keys: &[RateLimitKey]) -> Option<RateLimitDecision> {
+        let policy = self.policies.get(&group)?;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            let policies = &self.policies;
+            // A bucket that has filled up again behaves exactly like a new one.
+            buckets.retain(|(group, _), bucket| {
+                policies
+                    .get(group)
+                    .is_some_and(|policy| refilled(bucket, policy, now) < policy.capacity)
+            });
+        }
+
+        let mut tokens = Vec::with_capacity(keys.len());
+        for key in keys {
+            let bucket = buckets.entry((group, key.clone())).or_insert(Bucket {
+                tokens: policy.capacity,
+                updated: now,
+            });
+            bucket.tokens = refilled(bucket, policy, now);
+            bucket.updated = now;
+            tokens.push(bucket.tokens);
+        }
+
+        let mut lowest = tokens.into_iter().fold(policy.capacity, f64::min);
+        let allowed = lowest >= 1.0;
+        if allowed {
+            for key in keys {
+                if let Some(bucket) = buckets.get_mut(&(group, key.clone())) {
+                    bucket.tokens -= 1.0;
+                }
+            }
+            lowest -= 1.0;
+        }
+
+        let seconds_until = |tokens: f64| (tokens.max(0.0) / policy.refill_rate).ceil() as u64;
+
+        Some(RateLimitDecision {
+            limit: policy.capacity as u64,
+            remaining: lowest.floor() as u64,
+            reset: seconds_until(policy.capacity - lowest),
+            retry_after: (!allowed).then(|| seconds_until(1.0 - lowest).max(1)),
+        })
+    }
+}
+
+fn refilled(bucket: &Bucket, policy: &BucketPolicy, now: Instant) -> f64 {
+    let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * policy.refill_rate).min(policy.capacity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> RateLimitService {
+        RateLimitService {
+            policies: HashMap::from([(
+                RouteGroup::Reads,
+                BucketPolicy {
+                    capacity: 2.0,
+                    refill_rate: 0.001,
+                },
+            )]),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn ip(last: u8) -> RateLimitKey {
+        RateLimitKey::Ip(IpAddr::from([192, 0, 2, last]))
+    }
+
+    fn credential(value: &str) -> RateLimitKey {
+        RateLimitKey::Credential(value.to_string())
+    }
+
+    fn allowed(service: &RateLimitService, keys: &[RateLimitKey]) -> bool {
+        service
+            .acquire(RouteGroup::Reads, keys)
+            .unwrap()
+            .retry_after
+            .is_none()
+    }
+
+    #[test]
+    fn limits_each_client_separately() {
+        let service = service();
+        assert!(allowed(&service, &[ip(1)]));
+        assert!(allowed(&service, &[ip(1)]));
+        assert!(!allowed(&service, &[ip(1)]));
+
+        assert!(allowed(&service, &[ip(2)]));
+        assert!(service.acquire(RouteGroup::Writes, &[ip(1)]).is_none());
+    }
+
+    #[test]
+    fn made_up_credentials_dont_escape_the_ip_limit() {
+        let service = service();
+        assert!(allowed(&service, &[ip(1), credential("a")]));
+        assert!(allowed(&service, &[ip(1), credential("b")]));
+        assert!(!allowed(&service, &[ip(1), credential("c")]));
+    }
+
+    #[test]
+    fn a_credential_is_limited_across_addresses() {
+        let service = service();
+        assert!(allowed(&service, &[ip(1), credential("a")]));
+        assert!(allowed(&service, &[ip(2), credential("a")]));
+        assert!(!allowed(&service, &[ip(3), credential("a")]));
+
+        // The rejected request took nothing from the fresh IP bucket.
+        let decision = service.acquire(RouteGroup::Reads, &[ip(3)]).unwrap();
+        assert_eq!(decision.remaining, 1);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::env_or;
+use crate::error::AppError::{ForbiddenError, NotFoundError, ValidationError};
+use crate::error::{AppResult, FieldError};
+use crate::model::invite::{
+    Admission, CreateInviteForm, CreatedInviteDTO, Invite, NewInvite, RegistrationMode,
+};
+use crate::model::user::User;
+use crate::repository::invite::InviteRepository;
+use crate::token::{generate_token, hash_token};
+use crate::validation::Validator;
+use chrono::{Duration, Utc};
+
+/// Upper bound for invites minted by admins.
+const MAX_PRIVILEGED_USES: i32 = 10_000;
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+/// Decides who may sign up and manages the invite codes that let people in when registration
+/// isn't open. Admins (anyone with [`crate::model::role::Permission::ManageRegistrations`])
+/// mint invites without the limits that apply to regular users.
+pub struct RegistrationService {
+    invite_repository: InviteRepository,
+    mode: RegistrationMode,
+    invites_per_user: i64,
+    invite_max_uses: i32,
+    invite_ttl_days: i64,
+}
+
+impl RegistrationService {
+    pub fn new(invite_repository: InviteRepository) -> Self {
+        Self {
+            invite_repository,
+            mode: env_or("REGISTRATION_MODE", RegistrationMode::Open),
+            invites_per_user: env_or("INVITES_PER_USER", 5),
+            invite_max_uses: env_or("INVITE_MAX_USES", 5),
+            invite_ttl_days: env_or("INVITE_TTL_DAYS", 7),
+        }
+    }
+
+    pub fn mode(&self) -> RegistrationMode {
+        self.mode
+    }
+
+    /// Checks whether a sign-up may go ahead. The invite, if one is needed or given, is only
+    /// redeemed when the account is created.
+    pub fn admit(&self, invite: Option<&str>) -> AppResult<Admission> {
+        let invite_hash = invite
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(hash_token);
+
+        match (self.mode, invite_hash) {
+            (RegistrationMode::Open, _) => Ok(Admission::default()),
+            (RegistrationMode::Closed, _) => {
+                Err(ForbiddenError("Registration is closed".to_string()))
+            }
+            (RegistrationMode::Invite, None) => Err(ValidationError(vec![FieldError::new(
+                "invite",
+                "An invite code is required to sign up",
+            )])),
+            (RegistrationMode::Approval, None) => Ok(Admission {
+                invite_hash: None,
+                approval_pending: true,
+            }),
+            (RegistrationMode::Invite | RegistrationMode::Approval, Some(invite_hash)) => {
+                Ok(Admission {
+                    invite_hash: Some(invite_hash),
+                    approval_pending: false,
+                })
+            }
+        }
+    }
+
+    pub async fn get_invites(&self, username: String) -> AppResult<Vec<Invite>> {
+        self.invite_repository.get_invites_of_user(username).await
+    }
+
+    pub async fn get_all_invites(&self, page: u32) -> AppResult<Vec<Invite>> {
+        self.invite_repository.get_all_invites(page).await
+    }
+
+    pub async fn create_invite(
+        &self,
+        user: &User,
+        privileged: bool,
+        form: CreateInviteForm,
+    ) -> AppResult<CreatedInviteDTO> {
+        let now = Utc::now().naive_utc();
+        let max_uses = form.max_uses.unwrap_or(1);
+        let (uses_limit, expires_in_days) = if privileged {
+            (MAX_PRIVILEGED_USES, form.expires_in_days)
+        } else {
+            (
+                self.invite_max_uses,
+                Some(form.expires_in_days.unwrap_or(self.invite_ttl_days)),
+            )
+        };
+
+        let mut validator = Validator::new();
+        validator.check(
+            "maxUses",
+            (1..=uses_limit).contains(&max_uses),
+            format!("Must be between 1 and {uses_limit}"),
+        );
+        if let Some(days) = expires_in_days {
+            let days_limit = if privileged {
+                MAX_EXPIRY_DAYS
+            } else {
+                self.invite_ttl_days
+            };
+            validator.check(
+                "expiresInDays",
+                (1..=days_limit).contains(&days),
+                format!("Must be between 1 and {days_limit} days"),
+            );
+        }
+        validator.finish()?;
+
+        if !privileged
+            && self
+                .invite_repository
+                .count_usable_invites(user.username.clone(), now)
+                .await?
+                >= self.invites_per_user
+        {
+            return Err(ForbiddenError(format!(
+                "You can have at most {} unused invites",
+                self.invites_per_user
+            )));
+        }
+
+        let code = generate_token();
+        let invite = self
+            .invite_repository
+            .add_invite(NewInvite {
+                created_by: user.username.clone(),
+                code_hash: hash_token(&code),
+                max_uses,
+                created_at: now,
+                expires_at: expires_in_days.map(|days| now + Duration::days(days)),
+            })
+            .await?;
+
+        Ok(CreatedInviteDTO { code, invite })
+    }
+
+    /// Admins may revoke anyone's invite, everybody else only their own.
+    pub async fn delete_invite(
+        &self,
+        user: &User,
+        privileged: bool,
+        invite_id: i32,
+    ) -> AppResult<()> {
+        let owner = (!privileged).then(|| user.username.clone());
+        if !self
+            .invite_repository
+            .delete_invite(invite_id, owner)
+            .await?
+        {
+            return Err(NotFoundError("Could not find invite".to_string()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use crate::error::AppError::{ConflictError, ForbiddenError, NotFoundError};
+use crate::error::AppResult;
+use crate::mailer::{Mail, Mailer};
+use crate::model::post::Post;
+use crate::model::report::{
+    NewReport, Report, ReportAction, ReportForm, ReportQueueEntry, ReportTarget,
+};
+use crate::model::user::User;
+use crate::repository::report::ReportRepository;
+use crate::repository::user::UserRepository;
+use crate::validation::Validator;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+const MAX_DETAILS_LENGTH: usize = 1000;
+
+/// Lets users flag posts and accounts and feeds the moderation queue, where moderators
+/// resolve all open reports about a target at once.
+pub struct ReportService {
+    report_repository: ReportRepository,
+    user_repository: UserRepository,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl ReportService {
+    pub fn new(
+        report_repository: ReportRepository,
+        user_repository: UserRepository,
+        mailer: Arc<dyn Mailer>,
+    ) -> Self {
+        Self {
+            report_repository,
+            user_repository,
+            mailer,
+        }
+    }
+
+    /// `post` must be one the reporter can see.
+    pub async fn report_post(
+        &self,
+        reporter: &User,
+        post: &Post,
+        form: ReportForm,
+    ) -> AppResult<()> {
+        if post.username.as_ref() == Some(&reporter.username) {
+            return Err(ForbiddenError("You can't report your own post".to_string()));
+        }
+
+        self.add_report(reporter, ReportTarget::Post(post.id), form)
+            .await
+    }
+
+    pub async fn report_user(
+        &self,
+        reporter: &User,
+        username: String,
+        form: ReportForm,
+    ) -> AppResult<()> {
+        if username == reporter.username {
+            return Err(ForbiddenError("You can't report yourself".to_string()));
+        }
+
+        let reported = self
+            .user_repository
+            .get_user_by_username(username.clone())
+            .await?
+            .filter(|user| user.deletion_scheduled_at.is_none() && !user.approval_pending);
+        if reported.is_none() {
+            return Err(NotFoundError("Could not find user".to_string()));
+        }
+
+        self.add_report(reporter, ReportTarget::User(username), form)
+            .await
+    }
+
+    /// Targets with open reports, the most reported first, along with those reports.
+    pub async fn get_queue(&self, page: u32) -> AppResult<Vec<ReportQueueEntry>> {
+        let summaries = self.report_repository.get_open_summaries(page).await?;
+
+        let post_ids: Vec<i32> = summaries
+            .iter()
+            .filter_map(|summary| summary.post_id)
+            .collect();
+        let usernames: Vec<String> = summaries
+            .iter()
+            .filter_map(|summary| summary.reported_user.clone())
+            .collect();
+
+        let mut reports: HashMap<ReportTarget, Vec<Report>> = HashMap::new();
+        for report in self
+            .report_repository
+            .get_open_reports(post_ids.clone(), usernames)
+            .await?
+        {
+            reports.entry(report.target()).or_default().push(report);
+        }
+        let posts: HashMap<i32, (String, Option<String>)> = self
+            .report_repository
+            .get_post_summaries(post_ids)
+            .await?
+            .into_iter()
+            .map(|(id, title, author)| (id, (title, author)))
+            .collect();
+
+        let queue = summaries
+            .into_iter()
+            .map(|summary| {
+                let post_id = summary.post_id;
+                let target = ReportTarget::from_columns(post_id, summary.reported_user.clone());
+                let reports = reports.remove(&target).unwrap_or_default();
+
+                let mut reasons = BTreeMap::new();
+                for report in &reports {
+                    *reasons.entry(report.reason.clone()).or_insert(0) += 1;
+                }
+                let (post_title, username) = match post_id.and_then(|id| posts.get(&id)) {
+                    Some((title, author)) => (Some(title.clone()), author.clone()),
+                    None => (None, summary.reported_user),
+                };
+
+                ReportQueueEntry {
+                    post_id,
+                    post_title,
+                    username,
+                    report_count: summary.report_count,
+                    reasons,
+                    first_reported_at: summary.first_reported_at.unwrap_or_default(),
+                    last_reported_at: summary.last_reported_at.unwrap_or_default(),
+                    reports,
+                }
+            })
+            .collect();
+
+        Ok(queue)
+    }
+
+    pub async fn ensure_open_reports(&self, target: &ReportTarget) -> AppResult<()> {
+        if self
+            .report_repository
+            .count_open_reports(target.clone())
+            .await?
+            == 0
+        {
+            return Err(NotFoundError(format!(
+                "There are no open reports about {}",
+                target.describe()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Closes the open reports about `target`. Returns how many there were.
+    pub async fn resolve(
+        &self,
+        moderator: &User,
+        target: ReportTarget,
+        action: ReportAction,
+    ) -> AppResult<usize> {
+        self.report_repository
+            .resolve_reports(
+                target,
+                moderator.username.clone(),
+                action.as_str(),
+                Utc::now().naive_utc(),
+            )
+            .await
+    }
+
+    /// Mails `username` a warning if they have a verified address. The warning also shows up in
+    /// their account activity through the audit log, which the caller records.
+    pub async fn warn_user(&self, username: String, reason: Option<&str>) -> AppResult<()> {
+        let Some(user) = self.user_repository.get_user_by_username(username).await? else {
+            return Ok(());
+        };
+        let Some(email) = user.email.filter(|_| user.email_verified) else {
+            return Ok(());
+        };
+
+        let reason = match reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+            Some(reason) => format!("The moderators left this note:\n{reason}\n\n"),
+            None => String::new(),
+        };
+        self.mailer
+            .send(Mail {
+                to: email,
+                subject: "A warning about your RustyPosts account".to_string(),
+                body: format!(
+                    "Hi {},\n\n\
+                     Other users reported content of yours and the moderators found that it \
+                     breaks the rules of RustyPosts.\n\n\
+                     {reason}\
+                     Repeated violations can get your account suspended.",
+                    user.username
+                ),
+            })
+            .await
+    }
+
+    async fn add_report(
+        &self,
+        reporter: &User,
+        target: ReportTarget,
+        form: ReportForm,
+    ) -> AppResult<()> {
+        let details = form
+            .details
+            .map(|details| details.trim().to_string())
+            .filter(|details| !details.is_empty());
+
+        let mut validator = Validator::new();
+        if let Some(details) = &details {
+            validator.check(
+                "details",
+                details.chars().count() <= MAX_DETAILS_LENGTH,
+                format!("Must be at most {MAX_DETAILS_LENGTH} characters"),
+            );
+        }
+        validator.finish()?;
+
+        let kind = target.kind();
+        let (post_id, reported_user) = match target {
+            ReportTarget::Post(post_id) => (Some(post_id), None),
+            ReportTarget::User(username) => (None, Some(username)),
+        };
+        let inserted = self
+            .report_repository
+            .add_report(NewReport {
+                reporter: reporter.username.clone(),
+                post_id,
+                reported_user,
+                reason: form.reason.as_str().to_string(),
+                details,
+                created_at: Utc::now().naive_utc(),
+            })
+            .await?;
+
+        if !inserted {
+            return Err(ConflictError(format!("You already reported this {kind}")));
+        }
+
+        Ok(())
+    }
+}
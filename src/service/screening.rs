@@ -0,0 +1,252 @@
+use crate::config::env_or;
+use crate::error::AppError::{NotFoundError, ValidationError};
+use crate::error::{AppResult, FieldError};
+use crate::model::role::Permission;
+use crate::model::user::User;
+use crate::model::word_filter::{CreateWordFilterForm, FilterAction, NewWordFilter, WordFilter};
+use crate::repository::post::PostRepository;
+use crate::repository::word_filter::WordFilterRepository;
+use crate::token::hash_token;
+use crate::validation::Validator;
+use chrono::{Duration, NaiveDateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+const MAX_PATTERN_LENGTH: usize = 200;
+/// Keeps a single pathological expression from eating the memory of the server.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+struct CompiledFilter {
+    regex: Regex,
+    action: FilterAction,
+}
+
+/// The verdict on a post that wasn't blocked outright.
+pub struct Screening {
+    pub content_hash: String,
+    /// Why the post is held for review, `None` if it can be published.
+    pub held_reason: Option<String>,
+}
+
+/// Checks new posts before they are published. Posts matching a blocking word filter are
+/// rejected. Posts matching a flagging filter, carrying many links, repeating recent posts or
+/// coming in quick succession from a new account are held until a moderator releases them.
+/// Moderators are never held.
+pub struct ScreeningService {
+    word_filter_repository: WordFilterRepository,
+    post_repository: PostRepository,
+    /// Compiled on first use and dropped whenever the filters change.
+    filters: RwLock<Option<Arc<Vec<CompiledFilter>>>>,
+    /// Bumped whenever the filters change, so a list loaded before the change isn't cached.
+    filters_generation: AtomicU64,
+    max_links: usize,
+    repeat_window: Duration,
+    repeat_accounts: usize,
+    new_account_age: Duration,
+    new_account_max_posts: i64,
+}
+
+impl ScreeningService {
+    pub fn new(
+        word_filter_repository: WordFilterRepository,
+        post_repository: PostRepository,
+    ) -> Self {
+        Self {
+            word_filter_repository,
+            post_repository,
+            filters: RwLock::new(None),
+            filters_generation: AtomicU64::new(0),
+            max_links: env_or("SPAM_MAX_LINKS", 5),
+            repeat_window: Duration::hours(env_or("SPAM_REPEAT_WINDOW_HOURS", 24)),
+            repeat_accounts: env_or("SPAM_REPEAT_ACCOUNTS", 3),
+            new_account_age: Duration::hours(env_or("SPAM_NEW_ACCOUNT_HOURS", 24)),
+            new_account_max_posts: env_or("SPAM_NEW_ACCOUNT_MAX_POSTS", 3),
+        }
+    }
+
+    pub async fn get_filters(&self) -> AppResult<Vec<WordFilter>> {
+        self.word_filter_repository.get_filters().await
+    }
+
+    pub async fn add_filter(
+        &self,
+        creator: &User,
+        form: CreateWordFilterForm,
+    ) -> AppResult<WordFilter> {
+        let pattern = form.pattern.trim().to_string();
+
+        let mut validator = Validator::new();
+        validator.check(
+            "pattern",
+            !pattern.is_empty() && pattern.chars().count() <= MAX_PATTERN_LENGTH,
+            format!("Must be between 1 and {MAX_PATTERN_LENGTH} characters"),
+        );
+        validator.finish()?;
+        if let Err(err) = compile(&pattern, form.is_regex) {
+            return Err(ValidationError(vec![FieldError::new(
+                "pattern",
+                format!("Invalid regular expression: {err}"),
+            )]));
+        }
+
+        let filter = self
+            .word_filter_repository
+            .add_filter(NewWordFilter {
+                pattern,
+                is_regex: form.is_regex,
+                action: form.action.as_str().to_string(),
+                created_by: creator.username.clone(),
+                created_at: Utc::now().naive_utc(),
+            })
+            .await?;
+        self.invalidate_filters();
+
+        Ok(filter)
+    }
+
+    pub async fn delete_filter(&self, filter_id: i32) -> AppResult<()> {
+        if !self.word_filter_repository.delete_filter(filter_id).await? {
+            return Err(NotFoundError("Could not find word filter".to_string()));
+        }
+        self.invalidate_filters();
+
+        Ok(())
+    }
+
+    /// Rejects posts matching a blocking filter with a validation error.
+    pub async fn screen(&self, author: &User, title: &str, body: &str) -> AppResult<Screening> {
+        let text = format!("{title}\n{body}");
+        let filters = self.compiled_filters().await?;
+
+        let matches = |action| {
+            filters
+                .iter()
+                .any(|filter| filter.action == action && filter.regex.is_match(&text))
+        };
+        if matches(FilterAction::Block) {
+            return Err(ValidationError(vec![FieldError::new(
+                "body",
+                "The post contains a blocked term",
+            )]));
+        }
+
+        let normalized = body.split_whitespace().collect::<Vec<_>>().join(" ");
+        let content_hash = hash_token(&normalized.to_lowercase());
+
+        let held_reason = if author.role.grants(Permission::ModeratePosts) {
+            None
+        } else if matches(FilterAction::Flag) {
+            Some("Contains a flagged term".to_string())
+        } else {
+            self.heuristics(author, &text, &content_hash).await?
+        };
+
+        Ok(Screening {
+            content_hash,
+            held_reason,
+        })
+    }
+
+    async fn heuristics(
+        &self,
+        author: &User,
+        text: &str,
+        content_hash: &str,
+    ) -> AppResult<Option<String>> {
+        let now = Utc::now().naive_utc();
+
+        if link_pattern().find_iter(text).count() > self.max_links {
+            return Ok(Some("Contains too many links".to_string()));
+        }
+
+        let authors = self
+            .post_repository
+            .get_authors_of_content(content_hash.to_string(), now - self.repeat_window)
+            .await?;
+        if authors.contains(&Some(author.username.clone())) {
+            return Ok(Some("Repeats a recent post".to_string()));
+        }
+        if authors.len() >= self.repeat_accounts {
+            return Ok(Some("Repeats recent posts of other accounts".to_string()));
+        }
+
+        let joined = NaiveDateTime::from(author.joined);
+        if now - joined < self.new_account_age
+            && self
+                .post_repository
+                .count_posts_since(author.username.clone(), now - self.new_account_age)
+                .await?
+                >= self.new_account_max_posts
+        {
+            return Ok(Some("New account posting a lot".to_string()));
+        }
+
+        Ok(None)
+    }
+
+    async fn compiled_filters(&self) -> AppResult<Arc<Vec<CompiledFilter>>> {
+        if let Some(filters) = self.filters.read().unwrap().as_ref() {
+            return Ok(filters.clone());
+        }
+
+        let generation = self.filters_generation.load(Ordering::SeqCst);
+        // Filters that no longer compile, e.g. after a regex crate upgrade, are skipped rather
+        // than blocking every post.
+        let filters = Arc::new(
+            self.word_filter_repository
+                .get_filters()
+                .await?
+                .into_iter()
+                .filter_map(|filter| {
+                    Some(CompiledFilter {
+                        regex: compile(&filter.pattern, filter.is_regex).ok()?,
+                        action: FilterAction::parse(&filter.action)?,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        );
+        let mut cached = self.filters.write().unwrap();
+        if self.filters_generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(filters.clone());
+        }
+
+        Ok(filters)
+    }
+
+    fn invalidate_filters(&self) {
+        let mut cached = self.filters.write().unwrap();
+        self.filters_generation.fetch_add(1, Ordering::SeqCst);
+        *cached = None;
+    }
+}
+
+/// Plain patterns only match whole words, so "ass" doesn't catch "class".
+fn compile(pattern: &str, is_regex: bool) -> Result<Regex, regex::Error> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let source = if is_regex {
+        pattern.to_string()
+    } else {
+        let start = if pattern.starts_with(is_word) {
+            r"\b"
+        } else {
+            ""
+        };
+        let end = if pattern.ends_with(is_word) {
+            r"\b"
+        } else {
+            ""
+        };
+        format!("{start}{}{end}", regex::escape(pattern))
+    };
+
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+fn link_pattern() -> &'static Regex {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    LINK.get_or_init(|| Regex::new(r"(?i)\b(?:https?://|www\.)").unwrap())
+}
@@ -0,0 +1,189 @@
+use crate::config::env_or;
+use crate::error::AppError::TooManyRequestsError;
+use crate::error::AppResult;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Entries are swept once the table grows past this many keys.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct AttemptPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    window: Duration,
+}
+
+struct AttemptRecord {
+    attempts: u32,
+    last_attempt: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Counts attempts per key and locks the key out for an exponentially growing period once
+/// the allowed attempts are used up. Records are forgotten after a quiet `window`.
+struct AttemptThrottle {
+    policy: AttemptPolicy,
+    records: Mutex<HashMap<String, AttemptRecord>>,
+}
+
+impl AttemptThrottle {
+    fn new(policy: AttemptPolicy) -> Self {
+        Self {
+            policy,
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the remaining lockout for `key`, if any.
+    fn locked_for(&self, key: &str, now: Instant) -> Option<Duration> {
+        let records = self.records.lock().unwrap();
+        records
+            .get(key)
+            .and_then(|record| record.locked_until)
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    fn record(&self, key: &str, now: Instant) {
+        let mut records = self.records.lock().unwrap();
+
+        if records.len() > PRUNE_THRESHOLD {
+            let window = self.policy.window;
+            records.retain(|_, record| now.duration_since(record.last_attempt) < window);
+        }
+
+        let record = records.entry(key.to_string()).or_insert(AttemptRecord {
+            attempts: 0,
+            last_attempt: now,
+            locked_until: None,
+        });
+        if now.duration_since(record.last_attempt) >= self.policy.window {
+            record.attempts = 0;
+            record.locked_until = None;
+        }
+
+        record.attempts += 1;
+        record.last_attempt = now;
+
+        if record.attempts >= self.policy.max_attempts {
+            let exponent = (record.attempts - self.policy.max_attempts).min(20);
+            let delay = self
+                .policy
+                .base_delay
+                .saturating_mul(1 << exponent)
+                .min(self.policy.max_delay);
+            record.locked_until = Some(now + delay);
+        }
+    }
+
+    fn reset(&self, key: &str) {
+        self.records.lock().unwrap().remove(key);
+    }
+}
+
+/// Brute-force protection for the authentication endpoints. Failed logins are tracked per
+/// client IP and per username, wrong second factors per username and sign-ups per client IP.
+pub struct ThrottleService {
+    login_by_ip: AttemptThrottle,
+    login_by_username: AttemptThrottle,
+    second_factor_by_username: AttemptThrottle,
+    signup_by_ip: AttemptThrottle,
+}
+
+impl ThrottleService {
+    pub fn new() -> Self {
+        let policy = |max_attempts_key: &str, max_attempts: u32| AttemptPolicy {
+            max_attempts: env_or(max_attempts_key, max_attempts),
+            base_delay: Duration::from_secs(env_or("LOCKOUT_BASE_SECONDS", 2)),
+            max_delay: Duration::from_secs(env_or("LOCKOUT_MAX_SECONDS", 900)),
+            window: Duration::from_secs(env_or("ATTEMPT_WINDOW_SECONDS", 3600)),
+        };
+
+        Self {
+            login_by_ip: AttemptThrottle::new(policy("LOGIN_MAX_ATTEMPTS_PER_IP", 20)),
+            login_by_username: AttemptThrottle::new(policy("LOGIN_MAX_ATTEMPTS_PER_USERNAME", 5)),
+            second_factor_by_username: AttemptThrottle::new(policy(
+                "TWO_FACTOR_MAX_ATTEMPTS_PER_USERNAME",
+                5,
+            )),
+            signup_by_ip: AttemptThrottle::new(policy("SIGNUP_MAX_ATTEMPTS_PER_IP", 5)),
+        }
+    }
+
+    /// Rejects the attempt while the client or the targeted account is locked out. This runs
+    /// before the password is checked, so a locked account can't be probed either.
+    pub fn check_login(&self, ip: IpAddr, username: Option<&str>) -> AppResult<()> {
+        let now = Instant::now();
+        let locked = [
+            self.login_by_ip.locked_for(&ip.to_string(), now),
+            username.and_then(|name| self.login_by_username.locked_for(name, now)),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+
+        match locked {
+            Some(remaining) => Err(TooManyRequestsError(retry_after(remaining))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn login_failed(&self, ip: IpAddr, username: Option<&str>) {
+        let now = Instant::now();
+        self.login_by_ip.record(&ip.to_string(), now);
+        if let Some(username) = username {
+            self.login_by_username.record(username, now);
+        }
+    }
+
+    /// Clears the account's failures. The IP record is kept, otherwise logging into one's own
+    /// account would wipe the failures collected while guessing other accounts.
+    pub fn login_succeeded(&self, username: &str) {
+        self.login_by_username.reset(username);
+    }
+
+    /// Rejects second factor codes for `username` while it is locked out. The count belongs to
+    /// the account rather than to a pending login, so logging in with the password again to
+    /// get a fresh pending login doesn't buy more guesses.
+    pub fn check_second_factor(&self, username: &str) -> AppResult<()> {
+        match self
+            .second_factor_by_username
+            .locked_for(username, Instant::now())
+        {
+            Some(remaining) => Err(TooManyRequestsError(retry_after(remaining))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn second_factor_failed(&self, username: &str) {
+        self.second_factor_by_username
+            .record(username, Instant::now());
+    }
+
+    /// Unlike a correct password, only a correct second factor clears the failures.
+    pub fn second_factor_succeeded(&self, username: &str) {
+        self.second_factor_by_username.reset(username);
+    }
+
+    pub fn check_signup(&self, ip: IpAddr) -> AppResult<()> {
+        match self
+            .signup_by_ip
+            .locked_for(&ip.to_string(), Instant::now())
+        {
+            Some(remaining) => Err(TooManyRequestsError(retry_after(remaining))),
+            None => Ok(()),
+        }
+    }
+
+    /// Every sign-up counts, successful or not, to slow down mass account creation.
+    pub fn signup_attempted(&self, ip: IpAddr) {
+        self.signup_by_ip.record(&ip.to_string(), Instant::now());
+    }
+}
+
+fn retry_after(remaining: Duration) -> u64 {
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
@@ -0,0 +1,438 @@
+use crate::config::env_or;
+use crate::error::AppError::{InternalError, LoginError, ValidationError};
+use crate::error::AppError;
+use crate::error::{AppResult, FieldError};
+use crate::hashing::PasswordHasher;
+use crate::model::two_factor::{
+    NewRecoveryCode, PendingLogin, PendingLoginDTO, RecoveryCodesDTO, TwoFactorEnrollmentDTO,
+};
+use crate::model::user::User;
+use crate::repository::two_factor::TwoFactorRepository;
+use crate::repository::user::UserRepository;
+use crate::service::throttle::ThrottleService;
+use crate::token::{generate_token, hash_token};
+use chrono::{Duration, Utc};
+use rand::Rng;
+use std::sync::Arc;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const MAX_PENDING_LOGIN_ATTEMPTS: i32 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+pub struct TwoFactorService {
+    user_repository: UserRepository,
+    two_factor_repository: TwoFactorRepository,
+    password_hasher: Arc<PasswordHasher>,
+    throttle_service: Arc<ThrottleService>,
+    issuer: String,
+    pending_login_ttl: Duration,
+}
+
+impl TwoFactorService {
+    pub fn new(
+        user_repository: UserRepository,
+        two_factor_repository: TwoFactorRepository,
+        password_hasher: Arc<PasswordHasher>,
+        throttle_service: Arc<ThrottleService>,
+    ) -> Self {
+        Self {
+            user_repository,
+            two_factor_repository,
+            password_hasher,
+            throttle_service,
+            issuer: env_or("TOTP_ISSUER", "RustyPosts".to_string()),
+            pending_login_ttl: Duration::seconds(env_or("TWO_FACTOR_PENDING_TTL_SECONDS", 300)),
+        }
+    }
+
+    /// Generates a new secret for `user`. It only takes effect once confirmed with a valid code.
+    pub async fn begin_enrollment(&self, user: &User) -> AppResult<TwoFactorEnrollmentDTO> {
+        if user.totp_enabled {
+            return Err(already_enabled());
+        }
+
+        let mut secret = [0u8; 20];
+        rand::rng().fill(&mut secret);
+        let encoded = Secret::Raw(secret.to_vec()).to_encoded().to_string();
+
+        self.user_repository
+            .store_totp_secret(user.username.clone(), encoded.clone())
+            .await?;
+
+        let totp = self.totp(&encoded, &user.username)?;
+        let qr_png = totp.get_qr_base64().map_err(InternalError)?;
+
+        Ok(TwoFactorEnrollmentDTO {
+            secret: encoded,
+            otpauth_uri: totp.get_url(),
+            qr_png,
+        })
+    }
+
+    /// Activates two-factor authentication and hands out the initial recovery codes.
+    pub async fn confirm_enrollment(&self, user: &User, code: String) -> AppResult<RecoveryCodesDTO> {
+        if user.totp_enabled {
+            return Err(already_enabled());
+        }
+        if user.totp_secret.is_none() {
+            return Err(ValidationError(vec![FieldError::new(
+                "code",
+                "Start the enrollment before confirming it",
+            )]));
+        }
+        if !self.verify_totp(user, &code).await? {
+            return Err(invalid_code());
+        }
+
+        self.user_repository
+            .enable_totp(user.username.clone())
+            .await?;
+        self.issue_recovery_codes(&user.username).await
+    }
+
+    pub async fn disable(&self, user: &User, password: String, code: String) -> AppResult<()> {
+        if !user.totp_enabled {
+            return Err(not_enabled());
+        }
+        if !self.password_hasher.verify(&password, &user.password).await? {
+            return Err(ValidationError(vec![FieldError::new(
+                "password",
+                "Password is incorrect",
+            )]));
+        }
+        if !self.verify_second_factor(user, &code).await? {
+            return Err(invalid_code());
+        }
+
+        self.user_repository
+            .disable_totp(user.username.clone())
+            .await?;
+        self.two_factor_repository
+            .delete_recovery_codes(user.username.clone())
+            .await
+    }
+
+    /// Replaces every recovery code, used or not, with a fresh set.
+    pub async fn regenerate_recovery_codes(
+        &self,
+        user: &User,
+        code: String,
+    ) -> AppResult<RecoveryCodesDTO> {
+        if !user.totp_enabled {
+            return Err(not_enabled());
+        }
+        if !self.verify_totp(user, &code).await? {
+            return Err(invalid_code());
+        }
+
+        self.issue_recovery_codes(&user.username).await
+    }
+
+    /// Called after the password check succeeded for an account with two-factor enabled.
+    /// The returned token stands in for the session until the second factor is provided.
+    pub async fn begin_login(&self, username: String) -> AppResult<PendingLoginDTO> {
+        let token = generate_token();
+
+        self.two_factor_repository
+            .create_pending_login(PendingLogin {
+                token_hash: hash_token(&token),
+                username,
+                expires_at: (Utc::now() + self.pending_login_ttl).naive_utc(),
+                attempts: 0,
+            })
+            .await?;
+
+        Ok(PendingLoginDTO {
+            two_factor_required: true,
+            pending_token: token,
+            expires_in: self.pending_login_ttl.num_seconds(),
+        })
+    }
+
+    /// Checks the second factor of a pending login and returns the username it belongs to.
+    /// A pending login is discarded after too many wrong codes, and the account is locked out
+    /// of further attempts across pending logins by the [`ThrottleService`].
+    pub async fn complete_login(&self, pending_token: String, code: String) -> AppResult<String> {
+        let hash = hash_token(&pending_token);
+
+        let pending = self
+            .two_factor_repository
+            .find_pending_login(hash.clone(), Utc::now().naive_utc())
+            .await?
+            .ok_or(LoginError)?;
+
+        let user = self
+            .user_repository
+            .get_user_by_username(pending.username)
+            .await?
+            .ok_or(LoginError)?;
+        self.throttle_service.check_second_factor(&user.username)?;
+
+        if !self.verify_second_factor(&user, &code).await? {
+            self.throttle_service.second_factor_failed(&user.username);
+            let attempts = self
+                .two_factor_repository
+                .record_failed_attempt(hash.clone())
+                .await?;
+            if attempts >= MAX_PENDING_LOGIN_ATTEMPTS {
+                self.two_factor_repository
+                    .delete_pending_login(hash)
+                    .await?;
+            }
+            return Err(LoginError);
+        }
+
+        self.two_factor_repository
+            .delete_pending_login(hash)
+            .await?;
+        self.throttle_service.second_factor_succeeded(&user.username);
+        Ok(user.username)
+    }
+
+    fn totp(&self, secret: &str, username: &str) -> AppResult<TOTP> {
+        let bytes = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| InternalError(e.to_string()))?;
+
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            1,
+            TOTP_STEP_SECONDS,
+            bytes,
+            Some(self.issuer.clone()),
+            username.to_string(),
+        )
+        .map_err(|e| InternalError(e.to_string()))
+    }
+
+    /// Accepts codes from the previous, current and next time step, each at most once.
+    async fn verify_totp(&self, user: &User, code: &str) -> AppResult<bool> {
+        let Some(secret) = &user.totp_secret else {
+            return Ok(false);
+        };
+        let totp = self.totp(secret, &user.username)?;
+
+        let code = code.trim();
+        let current_step = Utc::now().timestamp() / TOTP_STEP_SECONDS as i64;
+
+        for step in current_step - 1..=current_step + 1 {
+            if totp.generate(step as u64 * TOTP_STEP_SECONDS) == code {
+                return self
+                    .user_repository
+                    .record_totp_step(user.username.clone(), step)
+                    .await;
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn verify_second_factor(&self, user: &User, code: &str) -> AppResult<bool> {
+        let code = code.trim();
+        if code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+            return self.verify_totp(user, code).await;
+        }
+
+        self.two_factor_repository
+            .use_recovery_code(
+                user.username.clone(),
+                hash_token(&normalize_recovery_code(code)),
+                Utc::now().naive_utc(),
+            )
+            .await
+    }
+
+    async fn issue_recovery_codes(&self, username: &str) -> AppResult<RecoveryCodesDTO> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+
+        let hashed = codes
+            .iter()
+            .map(|code| NewRecoveryCode {
+                username: username.to_string(),
+                code_hash: hash_token(&normalize_recovery_code(code)),
+            })
+            .collect();
+
+        self.two_factor_repository
+            .replace_recovery_codes(username.to_string(), hashed)
+            .await?;
+
+        Ok(RecoveryCodesDTO {
+            recovery_codes: codes,
+        })
+    }
+}
+
+/// Produces codes like `k7pq2-xm4ta`, avoiding characters that are easily confused.
+fn generate_recovery_code() -> String {
+    let mut rng = rand::rng();
+    let mut chars: Vec<char> = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    chars.insert(5, '-');
+    chars.into_iter().collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn invalid_code() -> AppError {
+    ValidationError(vec![FieldError::new("code", "Code is invalid")])
+}
+
+fn already_enabled() -> AppError {
+    ValidationError(vec![FieldError::new(
+        "code",
+        "Two-factor authentication is already enabled",
+    )])
+}
+
+fn not_enabled() -> AppError {
+    ValidationError(vec![FieldError::new(
+        "code",
+        "Two-factor authentication is not enabled",
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{test_pool, test_user};
+
+    fn service(pool: &deadpool_diesel::postgres::Pool) -> TwoFactorService {
+        TwoFactorService::new(
+            UserRepository::new(pool.clone()),
+            TwoFactorRepository::new(pool.clone()),
+            Arc::new(PasswordHasher::from_env()),
+            Arc::new(ThrottleService::new()),
+        )
+    }
+
+    async fn user(service: &TwoFactorService, username: &str) -> User {
+        service
+            .user_repository
+            .get_user_by_username(username.to_string())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// The code of the time step `offset` steps away from now.
+    fn code(service: &TwoFactorService, user: &User, offset: i64) -> String {
+        let totp = service
+            .totp(user.totp_secret.as_ref().unwrap(), &user.username)
+            .unwrap();
+        let step = Utc::now().timestamp() / TOTP_STEP_SECONDS as i64 + offset;
+        totp.generate(step as u64 * TOTP_STEP_SECONDS)
+    }
+
+    /// Enables two-factor authentication with the current code and returns the recovery codes.
+    async fn enroll(service: &TwoFactorService, username: &str) -> Vec<String> {
+        service
+            .begin_enrollment(&user(service, username).await)
+            .await
+            .unwrap();
+        let enrolling = user(service, username).await;
+        service
+            .confirm_enrollment(&enrolling, code(service, &enrolling, 0))
+            .await
+            .unwrap()
+            .recovery_codes
+    }
+
+    async fn log_in(service: &TwoFactorService, username: &str, code: String) -> AppResult<String> {
+        let pending = service.begin_login(username.to_string()).await.unwrap();
+        service.complete_login(pending.pending_token, code).await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn totp_codes_are_accepted_once_per_step() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "totp").await;
+        enroll(&service, &username).await;
+        let enrolled = user(&service, &username).await;
+
+        // The enrollment used up the current step, and earlier steps can't follow it.
+        let current = code(&service, &enrolled, 0);
+        assert!(matches!(
+            log_in(&service, &username, current).await,
+            Err(LoginError)
+        ));
+        let previous = code(&service, &enrolled, -1);
+        assert!(matches!(
+            log_in(&service, &username, previous).await,
+            Err(LoginError)
+        ));
+
+        let next = code(&service, &enrolled, 1);
+        assert_eq!(
+            log_in(&service, &username, next.clone()).await.unwrap(),
+            username
+        );
+        assert!(matches!(
+            log_in(&service, &username, next).await,
+            Err(LoginError)
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn recovery_codes_work_once() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "recovery").await;
+        let codes = enroll(&service, &username).await;
+
+        // Case and separators don't matter.
+        let code = codes[0].to_uppercase().replace('-', " ");
+        assert_eq!(
+            log_in(&service, &username, code.clone()).await.unwrap(),
+            username
+        );
+        assert!(matches!(
+            log_in(&service, &username, code).await,
+            Err(LoginError)
+        ));
+        assert!(log_in(&service, &username, codes[1].clone()).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn pending_logins_are_dropped_after_too_many_wrong_codes() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "pending").await;
+        enroll(&service, &username).await;
+        let enrolled = user(&service, &username).await;
+
+        let pending = service.begin_login(username.clone()).await.unwrap();
+        for _ in 0..MAX_PENDING_LOGIN_ATTEMPTS {
+            let result = service
+                .complete_login(pending.pending_token.clone(), "wrong".to_string())
+                .await;
+            assert!(matches!(result, Err(LoginError)));
+        }
+
+        // The pending login is gone, even the right code doesn't complete it.
+        let result = service
+            .complete_login(pending.pending_token, code(&service, &enrolled, 1))
+            .await;
+        assert!(matches!(result, Err(LoginError)));
+
+        // A fresh password login doesn't buy more guesses either.
+        let result = log_in(&service, &username, code(&service, &enrolled, 1)).await;
+        assert!(matches!(result, Err(AppError::TooManyRequestsError(_))));
+    }
+}
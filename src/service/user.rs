@@ -1,25 +1,53 @@
-use crate::error::AppError::{InternalError, LoginError};
-use crate::error::AppResult;
+use crate::config::env_or;
+use crate::error::AppError::{ForbiddenError, LoginError, NotFoundError, ValidationError};
+use crate::error::{username_taken, AppResult, FieldError};
+use crate::hashing::PasswordHasher;
+use crate::model::invite::Admission;
+use crate::model::role::Role;
 use crate::model::session::Session;
-use crate::model::user::{UpdateUser, User};
+use crate::model::user::{
+    DeleteAccountForm, DirectoryUserDTO, PostDisposal, UpdateProfile, UpdateProfileForm,
+    UpdateUser, User, UserSort, UserSuggestionDTO,
+};
 use crate::repository::session::SessionRepository;
 use crate::repository::user::UserRepository;
-use bcrypt::DEFAULT_COST;
-use chrono::Utc;
+use crate::validation::{normalize_email, ValidationRules};
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::sync::Arc;
+
+/// How many users the autocomplete endpoint suggests at most.
+const SUGGESTION_LIMIT: i64 = 8;
+
+pub enum LoginOutcome {
+    Session(uuid::Uuid),
+    /// The password was correct but the account requires a second factor before a session is
+    /// issued.
+    TwoFactorRequired(String),
+}
 
 pub struct UserService {
     user_repository: UserRepository,
     session_repository: SessionRepository,
+    validation_rules: Arc<ValidationRules>,
+    password_hasher: Arc<PasswordHasher>,
+    deletion_grace_period: Duration,
 }
 
 impl UserService {
-    pub fn new(user_repository: UserRepository, session_repository: SessionRepository) -> Self {
+    pub fn new(
+        user_repository: UserRepository,
+        session_repository: SessionRepository,
+        validation_rules: Arc<ValidationRules>,
+        password_hasher: Arc<PasswordHasher>,
+    ) -> Self {
         Self {
             user_repository,
             session_repository,
+            validation_rules,
+            password_hasher,
+            deletion_grace_period: Duration::days(env_or("ACCOUNT_DELETION_GRACE_DAYS", 14)),
         }
     }
-    
 
     pub async fn get_user_by_username(&self, username: String) -> AppResult<Option<User>> {
         self.user_repository
@@ -27,62 +55,277 @@ impl UserService {
             .await
     }
 
-    pub async fn login(&self, username: String, password: String) -> AppResult<uuid::Uuid> {
+    pub async fn login(&self, username: String, password: String) -> AppResult<LoginOutcome> {
         let user = self
             .user_repository
             .get_user_by_username(username.clone())
             .await?;
 
-        if let Some(result) = user {
-            let verify = bcrypt::verify(password, result.password.as_str());
-            match verify {
-                Ok(boolean) => {
-                    if boolean {
-                        let session_id = uuid::Uuid::new_v4();
-
-                        let session: Session = Session {
-                            username,
-                            session_id: session_id.to_string(),
-                        };
-                        self.session_repository.add_session(session).await?;
-
-                        return Ok(session_id);
-                    }
-                    Err(LoginError)
+        // Unknown usernames are still checked against a real hash so that the response time
+        // doesn't reveal which accounts exist.
+        let hash = match &user {
+            Some(user) => user.password.clone(),
+            None => self.password_hasher.dummy_hash().await?,
+        };
+        let verified = self.password_hasher.verify(&password, &hash).await?;
+
+        match user {
+            Some(user) if verified => {
+                user.ensure_active(Utc::now().naive_utc())?;
+                if self.password_hasher.needs_rehash(&user.password) {
+                    self.user_repository
+                        .update_user(UpdateUser {
+                            username: username.clone(),
+                            password: Some(self.password_hasher.hash(&password).await?),
+                            avatar: None,
+                        })
+                        .await?;
                 }
-                Err(_) => Err(InternalError("Invalid password".to_string())),
+                if user.totp_enabled {
+                    return Ok(LoginOutcome::TwoFactorRequired(username));
+                }
+
+                let session_id = self.start_session(username).await?;
+                Ok(LoginOutcome::Session(session_id))
             }
-        } else {
-            Err(LoginError)
+            _ => Err(LoginError),
         }
     }
 
+    /// Opens a session for a user whose credentials have already been checked.
+    pub async fn start_session(&self, username: String) -> AppResult<uuid::Uuid> {
+        self.user_repository
+            .get_user_by_username(username.clone())
+            .await?
+            .ok_or(LoginError)?
+            .ensure_active(Utc::now().naive_utc())?;
+
+        let session_id = uuid::Uuid::new_v4();
+
+        let session: Session = Session {
+            username,
+            session_id: session_id.to_string(),
+            csrf_token: crate::token::generate_token(),
+            created_at: Utc::now().naive_utc(),
+        };
+        self.session_repository.add_session(session).await?;
+
+        Ok(session_id)
+    }
+
     pub async fn logout(&self, session_id: String) -> AppResult<()> {
         self.session_repository.delete_session(session_id).await?;
         Ok(())
     }
 
+    pub async fn get_session(&self, session_id: String) -> AppResult<Option<Session>> {
+        self.session_repository.get_session(session_id).await
+    }
+
     pub async fn get_user_by_session(&self, session_id: String) -> AppResult<Option<User>> {
-        self.session_repository
+        let user = self
+            .session_repository
             .get_user_by_session(session_id)
+            .await?;
+
+        Ok(user.filter(|user| !user.is_suspended(Utc::now().naive_utc())))
+    }
+
+    pub async fn create_user(
+        &self,
+        username: String,
+        password: String,
+        email: Option<String>,
+        admission: Admission,
+    ) -> AppResult<()> {
+        let email = email
+            .map(|address| normalize_email(&address))
+            .filter(|address| !address.is_empty());
+        self.validation_rules
+            .validate_sign_up(&username, &password, email.as_deref())?;
+
+        if !self
+            .user_repository
+            .is_username_available(username.clone(), self.username_reserved_since(), None)
+            .await?
+        {
+            return Err(username_taken());
+        }
+
+        let hashed_pass = self.password_hasher.hash(&password).await?;
+
+        let user = User {
+            username,
+            password: hashed_pass,
+            avatar: None,
+            joined: Utc::now().date_naive(),
+            email,
+            approval_pending: admission.approval_pending,
+            ..Default::default()
+        };
+        match admission.invite_hash {
+            None => self.user_repository.create_new_user(user).await?,
+            Some(invite_hash) => {
+                let created = self
+                    .user_repository
+                    .create_invited_user(user, invite_hash, Utc::now().naive_utc())
+                    .await?;
+                if !created {
+                    return Err(ValidationError(vec![FieldError::new(
+                        "invite",
+                        "This invite code is invalid, expired or used up",
+                    )]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames the account and returns the new name. The old name keeps redirecting to the
+    /// account and cannot be claimed by anyone else for a while.
+    pub async fn change_username(&self, user: &User, new_username: String) -> AppResult<String> {
+        let new_username = new_username.trim().to_string();
+        if new_username == user.username {
+            return Ok(new_username);
+        }
+        self.validation_rules.validate_username(&new_username)?;
+
+        if !self
+            .user_repository
+            .is_username_available(
+                new_username.clone(),
+                self.username_reserved_since(),
+                Some(user.username.clone()),
+            )
+            .await?
+        {
+            return Err(username_taken());
+        }
+
+        self.user_repository
+            .rename_user(
+                user.username.clone(),
+                new_username.clone(),
+                Utc::now().naive_utc(),
+            )
+            .await?;
+        Ok(new_username)
+    }
+
+    /// The current name of the account that used to be called `old_username`.
+    pub async fn find_renamed_user(&self, old_username: String) -> AppResult<Option<String>> {
+        self.user_repository.find_renamed_user(old_username).await
+    }
+
+    pub async fn search_directory(
+        &self,
+        query: Option<String>,
+        sort: UserSort,
+        page: u32,
+    ) -> AppResult<Vec<DirectoryUserDTO>> {
+        let query = query
+            .map(|query| query.trim().to_string())
+            .filter(|query| !query.is_empty());
+
+        let users = self
+            .user_repository
+            .search_directory(query, sort, page)
+            .await?;
+
+        Ok(users
+            .into_iter()
+            .map(|(user, total_posts)| DirectoryUserDTO { user, total_posts })
+            .collect())
+    }
+
+    pub async fn suggest_users(&self, prefix: String) -> AppResult<Vec<UserSuggestionDTO>> {
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.user_repository
+            .suggest_users(prefix.to_string(), SUGGESTION_LIMIT)
             .await
     }
 
-    pub async fn create_user(&self, username: String, password: String) -> AppResult<()> {
-        let hashed_pass = bcrypt::hash(password, DEFAULT_COST)?;
+    fn username_reserved_since(&self) -> NaiveDateTime {
+        Utc::now().naive_utc() - Duration::days(self.validation_rules.username_reservation_days)
+    }
+
+    /// Schedules the account for deletion once the grace period is over and logs it out
+    /// everywhere. Logging in again and restoring the account cancels the deletion.
+    pub async fn request_deletion(
+        &self,
+        user: &User,
+        form: DeleteAccountForm,
+    ) -> AppResult<NaiveDateTime> {
+        if !self
+            .password_hasher
+            .verify(&form.password, &user.password)
+            .await?
+        {
+            return Err(ValidationError(vec![FieldError::new(
+                "password",
+                "Password is incorrect",
+            )]));
+        }
 
+        let at = Utc::now().naive_utc() + self.deletion_grace_period;
         self.user_repository
-            .create_new_user(User {
-                username,
-                password: hashed_pass,
-                avatar: None,
-                joined: Utc::now().date_naive(),
-            })
+            .schedule_deletion(
+                user.username.clone(),
+                Some(at),
+                form.posts == PostDisposal::Anonymize,
+            )
             .await?;
+        self.session_repository
+            .delete_sessions_of_user(user.username.clone(), None)
+            .await?;
+
+        Ok(at)
+    }
+
+    pub async fn restore_account(&self, user: &User) -> AppResult<()> {
+        self.user_repository
+            .schedule_deletion(user.username.clone(), None, false)
+            .await
+    }
+
+    /// Deletes every account whose grace period is over and returns their names.
+    pub async fn purge_deleted_accounts(&self) -> AppResult<Vec<String>> {
+        let now = Utc::now().naive_utc();
+        let mut purged = Vec::new();
+
+        for user in self.user_repository.get_users_due_for_deletion(now).await? {
+            if self
+                .user_repository
+                .purge_user(user.username.clone(), now)
+                .await?
+            {
+                purged.push(user.username);
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Admins can't change their own role, so there is always at least one left.
+    pub async fn change_role(&self, actor: &User, username: String, role: Role) -> AppResult<()> {
+        if actor.username == username {
+            return Err(ForbiddenError("You can't change your own role".to_string()));
+        }
+
+        if !self.user_repository.set_role(username, role).await? {
+            return Err(NotFoundError("Could not find user".to_string()));
+        }
+
         Ok(())
     }
 
     pub async fn update_user_avatar(&self, username: String, avatar: Vec<u8>) -> AppResult<()> {
+        self.validation_rules.validate_avatar(&avatar)?;
+
         let payload = UpdateUser {
             username,
             password: None,
@@ -94,17 +337,13 @@ impl UserService {
         Ok(())
     }
 
-    pub async fn get_user_avatar(&self, username: String) -> AppResult<Option<Vec<u8>>> {
-        let user = self.get_user_by_username(username).await?;
-        match user {
-            None => Ok(None),
-            Some(u) => {
-                if let Some(avatar) = u.avatar {
-                    Ok(Some(avatar))
-                } else {
-                    Ok(None)
-                }
-            }
+    pub async fn update_profile(&self, username: String, form: UpdateProfileForm) -> AppResult<()> {
+        let profile = UpdateProfile::from(form);
+        if profile.is_empty() {
+            return Ok(());
         }
+        self.validation_rules.validate_profile(&profile)?;
+
+        self.user_repository.update_profile(username, profile).await
     }
 }
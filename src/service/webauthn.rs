@@ -0,0 +1,600 @@
+use crate::config::{env_or, public_url};
+use crate::error::AppError::{InternalError, LoginError, NotFoundError, ValidationError};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::model::webauthn::{
+    AuthenticationChallengeDTO, RegistrationChallengeDTO, WebauthnChallenge, WebauthnCredential,
+};
+use crate::repository::user::UserRepository;
+use crate::repository::webauthn::WebauthnRepository;
+use crate::token::{generate_token, hash_token};
+use chrono::{Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use webauthn_rs::prelude::{
+    Passkey, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+    RegisterPublicKeyCredential, Url, Uuid,
+};
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+const REGISTRATION: &str = "registration";
+const AUTHENTICATION: &str = "authentication";
+
+/// Namespace for the stable WebAuthn user handle derived from the username.
+const USER_HANDLE_NAMESPACE: Uuid = Uuid::from_u128(0x5d1c_3f0e_8a0b_4c6e_9f31_27b4_d8a6_e912);
+
+pub struct WebauthnService {
+    webauthn: Webauthn,
+    user_repository: UserRepository,
+    webauthn_repository: WebauthnRepository,
+    challenge_ttl: Duration,
+}
+
+impl WebauthnService {
+    pub fn new(user_repository: UserRepository, webauthn_repository: WebauthnRepository) -> Self {
+        let origin = Url::parse(&env_or("WEBAUTHN_RP_ORIGIN", public_url())).unwrap();
+        let rp_id = env_or(
+            "WEBAUTHN_RP_ID",
+            origin.host_str().unwrap_or("localhost").to_string(),
+        );
+        let rp_name = env_or("WEBAUTHN_RP_NAME", "RustyPosts".to_string());
+
+        let webauthn = WebauthnBuilder::new(&rp_id, &origin)
+            .unwrap()
+            .rp_name(&rp_name)
+            .build()
+            .unwrap();
+
+        Self {
+            webauthn,
+            user_repository,
+            webauthn_repository,
+            challenge_ttl: Duration::seconds(env_or("WEBAUTHN_CHALLENGE_TTL_SECONDS", 300)),
+        }
+    }
+
+    pub async fn get_credentials(&self, username: String) -> AppResult<Vec<WebauthnCredential>> {
+        self.webauthn_repository
+            .get_credentials_of_user(username)
+            .await
+    }
+
+    pub async fn delete_credential(&self, username: String, credential_id: String) -> AppResult<()> {
+        let deleted = self
+            .webauthn_repository
+            .delete_credential(username, credential_id)
+            .await?;
+
+        if deleted {
+            Ok(())
+        } else {
+            Err(NotFoundError("Could not find passkey".to_string()))
+        }
+    }
+
+    pub async fn start_registration(&self, username: String) -> AppResult<RegistrationChallengeDTO> {
+        let exclude = self
+            .load_passkeys(username.clone())
+            .await?
+            .iter()
+            .map(|passkey| passkey.cred_id().clone())
+            .collect();
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_registration(
+                Uuid::new_v5(&USER_HANDLE_NAMESPACE, username.as_bytes()),
+                &username,
+                &username,
+                Some(exclude),
+            )
+            .map_err(|e| InternalError(e.to_string()))?;
+
+        let challenge_id = self
+            .store_challenge(username, REGISTRATION, serialize(&state)?)
+            .await?;
+
+        Ok(RegistrationChallengeDTO {
+            challenge_id,
+            options,
+        })
+    }
+
+    pub async fn finish_registration(
+        &self,
+        username: String,
+        challenge_id: String,
+        name: String,
+        credential: RegisterPublicKeyCredential,
+    ) -> AppResult<WebauthnCredential> {
+        let name = name.trim().to_string();
+        if name.is_empty() || name.chars().count() > 64 {
+            return Err(ValidationError(vec![FieldError::new(
+                "name",
+                "Name must be between 1 and 64 characters long",
+            )]));
+        }
+
+        let challenge = self
+            .webauthn_repository
+            .take_challenge(hash_token(&challenge_id), REGISTRATION, Utc::now().naive_utc())
+            .await?
+            .filter(|challenge| challenge.username == username)
+            .ok_or_else(invalid_challenge)?;
+        let state: PasskeyRegistration = deserialize(&challenge.state)?;
+
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(&credential, &state)
+            .map_err(|e| {
+                ValidationError(vec![FieldError::new(
+                    "credential",
+                    format!("Credential was rejected: {e}"),
+                )])
+            })?;
+
+        let stored = WebauthnCredential {
+            credential_id: hex::encode(passkey.cred_id().as_slice()),
+            username,
+            name,
+            passkey: serialize(&passkey)?,
+            sign_count: 0,
+            created_at: Utc::now().naive_utc(),
+            last_used_at: None,
+        };
+        self.webauthn_repository
+            .add_credential(stored.clone())
+            .await?;
+
+        Ok(stored)
+    }
+
+    pub async fn start_authentication(
+        &self,
+        username: String,
+    ) -> AppResult<AuthenticationChallengeDTO> {
+        let user = self
+            .user_repository
+            .get_user_by_username(username)
+            .await?
+            .ok_or(LoginError)?;
+
+        let passkeys = self.load_passkeys(user.username.clone()).await?;
+        if passkeys.is_empty() {
+            return Err(LoginError);
+        }
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| InternalError(e.to_string()))?;
+
+        let challenge_id = self
+            .store_challenge(user.username, AUTHENTICATION, serialize(&state)?)
+            .await?;
+
+        Ok(AuthenticationChallengeDTO {
+            challenge_id,
+            options,
+        })
+    }
+
+    /// Verifies an assertion and returns the username it authenticates. The stored sign counter
+    /// is advanced so cloned authenticators get noticed.
+    pub async fn finish_authentication(
+        &self,
+        challenge_id: String,
+        credential: PublicKeyCredential,
+    ) -> AppResult<String> {
+        let challenge = self
+            .webauthn_repository
+            .take_challenge(hash_token(&challenge_id), AUTHENTICATION, Utc::now().naive_utc())
+            .await?
+            .ok_or(LoginError)?;
+        let state: PasskeyAuthentication = deserialize(&challenge.state)?;
+
+        let result = self
+            .webauthn
+            .finish_passkey_authentication(&credential, &state)
+            .map_err(|_| LoginError)?;
+
+        let credential_id = hex::encode(result.cred_id().as_slice());
+        let stored = self
+            .webauthn_repository
+            .get_credentials_of_user(challenge.username.clone())
+            .await?
+            .into_iter()
+            .find(|stored| stored.credential_id == credential_id)
+            .ok_or(LoginError)?;
+
+        let mut passkey: Passkey = deserialize(&stored.passkey)?;
+        passkey.update_credential(&result);
+
+        self.webauthn_repository
+            .update_credential_usage(
+                credential_id,
+                serialize(&passkey)?,
+                i64::from(result.counter()),
+                Utc::now().naive_utc(),
+            )
+            .await?;
+
+        Ok(challenge.username)
+    }
+
+    async fn load_passkeys(&self, username: String) -> AppResult<Vec<Passkey>> {
+        self.webauthn_repository
+            .get_credentials_of_user(username)
+            .await?
+            .iter()
+            .map(|credential| deserialize(&credential.passkey))
+            .collect()
+    }
+
+    /// Persists the ceremony state and returns the opaque id the client has to send back.
+    async fn store_challenge(
+        &self,
+        username: String,
+        ceremony: &str,
+        state: String,
+    ) -> AppResult<String> {
+        let challenge_id = generate_token();
+        let now = Utc::now().naive_utc();
+
+        self.webauthn_repository
+            .add_challenge(
+                WebauthnChallenge {
+                    challenge_id: hash_token(&challenge_id),
+                    username,
+                    ceremony: ceremony.to_string(),
+                    state,
+                    expires_at: now + self.challenge_ttl,
+                },
+                now,
+            )
+            .await?;
+
+        Ok(challenge_id)
+    }
+}
+
+fn serialize<T: Serialize>(value: &T) -> AppResult<String> {
+    serde_json::to_string(value).map_err(|e| InternalError(e.to_string()))
+}
+
+fn deserialize<T: DeserializeOwned>(value: &str) -> AppResult<T> {
+    serde_json::from_str(value).map_err(|e| InternalError(e.to_string()))
+}
+
+fn invalid_challenge() -> AppError {
+    ValidationError(vec![FieldError::new(
+        "challengeId",
+        "Registration has expired, please try again",
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{test_pool, test_user};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::sign::Signer;
+    use serde_cbor_2::Value;
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+    use webauthn_rs::prelude::Base64UrlSafeData;
+
+    const USER_PRESENT: u8 = 0x01;
+    const USER_VERIFIED: u8 = 0x04;
+    const ATTESTED_CREDENTIAL: u8 = 0x40;
+
+    /// A passkey held in memory: one P-256 key, "none" attestation and user verification on
+    /// every ceremony.
+    #[derive(Clone)]
+    struct SoftAuthenticator {
+        key: PKey<Private>,
+        credential_id: Vec<u8>,
+        counter: u32,
+        origin: String,
+    }
+
+    impl SoftAuthenticator {
+        fn new() -> Self {
+            let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+            let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+            let origin = Url::parse(&env_or("WEBAUTHN_RP_ORIGIN", public_url())).unwrap();
+
+            Self {
+                key,
+                credential_id: generate_token().into_bytes()[..16].to_vec(),
+                counter: 0,
+                origin: origin.origin().ascii_serialization(),
+            }
+        }
+
+        fn register(&self, options: &RegistrationChallengeDTO) -> RegisterPublicKeyCredential {
+            let options = serde_json::to_value(&options.options).unwrap();
+            let rp_id = options["publicKey"]["rp"]["id"].as_str().unwrap();
+            let challenge = options["publicKey"]["challenge"].as_str().unwrap();
+
+            let mut auth_data =
+                self.auth_data(rp_id, USER_PRESENT | USER_VERIFIED | ATTESTED_CREDENTIAL);
+            auth_data.extend_from_slice(&[0; 16]);
+            auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            auth_data.extend_from_slice(&self.credential_id);
+            auth_data.extend(serde_cbor_2::to_vec(&self.cose_key()).unwrap());
+
+            let attestation = Value::Map(BTreeMap::from([
+                (text("fmt"), text("none")),
+                (text("attStmt"), Value::Map(BTreeMap::new())),
+                (text("authData"), Value::Bytes(auth_data)),
+            ]));
+
+            serde_json::from_value(json!({
+                "id": base64(&self.credential_id),
+                "rawId": base64(&self.credential_id),
+                "type": "public-key",
+                "response": {
+                    "attestationObject": base64(&serde_cbor_2::to_vec(&attestation).unwrap()),
+                    "clientDataJSON": base64(&self.client_data("webauthn.create", challenge)),
+                },
+            }))
+            .unwrap()
+        }
+
+        fn authenticate(&mut self, options: &AuthenticationChallengeDTO) -> PublicKeyCredential {
+            let options = serde_json::to_value(&options.options).unwrap();
+            let rp_id = options["publicKey"]["rpId"].as_str().unwrap();
+            let challenge = options["publicKey"]["challenge"].as_str().unwrap();
+
+            self.counter += 1;
+            let auth_data = self.auth_data(rp_id, USER_PRESENT | USER_VERIFIED);
+            let client_data = self.client_data("webauthn.get", challenge);
+
+            let mut signer = Signer::new(MessageDigest::sha256(), &self.key).unwrap();
+            signer.update(&auth_data).unwrap();
+            signer.update(&Sha256::digest(&client_data)).unwrap();
+            let signature = signer.sign_to_vec().unwrap();
+
+            serde_json::from_value(json!({
+                "id": base64(&self.credential_id),
+                "rawId": base64(&self.credential_id),
+                "type": "public-key",
+                "response": {
+                    "authenticatorData": base64(&auth_data),
+                    "clientDataJSON": base64(&client_data),
+                    "signature": base64(&signature),
+                    "userHandle": null,
+                },
+            }))
+            .unwrap()
+        }
+
+        fn auth_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+            let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend_from_slice(&self.counter.to_be_bytes());
+            data
+        }
+
+        fn client_data(&self, kind: &str, challenge: &str) -> Vec<u8> {
+            serde_json::to_vec(&json!({
+                "type": kind,
+                "challenge": challenge,
+                "origin": self.origin,
+                "crossOrigin": false,
+            }))
+            .unwrap()
+        }
+
+        /// The public key as an ES256 COSE_Key.
+        fn cose_key(&self) -> Value {
+            let ec_key = self.key.ec_key().unwrap();
+            let mut x = openssl::bn::BigNum::new().unwrap();
+            let mut y = openssl::bn::BigNum::new().unwrap();
+            let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+            ec_key
+                .public_key()
+                .affine_coordinates(ec_key.group(), &mut x, &mut y, &mut ctx)
+                .unwrap();
+
+            Value::Map(BTreeMap::from([
+                (Value::Integer(1), Value::Integer(2)),
+                (Value::Integer(3), Value::Integer(-7)),
+                (Value::Integer(-1), Value::Integer(1)),
+                (
+                    Value::Integer(-2),
+                    Value::Bytes(x.to_vec_padded(32).unwrap()),
+                ),
+                (
+                    Value::Integer(-3),
+                    Value::Bytes(y.to_vec_padded(32).unwrap()),
+                ),
+            ]))
+        }
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    fn base64(data: &[u8]) -> serde_json::Value {
+        serde_json::to_value(Base64UrlSafeData::from(data.to_vec())).unwrap()
+    }
+
+    fn service(pool: &deadpool_diesel::postgres::Pool) -> WebauthnService {
+        WebauthnService::new(
+            UserRepository::new(pool.clone()),
+            WebauthnRepository::new(pool.clone()),
+        )
+    }
+
+    /// Registers a new passkey for `username` and returns the authenticator holding it.
+    async fn register(service: &WebauthnService, username: &str) -> SoftAuthenticator {
+        let authenticator = SoftAuthenticator::new();
+        let options = service
+            .start_registration(username.to_string())
+            .await
+            .unwrap();
+        service
+            .finish_registration(
+                username.to_string(),
+                options.challenge_id.clone(),
+                "Soft key".to_string(),
+                authenticator.register(&options),
+            )
+            .await
+            .unwrap();
+
+        authenticator
+    }
+
+    async fn sign_count(service: &WebauthnService, username: &str) -> i64 {
+        service.get_credentials(username.to_string()).await.unwrap()[0].sign_count
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn registers_and_logs_in_with_a_passkey() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "passkey").await;
+
+        let mut authenticator = register(&service, &username).await;
+        assert_eq!(sign_count(&service, &username).await, 0);
+
+        for expected_count in 1..=2 {
+            let options = service
+                .start_authentication(username.clone())
+                .await
+                .unwrap();
+            let credential = authenticator.authenticate(&options);
+            let authenticated = service
+                .finish_authentication(options.challenge_id, credential)
+                .await
+                .unwrap();
+
+            assert_eq!(authenticated, username);
+            assert_eq!(sign_count(&service, &username).await, expected_count);
+        }
+
+        UserRepository::new(pool)
+            .delete_user(username)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn rejects_a_cloned_authenticator() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "passkey").await;
+
+        let mut authenticator = register(&service, &username).await;
+        let mut clone = authenticator.clone();
+
+        let options = service
+            .start_authentication(username.clone())
+            .await
+            .unwrap();
+        let credential = authenticator.authenticate(&options);
+        service
+            .finish_authentication(options.challenge_id, credential)
+            .await
+            .unwrap();
+
+        // The clone still sits at the old counter, so its next signature doesn't advance it.
+        let options = service
+            .start_authentication(username.clone())
+            .await
+            .unwrap();
+        let credential = clone.authenticate(&options);
+        let result = service
+            .finish_authentication(options.challenge_id, credential)
+            .await;
+
+        assert!(matches!(result, Err(LoginError)));
+        assert_eq!(sign_count(&service, &username).await, 1);
+
+        UserRepository::new(pool)
+            .delete_user(username)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn rejects_an_assertion_for_another_challenge() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "passkey").await;
+
+        let mut authenticator = register(&service, &username).await;
+        let signed = service
+            .start_authentication(username.clone())
+            .await
+            .unwrap();
+        let submitted = service
+            .start_authentication(username.clone())
+            .await
+            .unwrap();
+
+        let credential = authenticator.authenticate(&signed);
+        let result = service
+            .finish_authentication(submitted.challenge_id, credential.clone())
+            .await;
+        assert!(matches!(result, Err(LoginError)));
+
+        // Each challenge can only be answered once, even with a valid assertion.
+        service
+            .finish_authentication(signed.challenge_id.clone(), credential.clone())
+            .await
+            .unwrap();
+        let replayed = service
+            .finish_authentication(signed.challenge_id, credential)
+            .await;
+        assert!(matches!(replayed, Err(LoginError)));
+
+        UserRepository::new(pool)
+            .delete_user(username)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn rejects_a_registration_for_another_challenge() {
+        let pool = test_pool();
+        let service = service(&pool);
+        let username = test_user(&pool, "passkey").await;
+
+        let authenticator = SoftAuthenticator::new();
+        let signed = service.start_registration(username.clone()).await.unwrap();
+        let submitted = service.start_registration(username.clone()).await.unwrap();
+
+        let result = service
+            .finish_registration(
+                username.clone(),
+                submitted.challenge_id,
+                "Soft key".to_string(),
+                authenticator.register(&signed),
+            )
+            .await;
+
+        assert!(matches!(result, Err(ValidationError(_))));
+        assert!(service
+            .get_credentials(username.clone())
+            .await
+            .unwrap()
+            .is_empty());
+
+        UserRepository::new(pool)
+            .delete_user(username)
+            .await
+            .unwrap();
+    }
+}
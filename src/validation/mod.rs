@@ -0,0 +1,656 @@
+use crate::config::{env_list_or, env_or};
+use crate::error::AppError::ValidationError;
+use crate::error::{AppResult, FieldError};
+use crate::model::user::UpdateProfile;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+const DEFAULT_RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "api",
+    "assets",
+    "auth",
+    "autocomplete",
+    "login",
+    "logout",
+    "me",
+    "moderator",
+    "null",
+    "root",
+    "signup",
+    "support",
+    "system",
+    "undefined",
+];
+
+const DEFAULT_IMAGE_TYPES: &[&str] = &["png", "jpeg", "gif", "webp"];
+
+/// Limits applied to user supplied input. Every value can be overridden through the
+/// environment, see [`ValidationRules::from_env`].
+pub struct ValidationRules {
+    pub username_min_length: usize,
+    pub username_max_length: usize,
+    pub reserved_usernames: HashSet<String>,
+    /// Days a name given up through a rename stays reserved for its former owner.
+    pub username_reservation_days: i64,
+    pub password_min_length: usize,
+    pub password_max_length: usize,
+    pub password_min_entropy_bits: f64,
+    pub password_breach_check: bool,
+    pub post_title_max_length: usize,
+    pub post_body_max_length: usize,
+    pub display_name_max_length: usize,
+    pub bio_max_length: usize,
+    pub profile_max_links: usize,
+    pub location_max_length: usize,
+    pub pronouns_max_length: usize,
+    pub image_max_bytes: usize,
+    pub image_allowed_types: HashSet<String>,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            username_min_length: 3,
+            username_max_length: 32,
+            reserved_usernames: DEFAULT_RESERVED_USERNAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            username_reservation_days: 90,
+            password_min_length: 8,
+            password_max_length: 128,
+            password_min_entropy_bits: 40.0,
+            password_breach_check: true,
+            post_title_max_length: 200,
+            post_body_max_length: 20_000,
+            display_name_max_length: 64,
+            bio_max_length: 2_000,
+            profile_max_links: 5,
+            location_max_length: 100,
+            pronouns_max_length: 32,
+            image_max_bytes: 5 * 1024 * 1024,
+            image_allowed_types: DEFAULT_IMAGE_TYPES
+                .iter()
+                .map(|kind| kind.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ValidationRules {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            username_min_length: env_or("USERNAME_MIN_LENGTH", defaults.username_min_length),
+            username_max_length: env_or("USERNAME_MAX_LENGTH", defaults.username_max_length),
+            reserved_usernames: env_list_or("RESERVED_USERNAMES", DEFAULT_RESERVED_USERNAMES)
+                .into_iter()
+                .map(|name| name.to_lowercase())
+                .collect(),
+            username_reservation_days: env_or(
+                "USERNAME_RESERVATION_DAYS",
+                defaults.username_reservation_days,
+            ),
+            password_min_length: env_or("PASSWORD_MIN_LENGTH", defaults.password_min_length),
+            password_max_length: env_or("PASSWORD_MAX_LENGTH", defaults.password_max_length),
+            password_min_entropy_bits: env_or(
+                "PASSWORD_MIN_ENTROPY_BITS",
+                defaults.password_min_entropy_bits,
+            ),
+            password_breach_check: env_or("PASSWORD_BREACH_CHECK", defaults.password_breach_check),
+            post_title_max_length: env_or("POST_TITLE_MAX_LENGTH", defaults.post_title_max_length),
+            post_body_max_length: env_or("POST_BODY_MAX_LENGTH", defaults.post_body_max_length),
+            display_name_max_length: env_or(
+                "DISPLAY_NAME_MAX_LENGTH",
+                defaults.display_name_max_length,
+            ),
+            bio_max_length: env_or("BIO_MAX_LENGTH", defaults.bio_max_length),
+            profile_max_links: env_or("PROFILE_MAX_LINKS", defaults.profile_max_links),
+            location_max_length: env_or("LOCATION_MAX_LENGTH", defaults.location_max_length),
+            pronouns_max_length: env_or("PRONOUNS_MAX_LENGTH", defaults.pronouns_max_length),
+            image_max_bytes: env_or("IMAGE_MAX_BYTES", defaults.image_max_bytes),
+            image_allowed_types: env_list_or("IMAGE_ALLOWED_TYPES", DEFAULT_IMAGE_TYPES)
+                .into_iter()
+                .map(|kind| kind.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Largest request body accepted: a post with an image at the size limit, its text fields
+    /// at their length limits and room for the multipart framing.
+    pub fn request_body_limit(&self) -> usize {
+        const MULTIPART_OVERHEAD: usize = 64 * 1024;
+        // Every character can take up to four bytes in UTF-8.
+        let text = 4 * (self.post_title_max_length + self.post_body_max_length);
+
+        self.image_max_bytes + text + MULTIPART_OVERHEAD
+    }
+
+    pub fn validate_sign_up(
+        &self,
+        username: &str,
+        password: &str,
+        email: Option<&str>,
+    ) -> AppResult<()> {
+        let mut validator = Validator::new();
+        self.check_username(&mut validator, "username", username);
+        self.check_password(&mut validator, "password", password, username);
+        if let Some(email) = email {
+            validator.email("email", email);
+        }
+        validator.finish()
+    }
+
+    pub fn validate_username(&self, username: &str) -> AppResult<()> {
+        let mut validator = Validator::new();
+        self.check_username(&mut validator, "username", username);
+        validator.finish()
+    }
+
+    pub fn validate_email(&self, email: &str) -> AppResult<()> {
+        let mut validator = Validator::new();
+        validator.email("email", email);
+        validator.finish()
+    }
+
+    pub fn validate_new_password(&self, username: &str, password: &str) -> AppResult<()> {
+        let mut validator = Validator::new();
+        self.check_password(&mut validator, "newPassword", password, username);
+        validator.finish()
+    }
+
+    pub fn validate_post(&self, title: &str, body: &str, image: Option<&[u8]>) -> AppResult<()> {
+        let mut validator = Validator::new();
+        validator
+            .not_blank("title", title, "A title is required")
+            .max_length("title", title, self.post_title_max_length)
+            .not_blank("body", body, "A body is required")
+            .max_length("body", body, self.post_body_max_length);
+        if let Some(image) = image {
+            self.check_image(&mut validator, "image", image);
+        }
+        validator.finish()
+    }
+
+    pub fn validate_profile(&self, profile: &UpdateProfile) -> AppResult<()> {
+        let mut validator = Validator::new();
+        if let Some(Some(display_name)) = &profile.display_name {
+            validator
+                .max_length("displayName", display_name, self.display_name_max_length)
+                .check(
+                    "displayName",
+                    !display_name.chars().any(char::is_control),
+                    "Must not contain control characters",
+                );
+        }
+        if let Some(Some(bio)) = &profile.bio {
+            validator.max_length("bio", bio, self.bio_max_length);
+        }
+        if let Some(links) = &profile.links {
+            validator.check(
+                "links",
+                links.len() <= self.profile_max_links,
+                format!("At most {} links are allowed", self.profile_max_links),
+            );
+            for link in links {
+                validator.url("links", link);
+            }
+        }
+        if let Some(Some(location)) = &profile.location {
+            validator
+                .max_length("location", location, self.location_max_length)
+                .check(
+                    "location",
+                    !location.chars().any(char::is_control),
+                    "Must not contain control characters",
+                );
+        }
+        if let Some(Some(pronouns)) = &profile.pronouns {
+            validator
+                .max_length("pronouns", pronouns, self.pronouns_max_length)
+                .check(
+                    "pronouns",
+                    !pronouns.chars().any(char::is_control),
+                    "Must not contain control characters",
+                );
+        }
+        validator.finish()
+    }
+
+    pub fn validate_avatar(&self, avatar: &[u8]) -> AppResult<()> {
+        let mut validator = Validator::new();
+        self.check_image(&mut validator, "avatar", avatar);
+        validator.finish()
+    }
+
+    fn check_username(&self, validator: &mut Validator, field: &str, username: &str) {
+        validator
+            .min_length(field, username, self.username_min_length)
+            .max_length(field, username, self.username_max_length)
+            .check(
+                field,
+                username
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "Allowed: letters, numbers, underscores",
+            )
+            .check(
+                field,
+                !self.reserved_usernames.contains(&username.to_lowercase()),
+                "This username is reserved",
+            );
+    }
+
+    fn check_password(
+        &self,
+        validator: &mut Validator,
+        field: &str,
+        password: &str,
+        username: &str,
+    ) {
+        validator
+            .min_length(field, password, self.password_min_length)
+            .max_length(field, password, self.password_max_length)
+            .check(
+                field,
+                !password.eq_ignore_ascii_case(username),
+                "Password must not match the username",
+            )
+            .check(
+                field,
+                password_entropy_bits(password) >= self.password_min_entropy_bits,
+                "Password is too weak, mix in more character types or make it longer",
+            );
+
+        if self.password_breach_check {
+            validator.check(
+                field,
+                !is_common_password(password),
+                "Password appears in a list of commonly breached passwords",
+            );
+        }
+    }
+
+    fn check_image(&self, validator: &mut Validator, field: &str, data: &[u8]) {
+        validator.check(
+            field,
+            data.len() <= self.image_max_bytes,
+            format!("Image must be at most {} bytes", self.image_max_bytes),
+        );
+
+        let allowed = detect_image_type(data)
+            .is_some_and(|kind| self.image_allowed_types.contains(kind));
+        validator.check(field, allowed, "Unsupported file type");
+    }
+}
+
+/// Collects every failed rule so that the caller gets all field errors in one response.
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(
+        &mut self,
+        field: &str,
+        condition: bool,
+        message: impl Into<String>,
+    ) -> &mut Self {
+        if !condition {
+            self.errors.push(FieldError::new(field, message));
+        }
+        self
+    }
+
+    pub fn not_blank(&mut self, field: &str, value: &str, message: &str) -> &mut Self {
+        self.check(field, !value.trim().is_empty(), message)
+    }
+
+    pub fn min_length(&mut self, field: &str, value: &str, min: usize) -> &mut Self {
+        self.check(
+            field,
+            value.chars().count() >= min,
+            format!("Must be at least {min} characters long"),
+        )
+    }
+
+    pub fn max_length(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        self.check(
+            field,
+            value.chars().count() <= max,
+            format!("Must be at most {max} characters long"),
+        )
+    }
+
+    /// Deliberately loose: the verification mail is the real check.
+    pub fn email(&mut self, field: &str, value: &str) -> &mut Self {
+        let well_formed = value.len() <= 254
+            && !value.chars().any(char::is_whitespace)
+            && value.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+            });
+        self.check(field, well_formed, "Email address is invalid")
+    }
+
+    /// Absolute `http`/`https` links with a dotted host, nothing a browser could run.
+    pub fn url(&mut self, field: &str, value: &str) -> &mut Self {
+        let host = value
+            .strip_prefix("https://")
+            .or_else(|| value.strip_prefix("http://"))
+            .map(|rest| rest.split(['/', '?', '#']).next().unwrap_or_default());
+        let well_formed = value.len() <= 2048
+            && !value.chars().any(|c| c.is_whitespace() || c.is_control())
+            && host.is_some_and(|host| {
+                !host.contains('@')
+                    && host.contains('.')
+                    && !host.starts_with('.')
+                    && !host.ends_with('.')
+            });
+        self.check(
+            field,
+            well_formed,
+            format!("{value} is not a valid http(s) link"),
+        )
+    }
+
+    pub fn finish(self) -> AppResult<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError(self.errors))
+        }
+    }
+}
+
+/// The form addresses are stored in. Mail providers treat the local part case-insensitively in
+/// practice, so `A@x.com` and `a@x.com` are one mailbox and may only be claimed once.
+pub fn normalize_email(address: &str) -> String {
+    address.trim().to_lowercase()
+}
+
+/// Rough brute-force entropy estimate: the size of the character pools in use raised to the
+/// password length. Repeated characters only count twice so `aaaaaaaaaaaa` stays weak.
+pub fn password_entropy_bits(password: &str) -> f64 {
+    let mut pool = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let distinct = password.chars().collect::<HashSet<_>>().len();
+    let effective_length = password.chars().count().min(distinct * 2);
+
+    effective_length as f64 * f64::from(pool).log2()
+}
+
+pub fn is_common_password(password: &str) -> bool {
+    static LIST: OnceLock<HashSet<String>> = OnceLock::new();
+
+    LIST.get_or_init(|| {
+        COMMON_PASSWORDS
+            .lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty())
+            .collect()
+    })
+    .contains(&password.to_lowercase())
+}
+
+/// Identifies an image by its magic bytes, ignoring whatever the client claimed.
+pub fn detect_image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+/// The `Content-Type` to serve a stored image with.
+pub fn image_content_type(data: &[u8]) -> &'static str {
+    match detect_image_type(data) {
+        Some("png") => "image/png",
+        Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(result: AppResult<()>) -> Vec<String> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(ValidationError(errors)) => errors.into_iter().map(|error| error.field).collect(),
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    fn email_is_valid(value: &str) -> bool {
+        let mut validator = Validator::new();
+        validator.email("email", value);
+        validator.finish().is_ok()
+    }
+
+    fn url_is_valid(value: &str) -> bool {
+        let mut validator = Validator::new();
+        validator.url("links", value);
+        validator.finish().is_ok()
+    }
+
+    #[test]
+    fn validator_collects_every_failed_rule() {
+        let mut validator = Validator::new();
+        validator
+            .not_blank("title", "  ", "A title is required")
+            .min_length("username", "ab", 3)
+            .max_length("body", "abcd", 3)
+            .max_length("body", "abc", 3);
+
+        assert_eq!(fields(validator.finish()), ["title", "username", "body"]);
+    }
+
+    #[test]
+    fn lengths_count_characters_not_bytes() {
+        let mut validator = Validator::new();
+        validator
+            .max_length("title", "ééé", 3)
+            .min_length("title", "ééé", 3);
+
+        assert!(validator.finish().is_ok());
+    }
+
+    #[test]
+    fn email_accepts_plain_addresses() {
+        assert!(email_is_valid("user@example.com"));
+        assert!(email_is_valid("first.last+tag@mail.example.org"));
+    }
+
+    #[test]
+    fn email_rejects_malformed_addresses() {
+        for value in [
+            "",
+            "user",
+            "@example.com",
+            "user@localhost",
+            "user@.example.com",
+            "user@example.com.",
+            "user@@example.com",
+            "us er@example.com",
+        ] {
+            assert!(!email_is_valid(value), "{value} should be rejected");
+        }
+        assert!(!email_is_valid(&format!("{}@example.com", "a".repeat(250))));
+    }
+
+    #[test]
+    fn emails_are_stored_trimmed_and_lowercase() {
+        assert_eq!(normalize_email("  Rusty@Example.COM "), "rusty@example.com");
+    }
+
+    #[test]
+    fn url_accepts_http_and_https_links() {
+        assert!(url_is_valid("https://example.com"));
+        assert!(url_is_valid("http://blog.example.com/posts?page=2#top"));
+    }
+
+    #[test]
+    fn url_rejects_other_schemes_and_hosts() {
+        for value in [
+            "javascript:alert(1)",
+            "data:text/html,hi",
+            "ftp://example.com",
+            "example.com",
+            "https://localhost",
+            "https://.example.com",
+            "https://example.com.",
+            "https://user@example.com",
+            "https://example.com/a b",
+            "https://example.com/\u{7}",
+        ] {
+            assert!(!url_is_valid(value), "{value} should be rejected");
+        }
+        assert!(!url_is_valid(&format!(
+            "https://example.com/{}",
+            "a".repeat(2048)
+        )));
+    }
+
+    #[test]
+    fn usernames_follow_the_rules() {
+        let rules = ValidationRules::default();
+
+        assert!(rules.validate_username("rusty_42").is_ok());
+        assert_eq!(fields(rules.validate_username("ab")), ["username"]);
+        assert_eq!(fields(rules.validate_username("rusty-42")), ["username"]);
+        assert_eq!(fields(rules.validate_username("Admin")), ["username"]);
+        assert_eq!(
+            fields(rules.validate_username(&"a".repeat(33))),
+            ["username"]
+        );
+    }
+
+    #[test]
+    fn sign_up_reports_every_invalid_field() {
+        let rules = ValidationRules::default();
+
+        assert!(rules
+            .validate_sign_up(
+                "rusty",
+                "correct Horse 9 battery",
+                Some("rusty@example.com")
+            )
+            .is_ok());
+        assert_eq!(
+            fields(rules.validate_sign_up("a!", "password", Some("nope"))),
+            ["username", "username", "password", "password", "email"]
+        );
+    }
+
+    #[test]
+    fn passwords_must_not_match_the_username() {
+        let rules = ValidationRules::default();
+
+        assert_eq!(
+            fields(rules.validate_new_password("Tr0ub4dor_and_3", "tr0ub4dor_AND_3")),
+            ["newPassword"]
+        );
+    }
+
+    #[test]
+    fn entropy_grows_with_length_and_character_pools() {
+        assert_eq!(password_entropy_bits(""), 0.0);
+        assert!(password_entropy_bits("abcdefgh") < password_entropy_bits("abcdefghij"));
+        assert!(password_entropy_bits("abcdefgh") < password_entropy_bits("abcdEFG1"));
+        // Repeating a character doesn't make a password stronger past the second time.
+        assert_eq!(
+            password_entropy_bits("aaaaaaaaaaaa"),
+            password_entropy_bits("aa")
+        );
+    }
+
+    #[test]
+    fn common_passwords_are_matched_case_insensitively() {
+        assert!(is_common_password("password"));
+        assert!(is_common_password("QWERTY"));
+        assert!(!is_common_password("correct Horse 9 battery"));
+    }
+
+    #[test]
+    fn images_are_detected_by_their_magic_bytes() {
+        assert_eq!(detect_image_type(b"\x89PNG\r\n\x1a\n...."), Some("png"));
+        assert_eq!(detect_image_type(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("jpeg"));
+        assert_eq!(detect_image_type(b"GIF89a...."), Some("gif"));
+        assert_eq!(detect_image_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(detect_image_type(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(detect_image_type(b"<svg></svg>"), None);
+
+        assert_eq!(image_content_type(&[0xFF, 0xD8, 0xFF, 0xE0]), "image/jpeg");
+        assert_eq!(
+            image_content_type(b"<svg></svg>"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn images_are_checked_for_size_and_type() {
+        let rules = ValidationRules {
+            image_max_bytes: 16,
+            ..ValidationRules::default()
+        };
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+        assert!(rules.validate_avatar(&png).is_ok());
+        assert_eq!(fields(rules.validate_avatar(b"<svg></svg>")), ["avatar"]);
+        assert_eq!(
+            fields(rules.validate_avatar(&[png, vec![0; 16]].concat())),
+            ["avatar"]
+        );
+    }
+
+    #[test]
+    fn the_body_limit_fits_the_largest_allowed_post() {
+        let rules = ValidationRules::default();
+
+        assert!(rules.request_body_limit() > rules.image_max_bytes + rules.post_body_max_length);
+    }
+}